@@ -0,0 +1,250 @@
+use cardano::{
+    address::ExtendedAddr,
+    config::{NetworkMagic, ProtocolMagic},
+    hdwallet::{DerivationScheme, Signature, XPrv, XPub},
+    tx::{TxId, TxInWitness},
+    util::hex,
+};
+use cbor_event::de::Deserializer;
+use serde_json;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// how long to wait for the agent to answer
+#[cfg(unix)]
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+use super::{
+    key_id, network_magic_to_option, socket_path, Error, Request, Response, Result, UnlockedWallet,
+};
+use utils::secret::Secret;
+use wallet::{state::lookup::Address, Wallet};
+
+/// connection to the key agent of a root directory
+#[derive(Debug, Clone)]
+pub struct Client {
+    path: PathBuf,
+}
+impl Client {
+    pub fn new<P: AsRef<Path>>(root_dir: P) -> Self {
+        Client {
+            path: socket_path(root_dir),
+        }
+    }
+
+    fn request(&self, request: &Request) -> Result<Response> {
+        let message = Secret::new(serde_json::to_vec(request)?);
+        let response = exchange(&self.path, &message)?;
+        match serde_json::from_str(&response)? {
+            Response::Error(reason) => Err(Error::Rejected(reason)),
+            response => Ok(response),
+        }
+    }
+
+    fn expect_ok(&self, request: &Request) -> Result<()> {
+        match self.request(request)? {
+            Response::Ok => Ok(()),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// give the (decrypted) root key of the wallet to the agent
+    pub fn add(&self, wallet: &Wallet, xprv: &XPrv, timeout: Option<Duration>) -> Result<()> {
+        self.expect_ok(&Request::Add {
+            wallet: wallet.name.to_string(),
+            key_id: key_id(wallet),
+            hdwallet_model: wallet.config.hdwallet_model,
+            derivation_scheme: wallet.config.derivation_scheme,
+            key: hex::encode(xprv.as_ref()),
+            timeout: timeout.map(|timeout| timeout.as_secs()),
+        })
+    }
+
+    /// wipe the key of the given wallet from the agent
+    pub fn remove(&self, wallet: &str) -> Result<()> {
+        self.expect_ok(&Request::Remove {
+            wallet: wallet.to_owned(),
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<UnlockedWallet>> {
+        match self.request(&Request::List)? {
+            Response::Wallets(wallets) => Ok(wallets),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// stop the agent, wiping all its keys
+    pub fn stop(&self) -> Result<()> {
+        self.expect_ok(&Request::Stop)
+    }
+
+    /// the wallet, if its key is held by the agent
+    pub fn wallet(&self, wallet: &Wallet) -> Result<Option<RemoteWallet>> {
+        let name = wallet.name.to_string();
+        let key_id = key_id(wallet);
+        let unlocked = self
+            .list()?
+            .into_iter()
+            .any(|unlocked| unlocked.wallet == name && unlocked.key_id == key_id);
+        if unlocked {
+            Ok(Some(RemoteWallet {
+                client: self.clone(),
+                wallet: name,
+                key_id: key_id,
+                derivation_scheme: wallet.config.derivation_scheme,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// look for the given wallet in the key agent of its root directory
+///
+/// `None` if no agent is running, or if the agent does not hold the key of
+/// the wallet (the password is to be asked then).
+pub fn unlocked_wallet(wallet: &Wallet) -> Option<RemoteWallet> {
+    match Client::new(&wallet.root_dir).wallet(wallet) {
+        Ok(remote) => remote,
+        Err(Error::NotRunning(_)) | Err(Error::Unsupported) => None,
+        Err(err) => {
+            warn!("cannot query the key agent: {}", err);
+            None
+        }
+    }
+}
+
+/// a wallet whose keys are held by the agent: the derivations and the
+/// signatures are done by the agent.
+#[derive(Debug, Clone)]
+pub struct RemoteWallet {
+    client: Client,
+    wallet: String,
+    key_id: String,
+    derivation_scheme: DerivationScheme,
+}
+impl RemoteWallet {
+    pub fn derivation_scheme(&self) -> DerivationScheme {
+        self.derivation_scheme
+    }
+
+    pub fn root_public_key(&self) -> Result<XPub> {
+        match self.client.request(&Request::RootPublicKey {
+            wallet: self.wallet.clone(),
+            key_id: self.key_id.clone(),
+        })? {
+            Response::PublicKey(xpub) => decode_xpub(&xpub),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// the public key of the given BIP44 account
+    pub fn account_public_key(&self, account: u32) -> Result<XPub> {
+        match self.client.request(&Request::AccountPublicKey {
+            wallet: self.wallet.clone(),
+            key_id: self.key_id.clone(),
+            account: account,
+        })? {
+            Response::PublicKey(xpub) => decode_xpub(&xpub),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn public_key(&self, address: &Address) -> Result<XPub> {
+        match self.client.request(&Request::PublicKey {
+            wallet: self.wallet.clone(),
+            key_id: self.key_id.clone(),
+            address: address.clone(),
+        })? {
+            Response::PublicKey(xpub) => decode_xpub(&xpub),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn address(&self, address: &Address, network_magic: NetworkMagic) -> Result<ExtendedAddr> {
+        match self.client.request(&Request::Address {
+            wallet: self.wallet.clone(),
+            key_id: self.key_id.clone(),
+            address: address.clone(),
+            network_magic: network_magic_to_option(network_magic),
+        })? {
+            Response::Address(address) => address.parse().map_err(|_| Error::UnexpectedResponse),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn sign<T>(&self, address: &Address, data: &[u8]) -> Result<Signature<T>> {
+        match self.client.request(&Request::Sign {
+            wallet: self.wallet.clone(),
+            key_id: self.key_id.clone(),
+            address: address.clone(),
+            data: hex::encode(data),
+        })? {
+            Response::Signature(signature) => hex::decode(&signature)
+                .ok()
+                .and_then(|bytes| Signature::from_slice(&bytes).ok())
+                .ok_or(Error::UnexpectedResponse),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn sign_tx(
+        &self,
+        address: &Address,
+        protocol_magic: ProtocolMagic,
+        txid: &TxId,
+    ) -> Result<TxInWitness> {
+        match self.client.request(&Request::SignTx {
+            wallet: self.wallet.clone(),
+            key_id: self.key_id.clone(),
+            address: address.clone(),
+            protocol_magic: *protocol_magic,
+            txid: txid.to_string(),
+        })? {
+            Response::Witness(witness) => {
+                let bytes = hex::decode(&witness).map_err(|_| Error::UnexpectedResponse)?;
+                Deserializer::from(io::Cursor::new(bytes))
+                    .deserialize()
+                    .map_err(|_| Error::UnexpectedResponse)
+            }
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
+
+fn decode_xpub(xpub: &str) -> Result<XPub> {
+    hex::decode(xpub)
+        .ok()
+        .and_then(|bytes| XPub::from_slice(&bytes).ok())
+        .ok_or(Error::UnexpectedResponse)
+}
+
+/// send the message (a JSON object) to the agent and read its response
+#[cfg(unix)]
+fn exchange(path: &Path, message: &[u8]) -> Result<String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+            Error::NotRunning(path.to_path_buf())
+        }
+        _ => Error::IoError(err),
+    })?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.set_write_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.write_all(message)?;
+    stream.write_all(b"\n")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    Ok(response)
+}
+#[cfg(not(unix))]
+fn exchange(_path: &Path, _message: &[u8]) -> Result<String> {
+    Err(Error::Unsupported)
+}
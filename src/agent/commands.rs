@@ -0,0 +1,84 @@
+use std::{fs, io::Write, path::PathBuf, time::Duration};
+
+use super::{server, socket_path, Client, Error, Result};
+use utils::{
+    term::{style::Style, Term},
+    time,
+};
+use wallet::{Wallet, WalletName};
+
+/// run the key agent of the root directory, until `agent stop`
+pub fn start(term: &mut Term, root_dir: PathBuf, timeout: Duration) -> Result<()> {
+    let path = socket_path(&root_dir);
+    match Client::new(&root_dir).list() {
+        Ok(_) => return Err(Error::AlreadyRunning(path)),
+        Err(Error::Unsupported) => return Err(Error::Unsupported),
+        Err(_) => {}
+    }
+    fs::create_dir_all(&root_dir)?;
+    if path.exists() {
+        // left behind by an agent that was killed
+        fs::remove_file(&path)?;
+    }
+
+    term.success(&format!("key agent listening on `{}'\n", path.display()))?;
+    term.info(&format!(
+        "unlocked wallet keys are kept for {}, use `agent stop' to stop the agent\n",
+        time::Duration::from(timeout)
+    ))?;
+
+    server::serve(&path, timeout)
+}
+
+/// unlock the wallet: its decrypted key is given to the agent
+pub fn add(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let wallet = Wallet::load(root_dir.clone(), name)?;
+    let client = Client::new(&root_dir);
+    // do not ask the password for nothing
+    client.list()?;
+
+    term.info("Enter the wallet password.\n")?;
+    let password = term.password("wallet password: ")?;
+    let xprv = wallet.get_root_key(password.as_bytes())?;
+    client.add(&wallet, &xprv, timeout)?;
+
+    term.success(&format!(
+        "wallet `{}' unlocked in the key agent\n",
+        wallet.name
+    ))?;
+    Ok(())
+}
+
+/// lock the wallet again: its key is wiped from the agent
+pub fn remove(term: &mut Term, root_dir: PathBuf, name: WalletName) -> Result<()> {
+    Client::new(&root_dir).remove(&name.to_string())?;
+    term.success(&format!("wallet `{}' removed from the key agent\n", name))?;
+    Ok(())
+}
+
+pub fn list(term: &mut Term, root_dir: PathBuf) -> Result<()> {
+    let wallets = Client::new(&root_dir).list()?;
+    if wallets.is_empty() {
+        term.info("no wallet unlocked in the key agent\n")?;
+    }
+    for unlocked in wallets {
+        writeln!(
+            term,
+            "{}\texpires in {}",
+            style!(unlocked.wallet).cyan().italic(),
+            time::Duration::from(Duration::from_secs(unlocked.expires_in))
+        )?;
+    }
+    Ok(())
+}
+
+pub fn stop(term: &mut Term, root_dir: PathBuf) -> Result<()> {
+    Client::new(&root_dir).stop()?;
+    term.success("key agent stopped, all its keys are wiped\n")?;
+    Ok(())
+}
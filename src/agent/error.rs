@@ -0,0 +1,76 @@
+use serde_json;
+use std::{error, fmt, io, path::PathBuf};
+
+use wallet;
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(io::Error),
+    InvalidMessage(serde_json::Error),
+
+    /// the agent is not running in the given root directory
+    NotRunning(PathBuf),
+    AlreadyRunning(PathBuf),
+    /// the agent is only available on Unix platforms
+    Unsupported,
+
+    // boxed: the wallet errors may be key agent errors themselves
+    CannotUnlockWallet(Box<wallet::Error>),
+    /// the agent rejected the request
+    Rejected(String),
+    UnexpectedResponse,
+}
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::InvalidMessage(e)
+    }
+}
+impl From<wallet::Error> for Error {
+    fn from(e: wallet::Error) -> Self {
+        Error::CannotUnlockWallet(Box::new(e))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::IoError(_) => write!(f, "I/O Error with the key agent"),
+            Error::InvalidMessage(_) => write!(f, "Invalid key agent message"),
+            Error::NotRunning(path) => write!(
+                f,
+                "The key agent is not running (no agent listening on `{}')",
+                path.display()
+            ),
+            Error::AlreadyRunning(path) => write!(
+                f,
+                "A key agent is already listening on `{}'",
+                path.display()
+            ),
+            Error::Unsupported => write!(f, "The key agent is only available on Unix platforms"),
+            Error::CannotUnlockWallet(_) => write!(f, "Cannot unlock the wallet"),
+            Error::Rejected(reason) => write!(f, "The key agent rejected the request: {}", reason),
+            Error::UnexpectedResponse => write!(f, "Unexpected response from the key agent"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::IoError(ref err) => Some(err),
+            Error::InvalidMessage(ref err) => Some(err),
+            Error::NotRunning(_) => None,
+            Error::AlreadyRunning(_) => None,
+            Error::Unsupported => None,
+            Error::CannotUnlockWallet(ref err) => Some(err.as_ref()),
+            Error::Rejected(_) => None,
+            Error::UnexpectedResponse => None,
+        }
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
@@ -0,0 +1,138 @@
+//! the key agent: keeps the unlocked wallet keys between commands
+//!
+//! Like `ssh-agent`, the agent is a process listening on a Unix socket (in
+//! the root directory). `agent add` decrypts the key of a wallet and hands it
+//! to the agent, which then answers the derivation and signing requests of
+//! the other commands: the private keys never leave the agent, and the
+//! wallet password is not asked again until the key expires.
+//!
+//! The requests and responses are JSON objects, one per line and one request
+//! per connection.
+
+mod client;
+pub mod commands;
+mod error;
+mod server;
+
+pub use self::client::{unlocked_wallet, Client, RemoteWallet};
+pub use self::error::{Error, Result};
+
+use cardano::{config::NetworkMagic, hash::Blake2b256, hdwallet::DerivationScheme};
+use std::path::{Path, PathBuf};
+
+use utils::secret::Zeroize;
+use wallet::{state::lookup::Address, HDWalletModel, Wallet};
+
+static AGENT_SOCKET_FILE: &'static str = "agent.sock";
+
+/// the path of the agent's socket in the given root directory
+pub fn socket_path<P: AsRef<Path>>(root_dir: P) -> PathBuf {
+    root_dir.as_ref().join(AGENT_SOCKET_FILE)
+}
+
+/// identify the key of the wallet: the hash of the encrypted key, so a
+/// wallet deleted and created again under the same name is not mistaken
+/// for the one in the agent.
+pub fn key_id(wallet: &Wallet) -> String {
+    Blake2b256::new(&wallet.encrypted_key).to_string()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Request {
+    Add {
+        wallet: String,
+        key_id: String,
+        hdwallet_model: HDWalletModel,
+        derivation_scheme: DerivationScheme,
+        /// the root private key, in hexadecimal
+        key: String,
+        /// how long to keep the key (in seconds), the agent's default if none
+        timeout: Option<u64>,
+    },
+    Remove {
+        wallet: String,
+    },
+    List,
+    RootPublicKey {
+        wallet: String,
+        key_id: String,
+    },
+    /// the public key of a BIP44 account: the keys of its addresses are
+    /// derived from it by the client, without asking the agent
+    AccountPublicKey {
+        wallet: String,
+        key_id: String,
+        account: u32,
+    },
+    PublicKey {
+        wallet: String,
+        key_id: String,
+        address: Address,
+    },
+    Address {
+        wallet: String,
+        key_id: String,
+        address: Address,
+        network_magic: Option<u32>,
+    },
+    Sign {
+        wallet: String,
+        key_id: String,
+        address: Address,
+        /// the data to sign, in hexadecimal
+        data: String,
+    },
+    SignTx {
+        wallet: String,
+        key_id: String,
+        address: Address,
+        protocol_magic: u32,
+        txid: String,
+    },
+    Stop,
+}
+impl Drop for Request {
+    fn drop(&mut self) {
+        if let Request::Add { ref mut key, .. } = self {
+            key.zeroize()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Response {
+    Ok,
+    Wallets(Vec<UnlockedWallet>),
+    /// a public key, in hexadecimal
+    PublicKey(String),
+    Address(String),
+    /// a signature, in hexadecimal
+    Signature(String),
+    /// a CBOR encoded `TxInWitness`, in hexadecimal
+    Witness(String),
+    Error(String),
+}
+
+/// a wallet whose key is held by the agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockedWallet {
+    pub wallet: String,
+    pub key_id: String,
+    /// the number of seconds before the key expires
+    pub expires_in: u64,
+}
+
+fn network_magic_to_option(network_magic: NetworkMagic) -> Option<u32> {
+    match network_magic {
+        NetworkMagic::NoMagic => None,
+        NetworkMagic::Magic(magic) => Some(magic),
+    }
+}
+fn network_magic_from_option(magic: Option<u32>) -> NetworkMagic {
+    match magic {
+        None => NetworkMagic::NoMagic,
+        Some(magic) => NetworkMagic::Magic(magic),
+    }
+}
@@ -0,0 +1,518 @@
+use cardano::{
+    address::ExtendedAddr,
+    config::{NetworkMagic, ProtocolMagic},
+    hdwallet::{self, DerivationScheme, Signature, XPrv, XPub},
+    tx::{TxId, TxInWitness},
+    util::hex,
+    wallet::{bip44, rindex},
+};
+use cbor_event::se::Serializer;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use super::{network_magic_from_option, Request, Response, UnlockedWallet};
+use utils::secret::{xprv_from_bytes_verified, Secret, Wiped};
+use wallet::{
+    state::lookup::{
+        randomindex::RandomIndexLookup, sequentialindex::SequentialBip44Lookup, Address,
+    },
+    HDWalletModel,
+};
+
+/// how long the agent waits for a client to send its request
+#[cfg(unix)]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Reply = ::std::result::Result<Response, String>;
+
+/// a decrypted wallet key held by the agent
+struct Key {
+    key_id: String,
+    hdwallet_model: HDWalletModel,
+    derivation_scheme: DerivationScheme,
+    /// wiped from memory when the key is removed (or expires)
+    xprv: Wiped<XPrv>,
+    expires: Instant,
+}
+impl Key {
+    fn expires_in(&self) -> Duration {
+        let now = Instant::now();
+        if self.expires > now {
+            self.expires - now
+        } else {
+            Duration::from_secs(0)
+        }
+    }
+
+    fn lookup(&self, network_magic: NetworkMagic) -> Lookup {
+        match self.hdwallet_model {
+            HDWalletModel::BIP44 => {
+                let wallet =
+                    bip44::Wallet::from_root_key(self.xprv.clone(), self.derivation_scheme);
+                Lookup::Bip44(SequentialBip44Lookup::new(wallet, network_magic))
+            }
+            HDWalletModel::RandomIndex2Levels => {
+                let root_key = rindex::RootKey::new(self.xprv.clone(), self.derivation_scheme);
+                let wallet = rindex::Wallet::from_root_key(self.derivation_scheme, root_key);
+                Lookup::RIndex(RandomIndexLookup::from_wallet(wallet, network_magic))
+            }
+        }
+    }
+}
+
+/// the lookup structure of a key, to derive the keys of the addresses
+enum Lookup {
+    Bip44(SequentialBip44Lookup),
+    RIndex(RandomIndexLookup),
+}
+impl Lookup {
+    fn public_key(&self, address: &Address) -> ::std::result::Result<XPub, String> {
+        match (self, address) {
+            (Lookup::Bip44(lookup), Address::Bip44(addressing)) => {
+                lookup.get_public_key(addressing).map_err(|e| e.to_string())
+            }
+            (Lookup::RIndex(lookup), Address::RIndex(addressing)) => {
+                lookup.get_public_key(addressing).map_err(|e| e.to_string())
+            }
+            _ => Err(unsupported_address(address)),
+        }
+    }
+
+    fn address(&self, address: &Address) -> ::std::result::Result<ExtendedAddr, String> {
+        match (self, address) {
+            (Lookup::Bip44(lookup), Address::Bip44(addressing)) => {
+                lookup.get_address(addressing).map_err(|e| e.to_string())
+            }
+            (Lookup::RIndex(lookup), Address::RIndex(addressing)) => {
+                lookup.get_address(addressing).map_err(|e| e.to_string())
+            }
+            _ => Err(unsupported_address(address)),
+        }
+    }
+
+    fn sign(
+        &self,
+        address: &Address,
+        data: &[u8],
+    ) -> ::std::result::Result<Signature<Vec<u8>>, String> {
+        match (self, address) {
+            (Lookup::Bip44(lookup), Address::Bip44(addressing)) => {
+                lookup.sign(addressing, data).map_err(|e| e.to_string())
+            }
+            (Lookup::RIndex(lookup), Address::RIndex(addressing)) => {
+                lookup.sign(addressing, data).map_err(|e| e.to_string())
+            }
+            _ => Err(unsupported_address(address)),
+        }
+    }
+
+    fn sign_tx(
+        &self,
+        address: &Address,
+        protocol_magic: ProtocolMagic,
+        txid: &TxId,
+    ) -> ::std::result::Result<TxInWitness, String> {
+        match (self, address) {
+            (Lookup::Bip44(lookup), Address::Bip44(addressing)) => lookup
+                .sign_tx(protocol_magic, addressing, txid)
+                .map_err(|e| e.to_string()),
+            (Lookup::RIndex(lookup), Address::RIndex(addressing)) => lookup
+                .sign_tx(protocol_magic, addressing, txid)
+                .map_err(|e| e.to_string()),
+            _ => Err(unsupported_address(address)),
+        }
+    }
+}
+
+fn unsupported_address(address: &Address) -> String {
+    format!("unsupported address for this wallet: {}", address)
+}
+
+/// the keys held by the agent, by wallet name
+pub struct Keys {
+    keys: BTreeMap<String, Key>,
+    /// how long the keys are kept by default
+    timeout: Duration,
+}
+impl Keys {
+    pub fn new(timeout: Duration) -> Self {
+        Keys {
+            keys: BTreeMap::new(),
+            timeout: timeout,
+        }
+    }
+
+    /// wipe the expired keys
+    pub fn purge(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .keys
+            .iter()
+            .filter(|(_, key)| key.expires <= now)
+            .map(|(wallet, _)| wallet.clone())
+            .collect();
+        for wallet in expired {
+            debug!("key of the wallet `{}' expired", wallet);
+            self.keys.remove(&wallet);
+        }
+    }
+
+    /// wipe all the keys
+    pub fn clear(&mut self) {
+        self.keys.clear()
+    }
+
+    fn with_key<F>(&self, wallet: &str, key_id: &str, f: F) -> Response
+    where
+        F: FnOnce(&Key) -> Reply,
+    {
+        match self.keys.get(wallet) {
+            Some(ref key) if key.key_id == key_id => f(key).unwrap_or_else(Response::Error),
+            _ => Response::Error(format!("no key for the wallet `{}'", wallet)),
+        }
+    }
+
+    fn handle(&mut self, request: &Request) -> Response {
+        self.purge();
+        match request {
+            Request::Add {
+                wallet,
+                key_id,
+                hdwallet_model,
+                derivation_scheme,
+                key,
+                timeout,
+            } => match decode_xprv(key) {
+                Err(reason) => Response::Error(reason),
+                Ok(xprv) => {
+                    let timeout = timeout.map(Duration::from_secs).unwrap_or(self.timeout);
+                    self.keys.insert(
+                        wallet.clone(),
+                        Key {
+                            key_id: key_id.clone(),
+                            hdwallet_model: *hdwallet_model,
+                            derivation_scheme: *derivation_scheme,
+                            xprv: Wiped::new(xprv),
+                            expires: Instant::now() + timeout,
+                        },
+                    );
+                    Response::Ok
+                }
+            },
+            Request::Remove { wallet } => match self.keys.remove(wallet) {
+                Some(_) => Response::Ok,
+                None => Response::Error(format!("no key for the wallet `{}'", wallet)),
+            },
+            Request::List => Response::Wallets(
+                self.keys
+                    .iter()
+                    .map(|(wallet, key)| UnlockedWallet {
+                        wallet: wallet.clone(),
+                        key_id: key.key_id.clone(),
+                        expires_in: key.expires_in().as_secs(),
+                    })
+                    .collect(),
+            ),
+            Request::RootPublicKey { wallet, key_id } => self.with_key(wallet, key_id, |key| {
+                Ok(Response::PublicKey(key.xprv.public().to_string()))
+            }),
+            Request::AccountPublicKey {
+                wallet,
+                key_id,
+                account,
+            } => self.with_key(wallet, key_id, |key| match key.hdwallet_model {
+                HDWalletModel::BIP44 => {
+                    let account =
+                        bip44::bip44::Account::new(*account).map_err(|e| e.to_string())?;
+                    let wallet = Wiped::new(bip44::Wallet::from_root_key(
+                        key.xprv.clone(),
+                        key.derivation_scheme,
+                    ));
+                    let account_key = Wiped::new(
+                        wallet.account(key.derivation_scheme, account.get_scheme_value()),
+                    );
+                    let xpub: XPub = *account_key.public();
+                    Ok(Response::PublicKey(xpub.to_string()))
+                }
+                HDWalletModel::RandomIndex2Levels => {
+                    Err("random index wallets have no accounts".to_owned())
+                }
+            }),
+            Request::PublicKey {
+                wallet,
+                key_id,
+                address,
+            } => self.with_key(wallet, key_id, |key| {
+                let xpub = key.lookup(NetworkMagic::NoMagic).public_key(address)?;
+                Ok(Response::PublicKey(xpub.to_string()))
+            }),
+            Request::Address {
+                wallet,
+                key_id,
+                address,
+                network_magic,
+            } => self.with_key(wallet, key_id, |key| {
+                let network_magic = network_magic_from_option(*network_magic);
+                let address = key.lookup(network_magic).address(address)?;
+                Ok(Response::Address(address.to_string()))
+            }),
+            Request::Sign {
+                wallet,
+                key_id,
+                address,
+                data,
+            } => self.with_key(wallet, key_id, |key| {
+                let data = hex::decode(data).map_err(|_| "invalid data to sign".to_owned())?;
+                let signature = key.lookup(NetworkMagic::NoMagic).sign(address, &data)?;
+                Ok(Response::Signature(signature.to_string()))
+            }),
+            Request::SignTx {
+                wallet,
+                key_id,
+                address,
+                protocol_magic,
+                txid,
+            } => self.with_key(wallet, key_id, |key| {
+                let txid: TxId = txid
+                    .parse()
+                    .map_err(|_| "invalid transaction id".to_owned())?;
+                let protocol_magic = ProtocolMagic::from(*protocol_magic);
+                let witness =
+                    key.lookup(protocol_magic.into())
+                        .sign_tx(address, protocol_magic, &txid)?;
+                let mut serializer = Serializer::new_vec();
+                serializer
+                    .serialize(&witness)
+                    .expect("a TxInWitness can always be serialized");
+                Ok(Response::Witness(hex::encode(&serializer.finalize())))
+            }),
+            Request::Stop => Response::Ok,
+        }
+    }
+}
+
+fn decode_xprv(key: &str) -> ::std::result::Result<XPrv, String> {
+    let bytes = Secret::new(hex::decode(key).map_err(|_| "invalid key".to_owned())?);
+    if bytes.len() != hdwallet::XPRV_SIZE {
+        return Err("invalid key size".to_owned());
+    }
+    xprv_from_bytes_verified(&bytes).map_err(|_| "invalid key".to_owned())
+}
+
+/// listen on the given socket until a `stop` request is received
+#[cfg(unix)]
+pub fn serve(path: &::std::path::Path, timeout: Duration) -> super::Result<()> {
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Write},
+        os::unix::net::{UnixListener, UnixStream},
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    fn handle_connection(keys: &Mutex<Keys>, mut stream: UnixStream) -> super::Result<bool> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        // large enough for the requests not to be reallocated (leaving
+        // copies of the keys behind)
+        let mut line = Secret::new(String::with_capacity(4096));
+        BufReader::new(&stream).read_line(&mut line)?;
+
+        let (response, stop) = match ::serde_json::from_str::<Request>(&line) {
+            Err(err) => (Response::Error(format!("invalid request: {}", err)), false),
+            Ok(Request::Stop) => (Response::Ok, true),
+            Ok(request) => (keys.lock().unwrap().handle(&request), false),
+        };
+        ::serde_json::to_writer(&mut stream, &response)?;
+        stream.write_all(b"\n")?;
+        Ok(stop)
+    }
+
+    // only the user can connect to the agent
+    let listener = unsafe {
+        let umask = ::libc::umask(0o177);
+        let listener = UnixListener::bind(path);
+        ::libc::umask(umask);
+        listener?
+    };
+
+    let keys = Arc::new(Mutex::new(Keys::new(timeout)));
+    {
+        let keys = keys.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(1));
+            keys.lock().unwrap().purge();
+        });
+    }
+
+    for stream in listener.incoming() {
+        let stop = match stream
+            .map_err(super::Error::from)
+            .and_then(|stream| handle_connection(&keys, stream))
+        {
+            Ok(stop) => stop,
+            Err(err) => {
+                warn!("key agent request failed: {}", err);
+                false
+            }
+        };
+        if stop {
+            break;
+        }
+    }
+
+    keys.lock().unwrap().clear();
+    fs::remove_file(path)?;
+    Ok(())
+}
+#[cfg(not(unix))]
+pub fn serve(_path: &::std::path::Path, _timeout: Duration) -> super::Result<()> {
+    Err(super::Error::Unsupported)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY_ID: &'static str = "key-id";
+
+    fn xprv() -> XPrv {
+        XPrv::normalize_bytes([7; hdwallet::XPRV_SIZE])
+    }
+
+    fn add(keys: &mut Keys, wallet: &str, timeout: Option<u64>) -> Response {
+        keys.handle(&Request::Add {
+            wallet: wallet.to_owned(),
+            key_id: KEY_ID.to_owned(),
+            hdwallet_model: HDWalletModel::BIP44,
+            derivation_scheme: DerivationScheme::V2,
+            key: hex::encode(xprv().as_ref()),
+            timeout: timeout,
+        })
+    }
+
+    fn root_public_key(keys: &mut Keys, wallet: &str, key_id: &str) -> Response {
+        keys.handle(&Request::RootPublicKey {
+            wallet: wallet.to_owned(),
+            key_id: key_id.to_owned(),
+        })
+    }
+
+    fn listed(keys: &mut Keys) -> Vec<String> {
+        match keys.handle(&Request::List) {
+            Response::Wallets(wallets) => wallets.into_iter().map(|w| w.wallet).collect(),
+            _ => panic!("expected the list of the wallets"),
+        }
+    }
+
+    #[test]
+    fn add_and_list() {
+        let mut keys = Keys::new(Duration::from_secs(60));
+        match add(&mut keys, "wallet", None) {
+            Response::Ok => {}
+            _ => panic!("the key is not added"),
+        }
+        assert_eq!(listed(&mut keys), vec!["wallet".to_owned()]);
+        match root_public_key(&mut keys, "wallet", KEY_ID) {
+            Response::PublicKey(xpub) => assert_eq!(xpub, xprv().public().to_string()),
+            _ => panic!("expected the root public key"),
+        }
+    }
+
+    #[test]
+    fn invalid_key() {
+        let mut keys = Keys::new(Duration::from_secs(60));
+        let response = keys.handle(&Request::Add {
+            wallet: "wallet".to_owned(),
+            key_id: KEY_ID.to_owned(),
+            hdwallet_model: HDWalletModel::BIP44,
+            derivation_scheme: DerivationScheme::V2,
+            key: "0123".to_owned(),
+            timeout: None,
+        });
+        match response {
+            Response::Error(_) => {}
+            _ => panic!("an invalid key is added"),
+        }
+        assert!(listed(&mut keys).is_empty());
+    }
+
+    #[test]
+    fn expiry() {
+        let mut keys = Keys::new(Duration::from_secs(60));
+        add(&mut keys, "expired", Some(0));
+        add(&mut keys, "wallet", None);
+        assert_eq!(listed(&mut keys), vec!["wallet".to_owned()]);
+        match root_public_key(&mut keys, "expired", KEY_ID) {
+            Response::Error(_) => {}
+            _ => panic!("the expired key is still used"),
+        }
+    }
+
+    #[test]
+    fn key_id_mismatch() {
+        let mut keys = Keys::new(Duration::from_secs(60));
+        add(&mut keys, "wallet", None);
+        match root_public_key(&mut keys, "wallet", "other-key-id") {
+            Response::Error(_) => {}
+            _ => panic!("the key of another wallet is used"),
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let mut keys = Keys::new(Duration::from_secs(60));
+        add(&mut keys, "wallet", None);
+        let remove = Request::Remove {
+            wallet: "wallet".to_owned(),
+        };
+        match keys.handle(&remove) {
+            Response::Ok => {}
+            _ => panic!("the key is not removed"),
+        }
+        assert!(listed(&mut keys).is_empty());
+        match keys.handle(&remove) {
+            Response::Error(_) => {}
+            _ => panic!("a missing key is removed"),
+        }
+        match root_public_key(&mut keys, "wallet", KEY_ID) {
+            Response::Error(_) => {}
+            _ => panic!("the removed key is still used"),
+        }
+    }
+
+    #[test]
+    fn account_public_key() {
+        let mut keys = Keys::new(Duration::from_secs(60));
+        add(&mut keys, "wallet", None);
+        let addressing = bip44::bip44::Account::new(0)
+            .and_then(|account| account.external())
+            .and_then(|change| change.index(3))
+            .unwrap();
+
+        let account = match keys.handle(&Request::AccountPublicKey {
+            wallet: "wallet".to_owned(),
+            key_id: KEY_ID.to_owned(),
+            account: 0,
+        }) {
+            Response::PublicKey(xpub) => XPub::from_slice(&hex::decode(&xpub).unwrap()).unwrap(),
+            _ => panic!("expected the account public key"),
+        };
+        let derived = account
+            .derive(DerivationScheme::V2, addressing.change)
+            .and_then(|change| {
+                change.derive(DerivationScheme::V2, addressing.index.get_scheme_value())
+            })
+            .unwrap();
+
+        match keys.handle(&Request::PublicKey {
+            wallet: "wallet".to_owned(),
+            key_id: KEY_ID.to_owned(),
+            address: Address::Bip44(addressing),
+        }) {
+            Response::PublicKey(xpub) => assert_eq!(xpub, derived.to_string()),
+            _ => panic!("expected the address public key"),
+        }
+    }
+}
@@ -9,7 +9,7 @@ use utils::{
     time,
 };
 
-use super::{peer, Blockchain, BlockchainName, Error, Result};
+use super::{fetch, peer, Blockchain, BlockchainName, Error, Result};
 use cardano::{
     self,
     block::{BlockDate, HeaderHash},
@@ -166,17 +166,13 @@ pub fn remote_fetch(
 ) -> Result<()> {
     let blockchain = Blockchain::load(root_dir, name)?;
 
-    for np in blockchain.peers() {
-        if peers.is_empty() || peers.contains(&np.name().to_owned()) {
-            term.info(&format!("fetching blocks from peer: {}\n", np.name()))?;
-
-            let peer = peer::Peer::prepare(&blockchain, np.name().to_owned());
-
-            peer.connect(term).unwrap().sync(term);
-        }
-    }
+    let peers = blockchain
+        .peers()
+        .filter(|np| peers.is_empty() || peers.contains(&np.name().to_owned()))
+        .map(|np| peer::Peer::prepare(&blockchain, np.name().to_owned()))
+        .collect::<Result<_>>()?;
 
-    Ok(())
+    fetch::fetch(term, peers)
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
@@ -193,10 +189,11 @@ pub fn remote_ls(
     detailed: RemoteDetail,
 ) -> Result<()> {
     let blockchain = Blockchain::load(root_dir, name)?;
+    let mut outcomes = peer::Outcomes::new();
 
     for np in blockchain.peers() {
-        let peer = peer::Peer::prepare(&blockchain, np.name().to_owned());
-        let (tip, _is_genesis) = peer.load_local_tip();
+        let peer = peer::Peer::prepare(&blockchain, np.name().to_owned())?;
+        let (tip, _is_genesis) = peer.load_local_tip()?;
 
         writeln!(term, "{} ({})", style!(&peer.name), style!(&peer.config))?;
 
@@ -217,21 +214,41 @@ pub fn remote_ls(
             writeln!(term, " * local tip date:  {}", style!(tip.date))?;
 
             if detailed >= RemoteDetail::Remote {
-                let mut connected_peer = peer.connect(term).unwrap();
-                let remote_tip = connected_peer.query_tip();
-                let block_diff = remote_tip.date - tip.date;
-
-                writeln!(term, " * remote tip hash: {}", style!(remote_tip.hash))?;
-                writeln!(term, " * remote tip date: {}", style!(remote_tip.date))?;
-                writeln!(
-                    term,
-                    " * local is {} behind remote",
-                    style!(block_diff).red()
-                )?;
+                let name = peer.name.clone();
+                let remote_tip = peer
+                    .connect()
+                    .and_then(|mut connected_peer| connected_peer.query_tip());
+                match remote_tip {
+                    Err(err) => {
+                        writeln!(
+                            term,
+                            " * remote tip:      {}",
+                            style!(err.to_string().as_str()).red()
+                        )?;
+                        outcomes.push(&name, Err(err));
+                    }
+                    Ok(remote_tip) => {
+                        let block_diff = remote_tip.date - tip.date;
+
+                        writeln!(term, " * remote tip hash: {}", style!(remote_tip.hash))?;
+                        writeln!(term, " * remote tip date: {}", style!(remote_tip.date))?;
+                        writeln!(
+                            term,
+                            " * local is {} behind remote",
+                            style!(block_diff).red()
+                        )?;
+                        outcomes.push(&name, Ok(format!("tip {}", remote_tip.date)));
+                    }
+                }
             }
         }
     }
 
+    if detailed >= RemoteDetail::Remote {
+        term.info("remote nodes:\n")?;
+        outcomes.print(term)?;
+    }
+
     Ok(())
 }
 
@@ -274,12 +291,9 @@ pub fn forward(
         let initial_tip = blockchain.load_tip().0;
 
         let tip = blockchain
-            .peers()
-            .map(|np| {
-                peer::Peer::prepare(&blockchain, np.name().to_owned())
-                    .load_local_tip()
-                    .0
-            })
+            .load_remote_tips()?
+            .into_iter()
+            .map(|(tip, _)| tip)
             .fold(initial_tip, |current_tip, tip| {
                 if tip.date > current_tip.date {
                     tip
@@ -301,16 +315,12 @@ pub fn forward(
 pub fn pull(term: &mut Term, root_dir: PathBuf, name: BlockchainName) -> Result<()> {
     let blockchain = Blockchain::load(root_dir.clone(), name.clone())?;
 
-    for np in blockchain.peers() {
-        if !np.is_native() {
-            continue;
-        }
-        term.info(&format!("fetching blocks from peer: {}\n", np.name()))?;
-
-        let peer = peer::Peer::prepare(&blockchain, np.name().to_owned());
-
-        peer.connect(term).unwrap().sync(term);
-    }
+    let peers = blockchain
+        .peers()
+        .filter(|np| np.is_native())
+        .map(|np| peer::Peer::prepare(&blockchain, np.name().to_owned()))
+        .collect::<Result<_>>()?;
+    fetch::fetch(term, peers)?;
 
     forward(term, root_dir, name, None)
 }
@@ -389,8 +399,8 @@ pub fn status(term: &mut Term, root_dir: PathBuf, name: BlockchainName) -> Resul
 
     writeln!(term, "{}:", style!("Peers").cyan().bold())?;
     for (idx, np) in blockchain.peers().enumerate() {
-        let peer = peer::Peer::prepare(&blockchain, np.name().to_owned());
-        let (tip, _is_genesis) = peer.load_local_tip();
+        let peer = peer::Peer::prepare(&blockchain, np.name().to_owned())?;
+        let (tip, _is_genesis) = peer.load_local_tip()?;
 
         writeln!(
             term,
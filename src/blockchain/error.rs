@@ -1,6 +1,8 @@
 use cardano::block::{self, BlockDate, HeaderHash};
 use cardano_storage;
 use cbor_event;
+use exe_common::network;
+use serde_yaml;
 use std::{error, fmt, io, path::PathBuf};
 
 #[derive(Debug)]
@@ -30,6 +32,20 @@ pub enum Error {
     VerifyChainInvalidGenesisPrevHash(HeaderHash, HeaderHash), // (Expected, got)
     BlockchainIsNotValid(usize),
 
+    FetchBlockNotChained(HeaderHash, HeaderHash), // (Expected, got)
+    FetchInterrupted,
+    FetchNoPeerAvailable,
+
+    PeerNotConfigured(String),
+    PeerNoLocalTip(String),
+    PeerUnreachable(String, network::Error),
+    PeerTimedOut(String),
+    PeerDisconnected(String),
+    PeerProtocolError(String, network::Error),
+    PeerTransactionRejected(String),
+
+    JournalWriteFailed(PathBuf, serde_yaml::Error),
+
     QueryBlockDateNotResolved(BlockDate),
 }
 
@@ -67,6 +83,17 @@ impl fmt::Display for Error {
             Error::VerifyChainGenesisHashNotFound(hh)        => write!(f, "Genesis data for given blockchain not found ({})", hh),
             Error::VerifyChainInvalidGenesisPrevHash(eh, hh) => write!(f, "Genesis data invalid: expected previous hash {} different from the one provided {}", eh, hh),
             Error::BlockchainIsNotValid(num_invalid_blocks)  => write!(f, "Blockchain has {} invalid blocks", num_invalid_blocks),
+            Error::FetchBlockNotChained(eh, hh)              => write!(f, "Block does not follow the previous one: expected previous hash {} different from the one provided {}", eh, hh),
+            Error::FetchInterrupted                          => write!(f, "Interrupted, the download will continue from the last block received"),
+            Error::FetchNoPeerAvailable                      => write!(f, "None of the remote nodes could be fetched from"),
+            Error::PeerNotConfigured(peer)                   => write!(f, "The remote node `{}` is not in the blockchain configuration", peer),
+            Error::PeerNoLocalTip(peer)                      => write!(f, "Nothing fetched yet from the remote node `{}`", peer),
+            Error::PeerUnreachable(peer, err)                => write!(f, "Cannot connect to the remote node `{}`: {}", peer, err),
+            Error::PeerTimedOut(peer)                        => write!(f, "The remote node `{}` did not answer in time", peer),
+            Error::PeerDisconnected(peer)                    => write!(f, "The connection to the remote node `{}` was closed", peer),
+            Error::PeerProtocolError(peer, err)              => write!(f, "Unexpected answer from the remote node `{}`: {}", peer, err),
+            Error::PeerTransactionRejected(peer)             => write!(f, "The remote node `{}` did not accept the transaction", peer),
+            Error::JournalWriteFailed(p, _)                  => write!(f, "Cannot write the download journal `{}`", p.to_string_lossy()),
             Error::QueryBlockDateNotResolved(date) => {
                 write!(f, "Cannot resolve block date {}", date)
             }
@@ -83,6 +110,7 @@ impl error::Error for Error {
             Error::CatMalformedBlock(ref err) => Some(err),
             Error::VerifyInvalidBlock(ref err) => Some(err),
             Error::VerifyMalformedBlock(ref err) => Some(err),
+            Error::JournalWriteFailed(_, ref err) => Some(err),
             _ => None,
         }
     }
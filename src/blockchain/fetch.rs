@@ -0,0 +1,821 @@
+//! fetch the blocks from several peers at once
+//!
+//! Every peer is connected to from its own thread: the handshakes and the
+//! tip queries are done in parallel, so an unreachable peer does not delay
+//! the others.
+//!
+//! The range to download is then split in two: the peer with the best tip
+//! (the fastest to answer among the equals) streams the blocks from our tip,
+//! while the next best peer gets them one by one from its tip backward. The
+//! blocks got backward are checked and written as loose blocks as they come,
+//! and once the two ends meet they are appended in the order of the chain.
+//! Each of the two peers has its own progress bar.
+//!
+//! The network interface gives neither the headers nor the epoch boundaries
+//! alone: the only hashes known before downloading are the two ends of the
+//! range, so it cannot be split further. The hashes stored locally (the epoch
+//! packs, the journal, the tips of the peers) do not help, the download
+//! starts from the furthest of them.
+//!
+//! If the streaming peer stalls, fails or sends an invalid block, the
+//! download resumes with the next best peer, from the last block received
+//! (see the `journal` module). An interruption (`Ctrl-C`) stops the download
+//! the same way, the next `remote-fetch` or `pull` continues from the last
+//! block received.
+
+use cardano::{
+    block::{self, BlockDate, HeaderHash, RawBlock},
+    config::ProtocolMagic,
+};
+use cardano_storage as storage;
+use exe_common::{
+    config::net,
+    network::{
+        self,
+        api::{Api, BlockRef},
+    },
+};
+use indicatif::MultiProgress;
+use std::{
+    cmp, panic,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    peer::{Download, Outcomes, Peer, CONNECT_TIMEOUT, READ_TIMEOUT},
+    Error, Result,
+};
+use utils::{interrupt::Interruptible, term::Term};
+
+/// how often the interruptions and the timeouts are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// the commands are only sent to the idle workers, but `Stop`
+enum Command {
+    /// stream the blocks of the range, in the order of the chain
+    Fetch {
+        from: BlockRef,
+        inclusive: bool,
+        to: BlockRef,
+    },
+    /// get the blocks one by one, from the given one to its ancestors,
+    /// until told to stop
+    FetchBackward { from: HeaderHash },
+    /// stop the current request
+    Stop,
+}
+
+enum Event {
+    Connected(BlockRef),
+    Block(HeaderHash, RawBlock),
+    Done,
+    Failed(Error),
+}
+
+/// how a download ended
+enum Outcome {
+    Done,
+    Failed(Error),
+    Interrupted,
+}
+
+/// the payload of the unwinding out of a request which cannot be cancelled
+/// otherwise, see `worker`
+struct Stopped;
+
+enum State {
+    Connecting,
+    /// the tip of the peer, and how long it took to get it
+    Ready(BlockRef, Duration),
+    /// the blocks are fetched up to the tip of the peer
+    Done(BlockRef),
+    Failed(Error),
+}
+
+struct Remote<'a> {
+    peer: Peer<'a>,
+    state: State,
+    commands: Option<Sender<Command>>,
+    /// the number of the requests sent to the worker, the events of the
+    /// previous ones are ignored
+    request: usize,
+}
+impl<'a> Remote<'a> {
+    fn tip(&self) -> Option<(&BlockRef, Duration)> {
+        match self.state {
+            State::Ready(ref tip, latency) => Some((tip, latency)),
+            _ => None,
+        }
+    }
+
+    /// send a request to the worker of the peer, false if it is gone
+    fn send(&mut self, command: Command) -> bool {
+        let sent = self
+            .commands
+            .as_ref()
+            .map_or(false, |commands| commands.send(command).is_ok());
+        if sent {
+            self.request += 1;
+        }
+        sent
+    }
+
+    /// stop the current request of the worker of the peer, if any
+    fn stop(&mut self) {
+        if let Some(ref commands) = self.commands {
+            let _ = commands.send(Command::Stop);
+        }
+    }
+
+    /// the peer is not used anymore: its thread stops (see `worker`)
+    fn fail(&mut self, reason: Error) {
+        self.state = State::Failed(reason);
+        self.commands = None;
+    }
+}
+
+/// whether the worker is to stop its current request: told so, or its
+/// commands sender is dropped
+fn stop_requested(commands: &Receiver<Command>) -> bool {
+    match commands.try_recv() {
+        Ok(Command::Stop) | Err(TryRecvError::Disconnected) => true,
+        Ok(_) | Err(TryRecvError::Empty) => false,
+    }
+}
+
+/// connect to the peer and fetch the blocks it is asked for
+///
+/// The events are numbered with the request they answer. The worker stops
+/// once its commands sender or the events receiver is dropped, in the middle
+/// of a request too: a stream of blocks cannot be cancelled but by unwinding
+/// out of it, the connection is then dropped with the thread.
+fn worker(
+    index: usize,
+    blockchain_name: String,
+    name: String,
+    config: net::Peer,
+    protocol_magic: ProtocolMagic,
+    commands: Receiver<Command>,
+    events: Sender<(usize, usize, Event)>,
+) {
+    let mut connection =
+        match network::Peer::new(blockchain_name, name.clone(), config, protocol_magic) {
+            Err(err) => {
+                let _ = events.send((index, 0, Event::Failed(Error::PeerUnreachable(name, err))));
+                return;
+            }
+            Ok(connection) => connection,
+        };
+    match connection.get_tip() {
+        Err(err) => {
+            let _ = events.send((index, 0, Event::Failed(Error::PeerProtocolError(name, err))));
+            return;
+        }
+        Ok(tip_header) => {
+            let tip = BlockRef {
+                hash: tip_header.compute_hash(),
+                parent: tip_header.get_previous_header(),
+                date: tip_header.get_blockdate(),
+            };
+            if events.send((index, 0, Event::Connected(tip))).is_err() {
+                return;
+            }
+        }
+    }
+
+    let mut request = 0;
+    while let Ok(command) = commands.recv() {
+        let event = match command {
+            Command::Stop => continue,
+            Command::Fetch {
+                from,
+                inclusive,
+                to,
+            } => {
+                request += 1;
+                let result = connection.get_blocks(
+                    &from,
+                    inclusive,
+                    &to,
+                    &mut |block_hash, _, block_raw| {
+                        let event = Event::Block(block_hash.clone(), block_raw.clone());
+                        if stop_requested(&commands)
+                            || events.send((index, request, event)).is_err()
+                        {
+                            panic::resume_unwind(Box::new(Stopped));
+                        }
+                    },
+                );
+                match result {
+                    Ok(()) => Event::Done,
+                    Err(err) => Event::Failed(Error::PeerProtocolError(name.clone(), err)),
+                }
+            }
+            Command::FetchBackward { from } => {
+                request += 1;
+                let mut block_hash = from;
+                loop {
+                    match commands.try_recv() {
+                        Err(TryRecvError::Disconnected) => return,
+                        Ok(Command::Stop) => break Event::Done,
+                        Ok(_) | Err(TryRecvError::Empty) => {}
+                    }
+                    let block_raw = match connection.get_block(&block_hash) {
+                        Err(err) => {
+                            break Event::Failed(Error::PeerProtocolError(name.clone(), err))
+                        }
+                        Ok(block_raw) => block_raw,
+                    };
+                    let previous = match block_raw.decode() {
+                        Err(err) => break Event::Failed(Error::VerifyMalformedBlock(err)),
+                        Ok(block) => block.get_header().get_previous_header(),
+                    };
+                    let event = Event::Block(block_hash, block_raw);
+                    if events.send((index, request, event)).is_err() {
+                        return;
+                    }
+                    block_hash = previous;
+                }
+            }
+        };
+        if events.send((index, request, event)).is_err() {
+            return;
+        }
+    }
+}
+
+/// what to do with a block streamed forward, see `Reassembly::forward`
+#[derive(Debug, PartialEq)]
+enum Step {
+    Append,
+    /// append the block, then the blocks got backward (in the order of
+    /// the chain): the download is over
+    Meet(Vec<HeaderHash>),
+    /// append the block: the blocks got backward are not on the same chain,
+    /// they are dropped and the backward fetch is to stop
+    Diverged,
+}
+
+/// the two ends of a download: only the hashes and the dates of the blocks
+/// got backward are kept, the blocks themselves are written as they come.
+struct Reassembly {
+    /// the blocks got backward, the oldest last
+    backward: Vec<(HeaderHash, BlockDate)>,
+    /// the date of the last block streamed forward
+    forward: Option<BlockDate>,
+    /// no more blocks are taken backward
+    backward_stopped: bool,
+}
+impl Reassembly {
+    fn new() -> Self {
+        Reassembly {
+            backward: Vec::new(),
+            forward: None,
+            backward_stopped: false,
+        }
+    }
+
+    /// a block streamed forward (and appended to the download)
+    fn forward(&mut self, block_hash: &HeaderHash, date: BlockDate) -> Step {
+        self.forward = Some(date);
+        let (meets, diverges) = match self.backward.last() {
+            None => return Step::Append,
+            Some((oldest, oldest_date)) => (oldest == block_hash, date >= *oldest_date),
+        };
+        if meets {
+            let mut blocks: Vec<_> = self.backward.drain(..).map(|(hash, _)| hash).collect();
+            // the meeting block itself was streamed forward
+            blocks.pop();
+            blocks.reverse();
+            self.backward_stopped = true;
+            Step::Meet(blocks)
+        } else if diverges {
+            self.abandon_backward();
+            Step::Diverged
+        } else {
+            Step::Append
+        }
+    }
+
+    /// a block got backward (and written as a loose block): false once the
+    /// backward fetch is to stop, having reached the blocks streamed forward
+    fn backward(&mut self, block_hash: HeaderHash, date: BlockDate) -> bool {
+        if self.backward_stopped {
+            return false;
+        }
+        if self.forward.map_or(false, |forward| date <= forward) {
+            self.backward_stopped = true;
+            return false;
+        }
+        self.backward.push((block_hash, date));
+        true
+    }
+
+    /// the blocks got backward are invalid: drop them
+    fn abandon_backward(&mut self) {
+        self.backward.clear();
+        self.backward_stopped = true;
+    }
+}
+
+/// check a block got backward and write it as a loose block
+fn store_block(peer: &Peer, block_hash: &HeaderHash, block_raw: &RawBlock) -> Result<BlockDate> {
+    let block = block_raw.decode().map_err(Error::VerifyMalformedBlock)?;
+    block::verify_block(block_hash, &block).map_err(Error::VerifyInvalidBlock)?;
+    storage::blob::write(
+        &peer.blockchain.storage,
+        &storage::types::header_to_blockhash(block_hash),
+        block_raw.as_ref(),
+    )?;
+    Ok(block.get_header().get_blockdate())
+}
+
+/// the order of the peers to download from: the best tip first, the fastest
+/// peer first among the equals
+fn ranking<I>(tips: I) -> Vec<usize>
+where
+    I: IntoIterator<Item = (usize, BlockDate, Duration)>,
+{
+    let mut tips: Vec<_> = tips.into_iter().collect();
+    tips.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(cmp::Ordering::Equal)
+            .then(a.2.cmp(&b.2))
+    });
+    tips.into_iter().map(|(index, _, _)| index).collect()
+}
+
+fn has_block(peer: &Peer, hash: &HeaderHash) -> Result<bool> {
+    match peer.blockchain.storage.read_block(hash.as_hash_bytes()) {
+        Ok(_) => Ok(true),
+        Err(storage::Error::BlockNotFound(_)) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// fetch the blocks of all the given peers, see the module documentation
+pub fn fetch(term: &mut Term, peers: Vec<Peer>) -> Result<()> {
+    if peers.is_empty() {
+        return Ok(());
+    }
+    let interruptible = Interruptible::new();
+    let (events_sender, events) = mpsc::channel();
+    let started = Instant::now();
+    let mut remotes: Vec<Remote> = peers
+        .into_iter()
+        .enumerate()
+        .map(|(index, peer)| {
+            let (commands_sender, commands) = mpsc::channel();
+            let events_sender = events_sender.clone();
+            let blockchain_name = peer.blockchain.name.as_ref().to_owned();
+            let name = peer.name.clone();
+            let config = peer.config.clone();
+            let protocol_magic = peer.blockchain.config.protocol_magic;
+            thread::spawn(move || {
+                worker(
+                    index,
+                    blockchain_name,
+                    name,
+                    config,
+                    protocol_magic,
+                    commands,
+                    events_sender,
+                )
+            });
+            Remote {
+                peer: peer,
+                state: State::Connecting,
+                commands: Some(commands_sender),
+                request: 0,
+            }
+        })
+        .collect();
+    drop(events_sender);
+
+    // wait for all the peers to give their tip (or to fail doing so)
+    let deadline = started + CONNECT_TIMEOUT;
+    while remotes.iter().any(|remote| match remote.state {
+        State::Connecting => true,
+        _ => false,
+    }) {
+        if interruptible.interrupted() {
+            return Err(Error::FetchInterrupted);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let (index, _, event) = match events.recv_timeout(cmp::min(deadline - now, POLL_INTERVAL)) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+            Ok(event) => event,
+        };
+        let remote = &mut remotes[index];
+        match event {
+            Event::Connected(tip) => {
+                term.info(&format!(
+                    "peer {}: tip {} ({})\n",
+                    remote.peer.name, tip.hash, tip.date
+                ))?;
+                remote.state = State::Ready(tip, started.elapsed());
+            }
+            Event::Failed(reason) => {
+                term.warn(&format!("{}\n", reason))?;
+                remote.fail(reason);
+            }
+            Event::Block(..) | Event::Done => {}
+        }
+    }
+    for remote in remotes.iter() {
+        if let State::Connecting = remote.state {
+            term.warn(&format!(
+                "peer {}: no answer to the handshake yet\n",
+                remote.peer.name
+            ))?;
+        }
+    }
+
+    loop {
+        // the peers whose tip is already known have nothing more to give
+        for remote in remotes.iter_mut() {
+            let tip = match remote.tip() {
+                Some((tip, _)) => tip.clone(),
+                None => continue,
+            };
+            if has_block(&remote.peer, &tip.hash)? {
+                remote.peer.save_peer_local_tip(&tip.hash);
+                remote.state = State::Done(tip);
+            }
+        }
+
+        let ranked = ranking(remotes.iter().enumerate().filter_map(|(index, remote)| {
+            remote
+                .tip()
+                .map(|(tip, latency)| (index, tip.date, latency))
+        }));
+        match ranked.first() {
+            None => break,
+            Some(&index) => download(
+                term,
+                &mut remotes,
+                index,
+                ranked.get(1).cloned(),
+                &events,
+                &interruptible,
+            )?,
+        }
+    }
+
+    let mut outcomes = Outcomes::new();
+    for remote in remotes {
+        let name = remote.peer.name.clone();
+        let outcome = match remote.state {
+            State::Done(tip) => Ok(format!("fetched up to {} ({})", tip.hash, tip.date)),
+            State::Failed(err) => Err(err),
+            State::Connecting | State::Ready(..) => Err(Error::PeerTimedOut(name.clone())),
+        };
+        outcomes.push(&name, outcome);
+    }
+    term.info("remote nodes:\n")?;
+    outcomes.print(term)?;
+
+    if outcomes.any_success() {
+        Ok(())
+    } else {
+        Err(Error::FetchNoPeerAvailable)
+    }
+}
+
+/// download the blocks of the given remote, with the help of the next best
+/// one (if any) from the tip backward: the remote is either done or failed
+/// once this returns
+fn download(
+    term: &mut Term,
+    remotes: &mut Vec<Remote>,
+    index: usize,
+    helper: Option<usize>,
+    events: &Receiver<(usize, usize, Event)>,
+    interruptible: &Interruptible,
+) -> Result<()> {
+    let tip = match remotes[index].tip() {
+        Some((tip, _)) => tip.clone(),
+        None => return Ok(()),
+    };
+    let mut download = match Download::new(&remotes[index].peer, &tip) {
+        Err(err) => {
+            // the other peers are still fetched from
+            term.warn(&format!("peer {}: {}\n", remotes[index].peer.name, err))?;
+            remotes[index].fail(err);
+            return Ok(());
+        }
+        Ok(None) => {
+            remotes[index].state = State::Done(tip);
+            return Ok(());
+        }
+        Ok(Some(download)) => download,
+    };
+    let name = remotes[index].peer.name.clone();
+
+    term.info(&format!("fetching blocks from peer: {}\n", name))?;
+    let sent = remotes[index].send(Command::Fetch {
+        from: download.from.0.clone(),
+        inclusive: download.from.1,
+        to: tip.clone(),
+    });
+    // the helper, and when it last sent a block
+    let mut backward = match helper {
+        Some(helper)
+            if sent
+                && remotes[helper].send(Command::FetchBackward {
+                    from: tip.hash.clone(),
+                }) =>
+        {
+            term.info(&format!(
+                "fetching blocks backward from peer: {}\n",
+                remotes[helper].peer.name
+            ))?;
+            Some((helper, Instant::now()))
+        }
+        _ => None,
+    };
+    let mut reassembly = Reassembly::new();
+
+    // initialisation of the progress bars, one per peer:
+    let bars = MultiProgress::new();
+    let forward_pbr = bars.add(term.peer_progress_bar(&name, download.count()));
+    let backward_pbr = backward.map(|(helper, _)| {
+        bars.add(term.peer_progress_bar(&remotes[helper].peer.name, download.count()))
+    });
+    let drawing = thread::spawn(move || bars.join());
+    let mut last_event = Instant::now();
+    let outcome = loop {
+        if !sent {
+            break Outcome::Failed(Error::PeerDisconnected(name.clone()));
+        }
+        if interruptible.interrupted() {
+            break Outcome::Interrupted;
+        }
+        if let Some((helper, last_block)) = backward {
+            if last_block.elapsed() >= READ_TIMEOUT {
+                // the blocks got so far are kept, the other end still meets them
+                let remote = &mut remotes[helper];
+                warn!("peer {} stalled fetching backward", remote.peer.name);
+                let reason = Error::PeerTimedOut(remote.peer.name.clone());
+                remote.fail(reason);
+                backward = None;
+            }
+        }
+        let elapsed = last_event.elapsed();
+        if elapsed >= READ_TIMEOUT {
+            break Outcome::Failed(Error::PeerTimedOut(name.clone()));
+        }
+        let (from, request, event) =
+            match events.recv_timeout(cmp::min(READ_TIMEOUT - elapsed, POLL_INTERVAL)) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    break Outcome::Failed(Error::PeerDisconnected(name.clone()))
+                }
+                Ok(event) => event,
+            };
+        let stale = request != remotes[from].request;
+        if from == index && !stale {
+            last_event = Instant::now();
+            let (block_hash, block_raw) = match event {
+                Event::Block(block_hash, block_raw) => (block_hash, block_raw),
+                Event::Done => break Outcome::Done,
+                Event::Failed(reason) => break Outcome::Failed(reason),
+                Event::Connected(_) => continue,
+            };
+            let date = match download.append(&block_hash, &block_raw) {
+                Err(err) => {
+                    warn!("invalid block {} from {}: {}", block_hash, name, err);
+                    break Outcome::Failed(err);
+                }
+                Ok(date) => date,
+            };
+            forward_pbr.inc(1);
+            forward_pbr.set_message(&format!("downloading epoch {} -> ", date.get_epochid()));
+            match reassembly.forward(&block_hash, date) {
+                Step::Append => {}
+                Step::Diverged => {
+                    if let Some((helper, _)) = backward.take() {
+                        info!(
+                            "peer {} is not on the same chain",
+                            remotes[helper].peer.name
+                        );
+                        remotes[helper].stop();
+                    }
+                }
+                Step::Meet(blocks) => {
+                    if let Some((helper, _)) = backward.take() {
+                        remotes[helper].stop();
+                    }
+                    // the stream is not needed anymore
+                    remotes[index].commands = None;
+                    match append_stored(&mut download, &remotes[index].peer, &blocks) {
+                        Err(err) => break Outcome::Failed(err),
+                        Ok(()) => break Outcome::Done,
+                    }
+                }
+            }
+        } else if !stale && backward.map_or(false, |(helper, _)| helper == from) {
+            match event {
+                Event::Block(block_hash, block_raw) => {
+                    match store_block(&remotes[from].peer, &block_hash, &block_raw) {
+                        Err(err) => {
+                            warn!(
+                                "invalid block {} from {}: {}",
+                                block_hash, remotes[from].peer.name, err
+                            );
+                            reassembly.abandon_backward();
+                            remotes[from].fail(err);
+                            backward = None;
+                        }
+                        Ok(date) => {
+                            if let Some(ref pbr) = backward_pbr {
+                                pbr.inc(1);
+                                pbr.set_message(&format!(
+                                    "<- downloading epoch {}",
+                                    date.get_epochid()
+                                ));
+                            }
+                            if reassembly.backward(block_hash, date) {
+                                backward = Some((from, Instant::now()));
+                            } else {
+                                remotes[from].stop();
+                                backward = None;
+                            }
+                        }
+                    }
+                }
+                Event::Failed(reason) => {
+                    // the blocks got so far are kept, the other end still meets them
+                    info!("peer {}: {}", remotes[from].peer.name, reason);
+                    backward = None;
+                }
+                Event::Done | Event::Connected(_) => backward = None,
+            }
+        } else {
+            // the late answers of the other peers, the blocks of the peers
+            // given up on (or of their previous requests) are ignored
+            let remote = &mut remotes[from];
+            if let State::Connecting = remote.state {
+                match event {
+                    Event::Connected(tip) => {
+                        info!("peer {}: tip {} ({})", remote.peer.name, tip.hash, tip.date);
+                        // ranked after the peers which answered in time
+                        remote.state = State::Ready(tip, CONNECT_TIMEOUT);
+                    }
+                    Event::Failed(reason) => {
+                        info!("peer {}: {}", remote.peer.name, reason);
+                        remote.fail(reason);
+                    }
+                    Event::Block(..) | Event::Done => {}
+                }
+            }
+        }
+    };
+    forward_pbr.finish();
+    if let Some(pbr) = backward_pbr {
+        pbr.finish();
+    }
+    let _ = drawing.join();
+    if let Some((helper, _)) = backward {
+        remotes[helper].stop();
+    }
+
+    match outcome {
+        Outcome::Done => {
+            download.finish();
+            remotes[index].state = State::Done(tip);
+        }
+        Outcome::Failed(reason) => {
+            // the blocks received so far are kept, the next peer continues
+            // from the last of them
+            download.suspend()?;
+            term.warn(&format!(
+                "peer {}: {}, fetching from the other peers\n",
+                name, reason
+            ))?;
+            remotes[index].fail(reason);
+        }
+        Outcome::Interrupted => {
+            download.suspend()?;
+            return Err(Error::FetchInterrupted);
+        }
+    }
+    Ok(())
+}
+
+/// append the blocks got backward (written as loose blocks) to the download
+fn append_stored(download: &mut Download, peer: &Peer, blocks: &[HeaderHash]) -> Result<()> {
+    for block_hash in blocks {
+        let block_raw = peer
+            .blockchain
+            .storage
+            .read_block(block_hash.as_hash_bytes())?;
+        download.append(block_hash, &block_raw)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hash(n: u32) -> HeaderHash {
+        format!("{:064x}", n).parse().unwrap()
+    }
+
+    fn date(slot: u8) -> BlockDate {
+        BlockDate::Normal(block::EpochSlotId {
+            epoch: 0,
+            slotid: slot.into(),
+        })
+    }
+
+    #[test]
+    fn ranking_best_tip_first() {
+        let ranked = ranking(vec![
+            (0, date(10), Duration::from_millis(10)),
+            (1, date(12), Duration::from_millis(300)),
+            (2, date(11), Duration::from_millis(20)),
+        ]);
+        assert_eq!(ranked, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn ranking_fastest_first_among_equals() {
+        let ranked = ranking(vec![
+            (0, date(12), Duration::from_millis(300)),
+            (1, date(12), Duration::from_millis(20)),
+            (2, date(12), Duration::from_millis(20)),
+        ]);
+        assert_eq!(ranked, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn ranking_failover_to_the_next_best() {
+        // the failed (or stalled) peers give no tip anymore
+        let ranked = ranking(vec![
+            (1, date(11), Duration::from_millis(20)),
+            (2, date(11), Duration::from_millis(10)),
+        ]);
+        assert_eq!(ranked.first(), Some(&2));
+        assert_eq!(ranked.get(1), Some(&1));
+        assert!(ranking(vec![]).is_empty());
+    }
+
+    #[test]
+    fn reassembly_meet() {
+        let mut reassembly = Reassembly::new();
+        assert_eq!(reassembly.forward(&hash(1), date(1)), Step::Append);
+        assert!(reassembly.backward(hash(6), date(6)));
+        assert!(reassembly.backward(hash(5), date(5)));
+        assert_eq!(reassembly.forward(&hash(2), date(2)), Step::Append);
+        assert!(reassembly.backward(hash(4), date(4)));
+        assert!(!reassembly.backward(hash(2), date(2)));
+        assert_eq!(reassembly.forward(&hash(3), date(3)), Step::Append);
+        assert_eq!(
+            reassembly.forward(&hash(4), date(4)),
+            Step::Meet(vec![hash(5), hash(6)])
+        );
+        assert!(!reassembly.backward(hash(3), date(3)));
+    }
+
+    #[test]
+    fn reassembly_diverged() {
+        let mut reassembly = Reassembly::new();
+        assert!(reassembly.backward(hash(16), date(6)));
+        assert!(reassembly.backward(hash(15), date(5)));
+        assert_eq!(reassembly.forward(&hash(4), date(4)), Step::Append);
+        assert_eq!(reassembly.forward(&hash(5), date(5)), Step::Diverged);
+        assert!(!reassembly.backward(hash(14), date(4)));
+        assert_eq!(reassembly.forward(&hash(6), date(6)), Step::Append);
+    }
+
+    #[test]
+    fn reassembly_backward_stalled() {
+        // the backward peer stalls: the blocks it sent are still met
+        let mut reassembly = Reassembly::new();
+        assert!(reassembly.backward(hash(6), date(6)));
+        assert!(reassembly.backward(hash(5), date(5)));
+        assert_eq!(reassembly.forward(&hash(4), date(4)), Step::Append);
+        assert_eq!(
+            reassembly.forward(&hash(5), date(5)),
+            Step::Meet(vec![hash(6)])
+        );
+    }
+
+    #[test]
+    fn reassembly_backward_invalid() {
+        let mut reassembly = Reassembly::new();
+        assert!(reassembly.backward(hash(6), date(6)));
+        reassembly.abandon_backward();
+        assert!(!reassembly.backward(hash(5), date(5)));
+        assert_eq!(reassembly.forward(&hash(5), date(5)), Step::Append);
+        assert_eq!(reassembly.forward(&hash(6), date(6)), Step::Append);
+    }
+}
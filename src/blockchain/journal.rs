@@ -0,0 +1,123 @@
+//! the journal of the epoch being downloaded
+//!
+//! The blocks of a stable epoch are only packed once the whole epoch is
+//! downloaded, so every block of it is also written as a loose block as it
+//! is received (removed once the epoch is packed). The last of them is recorded in the journal every
+//! `JOURNAL_INTERVAL` blocks and when the download stops: if it is
+//! interrupted (or the peer fails, or the process is killed), the next
+//! download continues from there instead of downloading the whole epoch
+//! again.
+
+use cardano::block::{EpochId, HeaderHash};
+use exe_common::network::api::BlockRef;
+use serde_yaml;
+use std::{fs, io::ErrorKind, path::PathBuf};
+use storage_units::utils::tmpfile::TmpFile;
+
+use super::{Blockchain, Error, Result};
+
+static FETCH_JOURNAL_FILE: &'static str = "fetch.journal";
+
+/// how many blocks are received between two updates of the journal
+pub const JOURNAL_INTERVAL: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Journal {
+    pub epoch: EpochId,
+    /// the last block received, checked and written
+    pub last_block: String,
+}
+impl Journal {
+    fn path(blockchain: &Blockchain) -> PathBuf {
+        blockchain.dir.join(FETCH_JOURNAL_FILE)
+    }
+
+    /// load the journal of the blockchain, if any
+    ///
+    /// An unreadable journal is ignored: the epoch is downloaded again.
+    pub fn load(blockchain: &Blockchain) -> Option<Self> {
+        let path = Self::path(blockchain);
+        let mut file = match fs::File::open(&path) {
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return None,
+            Err(err) => {
+                warn!("cannot open the journal `{}': {}", path.display(), err);
+                return None;
+            }
+            Ok(file) => file,
+        };
+        match serde_yaml::from_reader(&mut file) {
+            Err(err) => {
+                warn!("invalid journal `{}': {}", path.display(), err);
+                None
+            }
+            Ok(journal) => Some(journal),
+        }
+    }
+
+    pub fn save(&self, blockchain: &Blockchain) -> Result<()> {
+        let path = Self::path(blockchain);
+        let mut tmpfile = TmpFile::create(blockchain.dir.clone())?;
+        serde_yaml::to_writer(&mut tmpfile, self)
+            .map_err(|e| Error::JournalWriteFailed(path.clone(), e))?;
+        tmpfile.render_permanent(&path)?;
+        Ok(())
+    }
+
+    /// remove the journal if it is about the given epoch (now packed)
+    pub fn clear(blockchain: &Blockchain, epoch: EpochId) -> Result<()> {
+        match Self::load(blockchain) {
+            Some(ref journal) if journal.epoch != epoch => Ok(()),
+            _ => match fs::remove_file(Self::path(blockchain)) {
+                Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err.into()),
+                Ok(()) => Ok(()),
+            },
+        }
+    }
+
+    /// the last block of the journal, to continue the download from
+    pub fn tip(&self, blockchain: &Blockchain) -> Option<BlockRef> {
+        let hash: HeaderHash = self.last_block.parse().ok()?;
+        let block = blockchain
+            .storage
+            .read_block(hash.as_hash_bytes())
+            .ok()?
+            .decode()
+            .ok()?;
+        let header = block.get_header();
+        if header.get_blockdate().get_epochid() != self.epoch {
+            return None;
+        }
+        Some(BlockRef {
+            hash: hash,
+            parent: header.get_previous_header(),
+            date: header.get_blockdate(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn journal_roundtrip() {
+        let journal = Journal {
+            epoch: 42,
+            last_block: format!("{:064x}", 7),
+        };
+        let yaml = serde_yaml::to_string(&journal).unwrap();
+        let loaded: Journal = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded.epoch, 42);
+        assert_eq!(loaded.last_block, journal.last_block);
+    }
+
+    #[test]
+    fn journal_with_a_peer_still_loads() {
+        // the journals written before the peer was dropped from them
+        let yaml = format!("---\npeer: hermes\nepoch: 3\nlast_block: {:064x}\n", 7);
+        let loaded: Journal = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded.epoch, 3);
+        assert!(loaded.last_block.parse::<HeaderHash>().is_ok());
+    }
+}
@@ -1,6 +1,8 @@
 pub mod commands;
 pub mod config;
 pub mod error;
+mod fetch;
+mod journal;
 pub mod peer;
 
 pub use self::error::{Error, Result};
@@ -192,10 +194,10 @@ impl Blockchain {
         format!("remote/{}", remote)
     }
 
-    pub fn load_remote_tips(&self) -> Vec<(BlockRef, bool)> {
+    pub fn load_remote_tips(&self) -> Result<Vec<(BlockRef, bool)>> {
         self.peers()
             .map(|np| {
-                let peer = peer::Peer::prepare(self, np.name().to_owned());
+                let peer = peer::Peer::prepare(self, np.name().to_owned())?;
                 peer.load_local_tip()
             })
             .collect()
@@ -249,6 +251,28 @@ impl Blockchain {
         tag::write_hash(&self.storage, &LOCAL_BLOCKCHAIN_TIP_TAG, hh);
     }
 
+    /// the dates of the latest `count` blocks of the local blockchain, the
+    /// tip first. The epoch boundary blocks are not counted.
+    pub fn latest_block_dates(&self, count: usize) -> Result<Vec<block::BlockDate>> {
+        let mut dates = Vec::with_capacity(count);
+        let mut hash = self.load_tip().0.hash;
+        while dates.len() < count {
+            let block = match self.storage.read_block(hash.as_hash_bytes()) {
+                // the parent of the first block is the genesis
+                Err(storage::Error::BlockNotFound(_)) => break,
+                Err(err) => return Err(err.into()),
+                Ok(block) => block.decode().map_err(Error::VerifyMalformedBlock)?,
+            };
+            let header = block.get_header();
+            let date = header.get_blockdate();
+            if !date.is_boundary() {
+                dates.push(date);
+            }
+            hash = header.get_previous_header();
+        }
+        Ok(dates)
+    }
+
     pub fn iter<'a>(
         &'a self,
         from: block::HeaderHash,
@@ -1,19 +1,53 @@
 use cardano::{
-    block::{BlockDate, EpochId, HeaderHash},
+    block::{self, BlockDate, EpochId, HeaderHash, RawBlock},
     tx::TxAux,
 };
 use cardano_storage::{self as storage, tag};
 use exe_common;
-use exe_common::network::{api::Api, api::BlockRef};
+use exe_common::network::{self, api::Api, api::BlockRef};
+use std::io::{self, Write};
 use std::mem;
 use std::ops::Deref;
-use std::time::SystemTime;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
 use storage_units::packfile;
-use utils::term::Term;
+use utils::term::{style::Style, Term};
 
+use super::{
+    journal::{Journal, JOURNAL_INTERVAL},
+    Error,
+};
+
+/// how long a peer has to answer the handshake
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// how long a peer has to answer a request (or to send the next block)
+pub const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// run `f` in its own thread, giving up on it after the given time: the
+/// network connections have no timeout of their own.
+fn with_timeout<T, F>(timeout: Duration, f: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+    receiver.recv_timeout(timeout).ok()
+}
+
+/// a connected peer
+///
+/// Every request runs in its own thread (see `with_timeout`): a request which
+/// times out leaves this thread behind, blocked on the connection until the
+/// remote node answers or closes it, and the connection is not used anymore.
 pub struct ConnectedPeer<'a> {
     peer: Peer<'a>,
-    connection: exe_common::network::Peer,
+    /// `None` once a request timed out: the connection is left behind
+    connection: Option<exe_common::network::Peer>,
 }
 impl<'a> Deref for ConnectedPeer<'a> {
     type Target = Peer<'a>;
@@ -22,48 +56,116 @@ impl<'a> Deref for ConnectedPeer<'a> {
     }
 }
 impl<'a> ConnectedPeer<'a> {
+    fn request<T, F>(&mut self, f: F) -> super::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut network::Peer) -> Result<T, network::Error> + Send + 'static,
+    {
+        let name = self.peer.name.clone();
+        let mut connection = match self.connection.take() {
+            None => return Err(Error::PeerTimedOut(name)),
+            Some(connection) => connection,
+        };
+        match with_timeout(READ_TIMEOUT, move || {
+            let result = f(&mut connection);
+            (connection, result)
+        }) {
+            None => Err(Error::PeerTimedOut(name)),
+            Some((connection, result)) => {
+                self.connection = Some(connection);
+                result.map_err(|err| Error::PeerProtocolError(name, err))
+            }
+        }
+    }
+
     /// get the remote tip
-    pub fn query_tip(&mut self) -> BlockRef {
-        let tip_header = self.connection.get_tip().unwrap();
-        BlockRef {
+    pub fn query_tip(&mut self) -> super::Result<BlockRef> {
+        let tip_header = self.request(|connection| connection.get_tip())?;
+        Ok(BlockRef {
             hash: tip_header.compute_hash(),
             parent: tip_header.get_previous_header(),
             date: tip_header.get_blockdate(),
-        }
+        })
     }
 
-    pub fn send_txaux(mut self, txaux: TxAux) -> bool {
-        match self.connection.send_transaction(txaux) {
-            Err(err) => {
-                warn!("cannot send transaction to {}: {}", self.peer.name, err);
-                false
-            }
-            Ok(sent) => sent,
-        }
+    pub fn send_txaux(mut self, txaux: TxAux) -> super::Result<bool> {
+        self.request(move |connection| connection.send_transaction(txaux))
     }
+}
+
+/// the furthest of the given tips, the first one among the equals
+fn furthest_tip<I>(our_tip: (BlockRef, bool), tips: I) -> (BlockRef, bool)
+where
+    I: IntoIterator<Item = (BlockRef, bool)>,
+{
+    tips.into_iter().fold(our_tip, |best_tip, current_tip| {
+        if best_tip.0.date < current_tip.0.date {
+            current_tip
+        } else {
+            best_tip
+        }
+    })
+}
+
+/// where to continue the download from: the furthest of our tip, the tips of
+/// the other peers and the last block of an interrupted download of a stable
+/// epoch (from the journal), unless this epoch got packed since
+fn resume_tip<I, F>(
+    our_tip: (BlockRef, bool),
+    remote_tips: I,
+    journal_tip: Option<BlockRef>,
+    epoch_packed: F,
+) -> (BlockRef, bool)
+where
+    I: IntoIterator<Item = (BlockRef, bool)>,
+    F: Fn(EpochId) -> bool,
+{
+    let journal_tip = journal_tip
+        .filter(|tip| !epoch_packed(tip.date.get_epochid()))
+        .map(|tip| (tip, false));
+    furthest_tip(our_tip, remote_tips.into_iter().chain(journal_tip))
+}
 
-    pub fn sync(mut self, term: &mut Term) -> Peer<'a> {
-        // recover and print the TIP of the network
-        let tip = self.query_tip();
+/// the blocks being downloaded from a peer: every block received is checked
+/// (and must follow the previous one) before being written to the storage.
+pub struct Download<'a> {
+    peer: Peer<'a>,
+    /// where the download starts, the bool tells if this block is to be
+    /// downloaded too (the boundary block of the blockchain)
+    pub from: (BlockRef, bool),
+    pub to: BlockRef,
+    first_unstable_epoch: EpochId,
+    cur_epoch_state: Option<(EpochId, packfile::Writer, SystemTime)>,
+    /// the blocks of the epoch being packed received since the last update
+    /// of the journal
+    unjournaled: usize,
+    /// the hash the next block is expected to point to
+    expected_previous: HeaderHash,
+    last_block: Option<HeaderHash>,
+}
+impl<'a> Download<'a> {
+    /// prepare the download of the blocks up to the given remote tip
+    ///
+    /// `None` if there is nothing to download from this peer (its tip
+    /// is then saved).
+    pub fn new(peer: &Peer<'a>, tip: &BlockRef) -> super::Result<Option<Self>> {
+        let peer = peer.clone();
 
         // Start fetching at the current HEAD tag, or the boundary block if
         // it doesn't exist.
-        let our_tip = self.load_local_tip();
+        let our_tip = peer.load_local_tip()?;
 
-        let mut best_tip = self.peer.blockchain.load_remote_tips().into_iter().fold(
+        // Or where a previous download of a stable epoch was interrupted.
+        let journal_tip =
+            Journal::load(peer.blockchain).and_then(|journal| journal.tip(peer.blockchain));
+
+        let mut best_tip = resume_tip(
             our_tip.clone(),
-            |best_tip, current_tip| {
-                if best_tip.0.date < current_tip.0.date {
-                    current_tip
-                } else {
-                    best_tip
-                }
-            },
+            peer.blockchain.load_remote_tips()?,
+            journal_tip,
+            |epoch_id| internal::epoch_exists(&peer.blockchain.storage, epoch_id),
         );
 
-        let mut connection = self.connection;
-        let peer = self.peer;
-
         if best_tip.0.date < tip.date {
             // do nothing, best_tip is behind the remote tip.
         } else if best_tip.0.date > tip.date {
@@ -71,17 +173,17 @@ impl<'a> ConnectedPeer<'a> {
                 Err(storage::Error::BlockNotFound(_)) => {
                     // we don't have the block locally... might be a fork, we need to download the
                     // blockchain anyway
-                    term.info(
-                        "remote may have forked from the consensus. Download the blocks anyway.",
-                    )
-                    .unwrap();
+                    info!(
+                        "remote {} may have forked from the consensus. Download the blocks anyway.",
+                        peer.name
+                    );
                     best_tip = our_tip;
                 }
-                Err(err) => panic!(err),
+                Err(err) => return Err(err.into()),
                 Ok(_) => {
-                    term.info("remote already as further as it takes").unwrap();
+                    info!("remote {} already as further as it takes", peer.name);
                     peer.save_peer_local_tip(&tip.hash);
-                    return peer;
+                    return Ok(None);
                 }
             }
         } else {
@@ -89,7 +191,7 @@ impl<'a> ConnectedPeer<'a> {
             if best_tip.0.hash == tip.hash {
                 // this is the same block hash. save the local tip
                 peer.save_peer_local_tip(&tip.hash);
-                return peer;
+                return Ok(None);
             } else {
                 // it seems the best_tip is for the same date, but has a different hash
                 // it could be there is a fork between the remotes.
@@ -137,8 +239,7 @@ impl<'a> ConnectedPeer<'a> {
             && !internal::epoch_exists(&peer.blockchain.storage, best_tip.0.date.get_epochid())
         {
             let epoch_id = best_tip.0.date.get_epochid();
-            let mut writer =
-                storage::pack::packwriter_init(&peer.blockchain.storage.config).unwrap();
+            let mut writer = storage::pack::packwriter_init(&peer.blockchain.storage.config)?;
             let epoch_time_start = SystemTime::now();
 
             let prev_block = internal::append_blocks_to_epoch_reverse(
@@ -146,7 +247,7 @@ impl<'a> ConnectedPeer<'a> {
                 epoch_id,
                 &mut writer,
                 &best_tip.0.hash,
-            );
+            )?;
 
             cur_epoch_state = Some((epoch_id, writer, epoch_time_start));
             last_block = Some(best_tip.0.hash.clone());
@@ -154,7 +255,7 @@ impl<'a> ConnectedPeer<'a> {
             // If tip.slotid < w, the previous epoch won't have been
             // created yet either, so do that now.
             if epoch_id > peer.blockchain.config.epoch_start {
-                internal::maybe_create_epoch(&peer.blockchain.storage, epoch_id - 1, &prev_block);
+                internal::maybe_create_epoch(&peer.blockchain.storage, epoch_id - 1, &prev_block)?;
             }
         }
         // If the previous epoch has become stable, then we may need to
@@ -169,9 +270,8 @@ impl<'a> ConnectedPeer<'a> {
                 let block_raw = peer
                     .blockchain
                     .storage
-                    .read_block(cur_hash.as_hash_bytes())
-                    .unwrap();
-                let block = block_raw.decode().unwrap();
+                    .read_block(cur_hash.as_hash_bytes())?;
+                let block = block_raw.decode().map_err(Error::VerifyMalformedBlock)?;
                 let hdr = block.get_header();
                 assert!(hdr.get_blockdate().get_epochid() == first_unstable_epoch);
                 cur_hash = hdr.get_previous_header();
@@ -183,88 +283,171 @@ impl<'a> ConnectedPeer<'a> {
                 &peer.blockchain.storage,
                 first_unstable_epoch - 1,
                 &cur_hash,
-            );
+            )?;
         }
 
-        // initialisation of the progress bar:
-        let count = tip.date - best_tip.0.date;
-        let pbr = term.progress_bar(count as u64);
-        connection
-            .get_blocks(
-                &best_tip.0,
-                best_tip.1,
-                &tip,
-                &mut |block_hash, block, block_raw| {
-                    let date = block.get_header().get_blockdate();
-                    pbr.inc(1);
-                    pbr.set_message(&format!("downloading epoch {} -> ", date.get_epochid()));
-
-                    // Flush the previous epoch (if any).
-                    if date.is_boundary() {
-                        let mut writer_state = None;
-                        mem::swap(&mut writer_state, &mut cur_epoch_state);
-                        if let Some((epoch_id, writer, epoch_time_start)) = writer_state {
-                            internal::finish_epoch(
-                                &peer.blockchain.storage,
-                                epoch_id,
-                                writer,
-                                &epoch_time_start,
-                            );
-
-                            // Checkpoint the tip so we don't have to refetch
-                            // everything if we get interrupted.
-                            peer.save_peer_local_tip(last_block.as_ref().unwrap());
-                        }
-                    }
+        // the boundary block of the blockchain is downloaded too
+        let expected_previous = if best_tip.1 {
+            best_tip.0.parent.clone()
+        } else {
+            best_tip.0.hash.clone()
+        };
 
-                    if date.get_epochid() >= first_unstable_epoch {
-                        // This block is not part of a stable epoch yet and could
-                        // be rolled back. Therefore we can't pack this epoch
-                        // yet. Instead we write this block to disk separately.
-                        let block_hash = storage::types::header_to_blockhash(&block_hash);
-                        storage::blob::write(
-                            &peer.blockchain.storage,
-                            &block_hash,
-                            block_raw.as_ref(),
-                        )
-                        .unwrap();
-                    } else {
-                        // If this is the epoch boundary block, start writing a new epoch pack.
-                        if date.is_boundary() {
-                            cur_epoch_state = Some((
-                                date.get_epochid(),
-                                storage::pack::packwriter_init(&peer.blockchain.storage.config)
-                                    .unwrap(),
-                                SystemTime::now(),
-                            ));
-                        }
-
-                        // And append the block to the epoch pack.
-                        let (_, writer, _) = &mut cur_epoch_state.as_mut().unwrap();
-                        writer
-                            .append(
-                                &storage::types::header_to_blockhash(&block_hash),
-                                block_raw.as_ref(),
-                            )
-                            .unwrap();
-                    }
+        Ok(Some(Download {
+            peer: peer,
+            from: best_tip,
+            to: tip.clone(),
+            first_unstable_epoch: first_unstable_epoch,
+            cur_epoch_state: cur_epoch_state,
+            unjournaled: 0,
+            expected_previous: expected_previous,
+            last_block: last_block,
+        }))
+    }
 
-                    last_block = Some(block_hash.clone());
-                },
-            )
-            .unwrap();
-        pbr.finish();
+    /// the number of slots to download
+    pub fn count(&self) -> u64 {
+        (self.to.date - self.from.0.date) as u64
+    }
 
-        // Update the tip tag to point to the most recent block.
-        if let Some(block_hash) = last_block {
-            peer.save_peer_local_tip(&block_hash);
+    /// check the given block and write it to the storage
+    pub fn append(
+        &mut self,
+        block_hash: &HeaderHash,
+        block_raw: &RawBlock,
+    ) -> super::Result<BlockDate> {
+        let block = block_raw.decode().map_err(Error::VerifyMalformedBlock)?;
+        let previous = block.get_header().get_previous_header();
+        if previous != self.expected_previous {
+            return Err(Error::FetchBlockNotChained(
+                self.expected_previous.clone(),
+                previous,
+            ));
+        }
+        block::verify_block(block_hash, &block).map_err(Error::VerifyInvalidBlock)?;
+
+        let peer = &self.peer;
+        let date = block.get_header().get_blockdate();
+
+        // Flush the previous epoch (if any).
+        let mut packed = false;
+        if date.is_boundary() {
+            let mut writer_state = None;
+            mem::swap(&mut writer_state, &mut self.cur_epoch_state);
+            if let Some((epoch_id, writer, epoch_time_start)) = writer_state {
+                internal::finish_epoch(
+                    &peer.blockchain.storage,
+                    epoch_id,
+                    writer,
+                    &epoch_time_start,
+                )?;
+                self.unjournaled = 0;
+                Journal::clear(peer.blockchain, epoch_id)?;
+
+                // The blocks are in the pack now.
+                internal::remove_loose_blocks(
+                    &peer.blockchain.storage,
+                    epoch_id,
+                    self.last_block.as_ref().unwrap(),
+                )?;
+                packed = true;
+            }
+        }
+
+        if date.get_epochid() >= self.first_unstable_epoch {
+            // This block is not part of a stable epoch yet and could
+            // be rolled back. Therefore we can't pack this epoch
+            // yet. Instead we write this block to disk separately.
+            let block_hash = storage::types::header_to_blockhash(&block_hash);
+            storage::blob::write(&peer.blockchain.storage, &block_hash, block_raw.as_ref())?;
+        } else {
+            // If this is the epoch boundary block, start writing a new epoch pack.
+            if date.is_boundary() {
+                self.cur_epoch_state = Some((
+                    date.get_epochid(),
+                    storage::pack::packwriter_init(&peer.blockchain.storage.config)?,
+                    SystemTime::now(),
+                ));
+            }
+
+            // And append the block to the epoch pack, and write it as a
+            // loose block for an interrupted download to continue from.
+            let (_, writer, _) = &mut self.cur_epoch_state.as_mut().unwrap();
+            let block_hash = storage::types::header_to_blockhash(&block_hash);
+            writer.append(&block_hash, block_raw.as_ref())?;
+            storage::blob::write(&peer.blockchain.storage, &block_hash, block_raw.as_ref())?;
+            self.unjournaled += 1;
+        }
+
+        // Checkpoint the tip so we don't have to refetch everything if we
+        // get interrupted. This is the boundary block rather than the last
+        // block of the epoch just packed, which is not loose anymore: the
+        // packs written by this process may only be read from once the
+        // storage is loaded again.
+        if packed {
+            peer.save_peer_local_tip(block_hash);
+        }
+
+        self.expected_previous = block_hash.clone();
+        self.last_block = Some(block_hash.clone());
+        if self.unjournaled >= JOURNAL_INTERVAL {
+            self.save_journal()?;
+        }
+        Ok(date)
+    }
+
+    /// record the last block of the epoch being packed in the journal
+    fn save_journal(&mut self) -> super::Result<()> {
+        let epoch_id = match self.cur_epoch_state {
+            None => return Ok(()),
+            Some((epoch_id, _, _)) => epoch_id,
+        };
+        let last_block = match self.last_block {
+            None => return Ok(()),
+            Some(ref block_hash) => block_hash.to_string(),
+        };
+        self.unjournaled = 0;
+        Journal {
+            epoch: epoch_id,
+            last_block: last_block,
+        }
+        .save(self.peer.blockchain)
+    }
+
+    /// the download is interrupted: keep the blocks received so far, for
+    /// the next download to continue from the last of them.
+    ///
+    /// The blocks of the epoch being packed are already written as loose
+    /// blocks, the last of them is recorded in the journal (the partial pack
+    /// is dropped). Otherwise the tip tag of the peer is updated.
+    pub fn suspend(mut self) -> super::Result<()> {
+        match self.cur_epoch_state {
+            None => {
+                if let Some(ref block_hash) = self.last_block {
+                    self.peer.save_peer_local_tip(block_hash);
+                }
+                Ok(())
+            }
+            Some((epoch_id, _, _)) => {
+                if let Some(ref block_hash) = self.last_block {
+                    info!("blocks of epoch {} kept, up to {}", epoch_id, block_hash);
+                }
+                self.save_journal()
+            }
         }
+    }
 
-        peer
+    /// all the blocks were received: update the tip tag of the peer
+    pub fn finish(self) {
+        // Update the tip tag to point to the most recent block.
+        if let Some(block_hash) = self.last_block {
+            self.peer.save_peer_local_tip(&block_hash);
+        }
     }
 }
 
 /// a connected peer
+#[derive(Clone)]
 pub struct Peer<'a> {
     /// keep a reference to the upper blockchain, we will need to drop
     /// the peer before finalising the blockchain
@@ -279,63 +462,58 @@ pub struct Peer<'a> {
     pub tag: String,
 }
 impl<'a> Peer<'a> {
-    pub fn prepare(blockchain: &'a super::Blockchain, name: String) -> Self {
+    pub fn prepare(blockchain: &'a super::Blockchain, name: String) -> super::Result<Self> {
         let config = match blockchain.peers().find(|np| np.name() == &name) {
-            None => panic!(""),
+            None => return Err(Error::PeerNotConfigured(name)),
             Some(np) => np.peer().clone(),
         };
         let tag = blockchain.mk_remote_tag(&name);
 
-        Peer {
+        Ok(Peer {
             blockchain,
             name,
             config: config,
             tag,
-        }
+        })
     }
 
     /// initialise the connection by performing initial handshake (if necessary).
-    pub fn connect(self, term: &mut Term) -> Result<ConnectedPeer<'a>, ()> {
-        let peer_handshake = exe_common::network::Peer::new(
-            self.blockchain.name.as_ref().to_owned(),
-            self.name.to_owned(),
-            self.config.clone(),
-            self.blockchain.config.protocol_magic,
-        );
+    pub fn connect(self) -> super::Result<ConnectedPeer<'a>> {
+        let blockchain_name = self.blockchain.name.as_ref().to_owned();
+        let name = self.name.to_owned();
+        let config = self.config.clone();
+        let protocol_magic = self.blockchain.config.protocol_magic;
+        let peer_handshake = with_timeout(CONNECT_TIMEOUT, move || {
+            exe_common::network::Peer::new(blockchain_name, name, config, protocol_magic)
+        });
 
         let connection = match peer_handshake {
-            Err(err) => {
-                term.warn(&format!(
-                    "Unable to initiate handshake with peer {} ({})\n\t{:?}\n",
-                    self.name, self.config, err
-                ))
-                .unwrap();
-                return Err(());
-            }
-            Ok(peer) => peer,
+            None => return Err(Error::PeerTimedOut(self.name)),
+            Some(Err(err)) => return Err(Error::PeerUnreachable(self.name, err)),
+            Some(Ok(peer)) => peer,
         };
 
         Ok(ConnectedPeer {
             peer: self,
-            connection,
+            connection: Some(connection),
         })
     }
 
     /// load the peer current block
-    pub fn load_peer_local_tip(&self) -> HeaderHash {
+    pub fn load_peer_local_tip(&self) -> super::Result<HeaderHash> {
         match tag::read_hash(&self.blockchain.storage, &self.tag) {
-            None => panic!("expecting any peer to have a tag"),
-            Some(hh) => hh,
+            None => Err(Error::PeerNoLocalTip(self.name.clone())),
+            Some(hh) => Ok(hh),
         }
     }
 
     /// save the given peer header hash
-    fn save_peer_local_tip(&self, tip: &HeaderHash) {
+    pub fn save_peer_local_tip(&self, tip: &HeaderHash) {
         tag::write_hash(&self.blockchain.storage, &self.tag, tip)
     }
 
     /// get the remote local tip. the bool is to note if the tip is the same as boundary
-    pub fn load_local_tip(&self) -> (BlockRef, bool) {
+    pub fn load_local_tip(&self) -> super::Result<(BlockRef, bool)> {
         let boundary_ref = (
             BlockRef {
                 hash: self.blockchain.config.genesis.clone(),
@@ -351,7 +529,7 @@ impl<'a> Peer<'a> {
             {
                 boundary_ref
             }
-            Err(err) => panic!("unable to fetch tag '{}': {}", self.tag, err),
+            Err(err) => return Err(err.into()),
             Ok(block) => {
                 let header = block.get_header();
                 let hash = header.compute_hash();
@@ -366,15 +544,57 @@ impl<'a> Peer<'a> {
                 )
             }
         };
-        our_tip
+        Ok(our_tip)
+    }
+}
+
+/// what happened with each peer of a command, printed at its end
+pub struct Outcomes(Vec<(String, super::Result<String>)>);
+impl Outcomes {
+    pub fn new() -> Self {
+        Outcomes(Vec::new())
+    }
+
+    pub fn push(&mut self, peer: &str, outcome: super::Result<String>) {
+        self.0.push((peer.to_owned(), outcome))
+    }
+
+    /// at least one of the peers succeeded (or there were none)
+    pub fn any_success(&self) -> bool {
+        self.0.is_empty() || self.0.iter().any(|(_, outcome)| outcome.is_ok())
+    }
+
+    pub fn print(&self, term: &mut Term) -> io::Result<()> {
+        let width = self.0.iter().map(|(peer, _)| peer.len()).max().unwrap_or(0);
+        for (peer, outcome) in self.0.iter() {
+            match outcome {
+                Ok(message) => writeln!(
+                    term,
+                    "  {:width$}  {}",
+                    peer,
+                    style!(message.as_str()).green(),
+                    width = width
+                )?,
+                Err(err) => writeln!(
+                    term,
+                    "  {:width$}  {}",
+                    peer,
+                    style!(err.to_string().as_str()).red(),
+                    width = width
+                )?,
+            }
+        }
+        Ok(())
     }
 }
 
 mod internal {
+    use super::super::{Error, Result};
     use cardano::block::{EpochId, HeaderHash};
     use cardano::util::hex;
     use cardano_storage as storage;
     use std::time::{Duration, SystemTime};
+    use std::{fs, io};
     use storage_units::packfile;
 
     fn duration_print(d: Duration) -> String {
@@ -387,21 +607,21 @@ mod internal {
         storage: &storage::Storage,
         epoch_id: EpochId,
         last_block: &HeaderHash,
-    ) {
+    ) -> Result<()> {
         if epoch_exists(&storage, epoch_id) {
-            return;
+            return Ok(());
         }
 
         info!("Packing epoch {}", epoch_id);
 
-        let mut writer = storage::pack::packwriter_init(&storage.config).unwrap();
+        let mut writer = storage::pack::packwriter_init(&storage.config)?;
         let epoch_time_start = SystemTime::now();
 
-        append_blocks_to_epoch_reverse(&storage, epoch_id, &mut writer, last_block);
+        append_blocks_to_epoch_reverse(&storage, epoch_id, &mut writer, last_block)?;
 
-        finish_epoch(storage, epoch_id, writer, &epoch_time_start);
+        finish_epoch(storage, epoch_id, writer, &epoch_time_start)?;
 
-        // TODO: delete the blocks from disk?
+        remove_loose_blocks(storage, epoch_id, last_block)
     }
 
     // Check whether an epoch pack exists on disk.
@@ -416,12 +636,12 @@ mod internal {
         epoch_id: EpochId,
         writer: &mut packfile::Writer,
         last_block: &HeaderHash,
-    ) -> HeaderHash {
+    ) -> Result<HeaderHash> {
         let mut cur_hash = last_block.clone();
         let mut blocks = vec![];
         loop {
-            let block_raw = storage.read_block(cur_hash.as_hash_bytes()).unwrap();
-            let block = block_raw.decode().unwrap();
+            let block_raw = storage.read_block(cur_hash.as_hash_bytes())?;
+            let block = block_raw.decode().map_err(Error::VerifyMalformedBlock)?;
             let hdr = block.get_header();
             assert!(hdr.get_blockdate().get_epochid() == epoch_id);
             blocks.push((storage::types::header_to_blockhash(&cur_hash), block_raw));
@@ -432,10 +652,39 @@ mod internal {
         }
 
         while let Some((hash, block_raw)) = blocks.pop() {
-            writer.append(&hash, block_raw.as_ref()).unwrap();
+            writer.append(&hash, block_raw.as_ref())?;
         }
 
-        cur_hash
+        Ok(cur_hash)
+    }
+
+    // Remove the loose blocks of an epoch now packed, from its last block
+    // back to its boundary block.
+    pub fn remove_loose_blocks(
+        storage: &storage::Storage,
+        epoch_id: EpochId,
+        last_block: &HeaderHash,
+    ) -> Result<()> {
+        let mut cur_hash = last_block.clone();
+        loop {
+            let block_raw = storage.read_block(cur_hash.as_hash_bytes())?;
+            let block = block_raw.decode().map_err(Error::VerifyMalformedBlock)?;
+            let hdr = block.get_header();
+            assert!(hdr.get_blockdate().get_epochid() == epoch_id);
+
+            let path = storage.config.get_blob_filepath(cur_hash.as_hash_bytes());
+            match fs::remove_file(path) {
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+                Ok(()) => {}
+            }
+
+            cur_hash = hdr.get_previous_header();
+            if hdr.get_blockdate().is_boundary() {
+                break;
+            }
+        }
+        Ok(())
     }
 
     pub fn finish_epoch(
@@ -443,12 +692,10 @@ mod internal {
         epoch_id: EpochId,
         writer: packfile::Writer,
         epoch_time_start: &SystemTime,
-    ) {
+    ) -> Result<()> {
         let (packhash, index) = storage::pack::packwriter_finalize(&storage.config, writer);
         let (_, tmpfile) = storage::pack::create_index(&storage, &index);
-        tmpfile
-            .render_permanent(&storage.config.get_index_filepath(&packhash))
-            .unwrap();
+        tmpfile.render_permanent(&storage.config.get_index_filepath(&packhash))?;
         let epoch_time_elapsed = epoch_time_start.elapsed().unwrap();
 
         // TODO: should test that epoch <epoch_id - 1> exists.
@@ -461,5 +708,70 @@ mod internal {
             epoch_id,
             duration_print(epoch_time_elapsed)
         );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block_ref(n: u32, epoch: EpochId, slot: u16) -> BlockRef {
+        let hash: HeaderHash = format!("{:064x}", n).parse().unwrap();
+        BlockRef {
+            hash: hash.clone(),
+            parent: hash,
+            date: BlockDate::Normal(block::EpochSlotId {
+                epoch: epoch,
+                slotid: slot.into(),
+            }),
+        }
+    }
+
+    fn tip(n: u32, slot: u8) -> (BlockRef, bool) {
+        (block_ref(n, 0, slot.into()), false)
+    }
+
+    #[test]
+    fn resume_from_the_journal() {
+        // the journal is further than our tip and the remote tips
+        let best_tip = furthest_tip(tip(1, 10), vec![tip(2, 5), tip(3, 200)]);
+        assert_eq!(best_tip.0.hash, tip(3, 200).0.hash);
+    }
+
+    #[test]
+    fn resume_from_our_tip() {
+        let best_tip = furthest_tip(tip(1, 10), vec![tip(2, 10), tip(3, 5)]);
+        assert_eq!(best_tip.0.hash, tip(1, 10).0.hash);
+        let best_tip = furthest_tip(tip(1, 10), vec![]);
+        assert_eq!(best_tip.0.hash, tip(1, 10).0.hash);
+    }
+
+    #[test]
+    fn resume_an_interrupted_stable_epoch() {
+        // the download of the stable epoch 3 stopped at its slot 5000, our
+        // tip is at the end of the epoch 2
+        let our_tip = (block_ref(1, 2, 21599), false);
+        let remote_tips = vec![(block_ref(2, 1, 100), false)];
+        let journal_tip = block_ref(3, 3, 5000);
+
+        let best_tip = resume_tip(
+            our_tip.clone(),
+            remote_tips.clone(),
+            Some(journal_tip.clone()),
+            |epoch_id| epoch_id < 3,
+        );
+        assert_eq!(best_tip.0.hash, journal_tip.hash);
+        // the last block of the journal is not downloaded again
+        assert!(!best_tip.1);
+
+        // the epoch got packed since (by the download from another peer)
+        let best_tip = resume_tip(
+            our_tip.clone(),
+            remote_tips,
+            Some(journal_tip),
+            |epoch_id| epoch_id <= 3,
+        );
+        assert_eq!(best_tip.0.hash, our_tip.0.hash);
     }
 }
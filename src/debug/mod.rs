@@ -1,6 +1,6 @@
 use cardano::hdwallet;
 use cardano::{
-    address::{ExtendedAddr, StakeDistribution},
+    address::{ExtendedAddr, SpendingData, StakeDistribution},
     hash,
     util::{base58, hex, try_from_slice::TryFromSlice},
 };
@@ -159,6 +159,62 @@ pub fn xprv_to_xpub(input_priv: &str, output_pub: &str) {
     ()
 }
 
+/// Check the signature of a message made with `wallet sign-message`:
+/// the given public key must be the one of the address, and the signature
+/// must be valid for this key.
+pub fn verify_message(
+    mut term: Term,
+    address: String,
+    xpub: String,
+    signature: String,
+    message: String,
+) {
+    let address = match address.parse::<ExtendedAddr>() {
+        Err(err) => {
+            term.error(&format!("Invalid Address\n")).unwrap();
+            term.error(&format!("{:?}\n", err)).unwrap();
+            ::std::process::exit(2)
+        }
+        Ok(address) => address,
+    };
+    let xpub = match hex::decode(&xpub).map(|bytes| hdwallet::XPub::from_slice(&bytes)) {
+        Ok(Ok(xpub)) => xpub,
+        _ => {
+            term.error("Invalid XPub, should be encoded in hexadecimal\n")
+                .unwrap();
+            ::std::process::exit(2)
+        }
+    };
+    let signature: hdwallet::Signature<Vec<u8>> =
+        match hex::decode(&signature).map(|bytes| hdwallet::Signature::from_slice(&bytes)) {
+            Ok(Ok(signature)) => signature,
+            _ => {
+                term.error("Invalid signature, should be encoded in hexadecimal\n")
+                    .unwrap();
+                ::std::process::exit(2)
+            }
+        };
+
+    let expected = ExtendedAddr::new(
+        address.addr_type,
+        SpendingData::PubKeyASD(xpub),
+        address.attributes.clone(),
+    );
+    if expected.addr != address.addr {
+        term.error("The public key is not the one of the address\n")
+            .unwrap();
+        ::std::process::exit(1)
+    }
+
+    let data = ::wallet::utils::message_signing_data(&message);
+    if !xpub.verify(&data, &signature) {
+        term.error("Invalid signature\n").unwrap();
+        ::std::process::exit(1)
+    }
+
+    term.success("Valid signature\n").unwrap();
+}
+
 pub fn chain_state_dump(storage: &std::path::Path) {
     /*
         use cardano_storage::
@@ -26,9 +26,12 @@ extern crate rand;
 extern crate serde;
 extern crate serde_json;
 extern crate serde_yaml;
+#[cfg(unix)]
+extern crate libc;
 
 #[macro_use]
 mod utils;
+mod agent;
 mod blockchain;
 mod debug;
 mod transaction;
@@ -41,6 +44,9 @@ extern crate clap;
 use clap::{App, Arg, ArgMatches, SubCommand};
 
 fn main() {
+    // the process holds passwords and private keys
+    utils::secret::disable_core_dumps();
+
     let default_root_dir = get_default_root_dir();
 
     let commit_hash: &'static str = option_env!("TRAVIS_COMMIT")
@@ -69,6 +75,7 @@ commit: {}
         .subcommand(blockchain_commands_definition())
         .subcommand(wallet_commands_definition())
         .subcommand(transaction_commands_definition())
+        .subcommand(agent_commands_definition())
         .subcommand(debug_commands_definition())
         .get_matches();
 
@@ -82,6 +89,7 @@ commit: {}
         (BLOCKCHAIN_COMMAND, Some(matches)) => subcommand_blockchain(term, root_dir, matches),
         (WALLET_COMMAND, Some(matches)) => subcommand_wallet(term, root_dir, matches),
         (TRANSACTION_COMMAND, Some(matches)) => subcommand_transaction(term, root_dir, matches),
+        (AGENT_COMMAND, Some(matches)) => subcommand_agent(term, root_dir, matches),
         (DEBUG_COMMAND, Some(matches)) => subcommand_debug(term, root_dir, matches),
         _ => {
             term.error(matches.usage()).unwrap();
@@ -747,6 +755,9 @@ fn subcommand_wallet<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgM
             let derivation_scheme = wallet_argument_derivation_scheme_match(&matches);
             let mnemonic_length = wallet_argument_mnemonic_size_match(&matches);
             let mnemonic_langs = wallet_argument_mnemonic_languages_match(&matches);
+            let entropy_source = value_t!(matches, "ENTROPY_SOURCE", wallet::EntropySource)
+                .unwrap_or_else(|e| e.exit());
+            let mix_system_entropy = matches.is_present("MIX_SYSTEM_ENTROPY");
 
             wallet::commands::new(
                 &mut term,
@@ -755,6 +766,8 @@ fn subcommand_wallet<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgM
                 wallet_scheme,
                 derivation_scheme,
                 mnemonic_length,
+                entropy_source,
+                mix_system_entropy,
                 mnemonic_langs,
             )
         }
@@ -766,6 +779,25 @@ fn subcommand_wallet<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgM
             let mnemonic_lang = wallet_argument_mnemonic_language_match(&matches);
             let daedalus_seed = wallet_argument_daedalus_seed_match(&matches);
             let interactive = matches.is_present("RECOVER_INTERACTIVE");
+            let paper = matches.is_present("RECOVER_PAPER");
+            let from_shares = matches.is_present("RECOVER_FROM_SHARES");
+            let repair = matches.is_present("RECOVER_REPAIR");
+            let address = if matches.is_present("RECOVER_ADDRESS") {
+                Some(
+                    value_t!(matches, "RECOVER_ADDRESS", cardano::address::ExtendedAddr)
+                        .unwrap_or_else(|e| e.exit()),
+                )
+            } else {
+                None
+            };
+            let blockchain = if matches.is_present("RECOVER_BLOCKCHAIN") {
+                Some(
+                    value_t!(matches, "RECOVER_BLOCKCHAIN", blockchain::BlockchainName)
+                        .unwrap_or_else(|e| e.exit()),
+                )
+            } else {
+                None
+            };
 
             if daedalus_seed {
                 if wallet_scheme != wallet::HDWalletModel::RandomIndex2Levels {
@@ -790,9 +822,103 @@ fn subcommand_wallet<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgM
                 mnemonic_length,
                 interactive,
                 daedalus_seed,
+                paper,
+                from_shares,
+                repair,
+                address,
+                blockchain,
+                mnemonic_lang,
+            )
+        }
+        ("inspect-mnemonic", Some(matches)) => {
+            let wallet_scheme = wallet_argument_wallet_scheme_match(&matches);
+            let derivation_scheme = wallet_argument_derivation_scheme_match(&matches);
+            let mnemonic_length = wallet_argument_mnemonic_size_match(&matches);
+            let mnemonic_lang = wallet_argument_mnemonic_language_match(&matches);
+            let daedalus_seed = wallet_argument_daedalus_seed_match(&matches);
+            let interactive = matches.is_present("RECOVER_INTERACTIVE");
+            let protocol_magic = if matches.is_present("PROTOCOL_MAGIC") {
+                Some(value_t!(matches, "PROTOCOL_MAGIC", u32).unwrap_or_else(|e| e.exit()))
+            } else {
+                None
+            };
+            let count = value_t!(matches, "INSPECT_ADDRESS_COUNT", u32).unwrap_or_else(|e| e.exit());
+
+            wallet::commands::inspect_mnemonic(
+                &mut term,
+                wallet_scheme,
+                derivation_scheme,
+                mnemonic_length,
+                interactive,
+                daedalus_seed,
+                protocol_magic,
+                count,
+                mnemonic_lang,
+            )
+        }
+        ("import-daedalus-keyfile", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let keyfile = PathBuf::from(matches.value_of("DAEDALUS_KEYFILE").unwrap());
+            let key_index = if matches.is_present("DAEDALUS_KEY_INDEX") {
+                Some(value_t!(matches, "DAEDALUS_KEY_INDEX", usize).unwrap_or_else(|e| e.exit()))
+            } else {
+                None
+            };
+
+            wallet::commands::import_daedalus_keyfile(&mut term, root_dir, name, keyfile, key_index)
+        }
+        ("split", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let threshold = value_t!(matches, "SPLIT_THRESHOLD", u8).unwrap_or_else(|e| e.exit());
+            let shares = value_t!(matches, "SPLIT_SHARES", u8).unwrap_or_else(|e| e.exit());
+            let mnemonic_length = wallet_argument_mnemonic_size_match(&matches);
+            let daedalus_seed = wallet_argument_daedalus_seed_match(&matches);
+            let mnemonic_lang = wallet_argument_mnemonic_language_match(&matches);
+
+            wallet::commands::split(
+                &mut term,
+                root_dir,
+                name,
+                threshold,
+                shares,
+                mnemonic_length,
+                daedalus_seed,
+                mnemonic_lang,
+            )
+        }
+        ("paper", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let derivation_scheme = wallet_argument_derivation_scheme_match(&matches);
+            let mnemonic_length = wallet_argument_mnemonic_size_match(&matches);
+            let mnemonic_lang = wallet_argument_mnemonic_language_match(&matches);
+            let protocol_magic = if matches.is_present("PROTOCOL_MAGIC") {
+                Some(value_t!(matches, "PROTOCOL_MAGIC", u32).unwrap_or_else(|e| e.exit()))
+            } else {
+                None
+            };
+            let output = matches.value_of("PAPER_OUTPUT").map(PathBuf::from);
+
+            wallet::commands::paper(
+                &mut term,
+                name,
+                derivation_scheme,
+                mnemonic_length,
+                protocol_magic,
+                output,
                 mnemonic_lang,
             )
         }
+        ("address", Some(matches)) if matches.is_present("NEXT_ADDRESS") => {
+            let name = wallet_argument_name_match(&matches);
+            let protocol_magic = if matches.is_present("PROTOCOL_MAGIC") {
+                Some(value_t!(matches, "PROTOCOL_MAGIC", u32).unwrap_or_else(|e| e.exit()))
+            } else {
+                None
+            };
+            let force = matches.is_present("FORCE");
+
+            wallet::commands::address_next(&mut term, root_dir, name, protocol_magic, force)
+        }
         ("address", Some(matches)) => {
             let name = wallet_argument_name_match(&matches);
             let account = value_t!(matches, "ACCOUNT_INDEX", u32).unwrap_or_else(|e| e.exit());
@@ -814,6 +940,16 @@ fn subcommand_wallet<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgM
                 index,
             )
         }
+        ("addresses-report", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+
+            wallet::commands::addresses_report(&mut term, root_dir, name)
+        }
+        ("addresses", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+
+            wallet::commands::addresses(&mut term, root_dir, name)
+        }
         ("attach", Some(matches)) => {
             let name = wallet_argument_name_match(&matches);
             let blockchain = blockchain_argument_name_match(&mut term, &matches);
@@ -860,6 +996,51 @@ fn subcommand_wallet<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgM
 
             wallet::commands::list(&mut term, root_dir, detailed)
         }
+        ("send", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let (address, amount) = transaction_argument_output_match(&matches).unwrap();
+            let dry_run = matches.is_present("DRY_RUN");
+
+            wallet::commands::send(&mut term, root_dir, name, address, amount, dry_run)
+        }
+        ("consolidate", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let outputs = value_t!(matches, "CONSOLIDATE_OUTPUTS", u32).unwrap_or_else(|e| e.exit());
+            let dry_run = matches.is_present("DRY_RUN");
+
+            wallet::commands::consolidate(&mut term, root_dir, name, outputs, dry_run)
+        }
+        ("owns", Some(matches)) => {
+            let address = value_t!(matches, "ADDRESS", cardano::address::ExtendedAddr).unwrap_or_else(|e| e.exit());
+
+            wallet::commands::owns(&mut term, root_dir, address)
+        }
+        ("sign-message", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let signer = matches.value_of("SIGNER").unwrap();
+            let signer = match signer.parse::<cardano::address::ExtendedAddr>() {
+                Ok(address) => wallet::commands::AddressOrPath::Address(address),
+                Err(_) => wallet::commands::AddressOrPath::Path(signer.to_owned()),
+            };
+            let message = matches.value_of("MESSAGE").unwrap();
+
+            wallet::commands::sign_message(&mut term, root_dir, name, signer, message)
+        }
+        ("redeem", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let redeem_key = transaction::redeem::read_key(matches.value_of("REDEEM_KEY").unwrap())
+                .unwrap_or_else(|e| term.fail_with(e));
+
+            wallet::commands::redeem(&mut term, root_dir, name, redeem_key)
+        }
+        ("migrate", Some(matches)) => {
+            let from = value_t!(matches, "MIGRATE_FROM", wallet::WalletName).unwrap_or_else(|e| e.exit());
+            let to = value_t!(matches, "MIGRATE_TO", wallet::WalletName).unwrap_or_else(|e| e.exit());
+            let outputs = value_t!(matches, "CONSOLIDATE_OUTPUTS", u32).unwrap_or_else(|e| e.exit());
+            let send = matches.is_present("MIGRATE_SEND");
+
+            wallet::commands::migrate(&mut term, root_dir, from, to, outputs, send)
+        }
         _ => {
             term.error(matches.usage()).unwrap();
             ::std::process::exit(1)
@@ -884,6 +1065,17 @@ fn wallet_commands_definition<'a, 'b>() -> App<'a, 'b> {
             .arg(wallet_argument_derivation_scheme())
             .arg(wallet_argument_wallet_scheme())
             .arg(wallet_argument_mnemonic_languages())
+            .arg(Arg::with_name("ENTROPY_SOURCE")
+                .help("where the entropy of the mnemonics comes from: the system's random number generator, dice rolls, hexadecimal digits or raw bytes on the standard input")
+                .long("entropy-source")
+                .takes_value(true)
+                .possible_values(&["system", "dice", "hex", "stdin"])
+                .default_value("system")
+            )
+            .arg(Arg::with_name("MIX_SYSTEM_ENTROPY")
+                .help("mix the given entropy with the system's random number generator")
+                .long("mix-system-entropy")
+            )
             .arg(wallet_argument_name_definition())
         )
         .subcommand(SubCommand::with_name("recover")
@@ -899,6 +1091,105 @@ fn wallet_commands_definition<'a, 'b>() -> App<'a, 'b> {
                 .long("interactive")
                 .short("i")
             )
+            .arg(Arg::with_name("RECOVER_PAPER")
+                .help("the mnemonic words come from a paper wallet scrambled with a passphrase (`--mnemonics-length' is the number of scrambled words)")
+                .long("paper")
+                .conflicts_with("DAEDALUS_SEED")
+            )
+            .arg(Arg::with_name("RECOVER_FROM_SHARES")
+                .help("recover the wallet from the shares created with `wallet split' (prompted one by one), then the recovery password of its mnemonics")
+                .long("from-shares")
+                .conflicts_with_all(&["RECOVER_PAPER", "RECOVER_INTERACTIVE"])
+            )
+            .arg(Arg::with_name("RECOVER_REPAIR")
+                .help("if the mnemonics are invalid, look for the valid ones with one wrong, swapped or missing word")
+                .long("repair")
+                .conflicts_with_all(&["RECOVER_FROM_SHARES", "RECOVER_PAPER", "RECOVER_INTERACTIVE"])
+            )
+            .arg(Arg::with_name("RECOVER_ADDRESS")
+                .help("with `--repair', only offer the candidates owning this address of the wallet")
+                .long("address")
+                .takes_value(true)
+                .value_name("ADDRESS")
+                .requires("RECOVER_REPAIR")
+            )
+            .arg(Arg::with_name("RECOVER_BLOCKCHAIN")
+                .help("with `--repair', only offer the candidates which received funds in the given blockchain (on their first address for BIP44 wallets)")
+                .long("blockchain")
+                .takes_value(true)
+                .value_name("BLOCKCHAIN_NAME")
+                .requires("RECOVER_REPAIR")
+            )
+        )
+        .subcommand(SubCommand::with_name("inspect-mnemonic")
+            .about("print the root public key, the fingerprint and the first addresses of the wallet of the given mnemonics, without creating the wallet")
+            .arg(wallet_argument_mnemonic_size())
+            .arg(wallet_argument_derivation_scheme())
+            .arg(wallet_argument_wallet_scheme())
+            .arg(wallet_argument_mnemonic_language())
+            .arg(wallet_argument_daedalus_seed())
+            .arg(Arg::with_name("RECOVER_INTERACTIVE")
+                .help("use interactive mode for entering the mnemonic words")
+                .long("interactive")
+                .short("i")
+            )
+            .arg(Arg::with_name("PROTOCOL_MAGIC").long("protocol-magic").takes_value(true).help("the protocol magic of the network of the printed addresses (default: mainnet)"))
+            .arg(Arg::with_name("INSPECT_ADDRESS_COUNT")
+                .help("the number of external addresses to print")
+                .long("addresses")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("5")
+            )
+        )
+        .subcommand(SubCommand::with_name("import-daedalus-keyfile")
+            .about("import a Daedalus wallet from Daedalus' `secret.key' file (as a random index wallet)")
+            .arg(wallet_argument_name_definition())
+            .arg(Arg::with_name("DAEDALUS_KEYFILE")
+                .help("the path to Daedalus' `secret.key' file")
+                .required(true)
+            )
+            .arg(Arg::with_name("DAEDALUS_KEY_INDEX")
+                .help("the index of the wallet key to import, if the file holds several of them")
+                .long("key-index")
+                .takes_value(true)
+                .value_name("INDEX")
+            )
+        )
+        .subcommand(SubCommand::with_name("split")
+            .about("split the wallet's mnemonics in mnemonic shares, a given number of them being needed to recover the wallet")
+            .arg(wallet_argument_name_definition())
+            .arg(wallet_argument_mnemonic_size())
+            .arg(wallet_argument_daedalus_seed())
+            .arg(Arg::with_name("SPLIT_THRESHOLD")
+                .help("the number of shares needed to recover the wallet")
+                .long("threshold")
+                .takes_value(true)
+                .value_name("M")
+                .required(true)
+            )
+            .arg(Arg::with_name("SPLIT_SHARES")
+                .help("the number of shares to create")
+                .long("shares")
+                .takes_value(true)
+                .value_name("N")
+                .required(true)
+            )
+            .arg(wallet_argument_mnemonic_language())
+        )
+        .subcommand(SubCommand::with_name("paper")
+            .about("generate a new wallet and print it on a paper sheet, the wallet is not kept locally")
+            .arg(wallet_argument_name_definition())
+            .arg(wallet_argument_mnemonic_size())
+            .arg(wallet_argument_derivation_scheme())
+            .arg(wallet_argument_mnemonic_language())
+            .arg(Arg::with_name("PROTOCOL_MAGIC").long("protocol-magic").takes_value(true).help("the protocol magic of the network of the printed address (default: mainnet)"))
+            .arg(Arg::with_name("PAPER_OUTPUT")
+                .help("write the paper wallet sheet in the given file instead of the standard output")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+            )
         )
         .subcommand(SubCommand::with_name("destroy")
             .about("delete all data associated to the given wallet.")
@@ -908,9 +1199,27 @@ fn wallet_commands_definition<'a, 'b>() -> App<'a, 'b> {
             .about("create a new address")
             .arg(wallet_argument_name_definition())
             .arg(Arg::with_name("PROTOCOL_MAGIC").long("protocol-magic").help("Optional parameter, only required if the wallet is not attached."))
-            .arg(Arg::with_name("ACCOUNT_INDEX").required(true))
-            .arg(Arg::with_name("ADDRESS_INDEX").required(true))
+            .arg(Arg::with_name("ACCOUNT_INDEX").required_unless("NEXT_ADDRESS"))
+            .arg(Arg::with_name("ADDRESS_INDEX").required_unless("NEXT_ADDRESS"))
             .arg(Arg::with_name("INTERNAL_ADDRESS").long("internal"))
+            .arg(Arg::with_name("NEXT_ADDRESS")
+                .long("next")
+                .conflicts_with_all(&["ACCOUNT_INDEX", "ADDRESS_INDEX", "INTERNAL_ADDRESS"])
+                .help("issue the next unused external address, and record it in the wallet's address pool")
+            )
+            .arg(Arg::with_name("FORCE")
+                .long("force")
+                .requires("NEXT_ADDRESS")
+                .help("issue the address even if too many issued addresses are still unused (gap limit)")
+            )
+        )
+        .subcommand(SubCommand::with_name("addresses-report")
+            .about("report the usage of every address of the wallet, flagging address reuse and accounts spent together")
+            .arg(wallet_argument_name_definition())
+        )
+        .subcommand(SubCommand::with_name("addresses")
+            .about("list the addresses issued with `address --next', with the funds they received")
+            .arg(wallet_argument_name_definition())
         )
         .subcommand(SubCommand::with_name("attach")
             .about("Attach the existing wallet to the existing local blockchain. Detach first to attach to an other blockchain.")
@@ -951,6 +1260,69 @@ fn wallet_commands_definition<'a, 'b>() -> App<'a, 'b> {
             .about("print the wallet's available funds")
             .arg(wallet_argument_name_definition())
         )
+        .subcommand(SubCommand::with_name("send")
+            .about("send funds from the wallet: build, select inputs, sign and send a transaction in one go")
+            .arg(wallet_argument_name_definition())
+            .arg(transaction_argument_address_definition().required(true))
+            .arg(transaction_argument_amount_definition().required(true))
+            .arg(Arg::with_name("DRY_RUN")
+                .help("sign the transaction and print it (encoded in base64) without sending it")
+                .long("dry-run")
+            )
+        )
+        .subcommand(SubCommand::with_name("consolidate")
+            .about("merge the wallet's UTxOs into a few outputs at fresh internal addresses, using as many transactions as needed")
+            .arg(wallet_argument_name_definition())
+            .arg(Arg::with_name("CONSOLIDATE_OUTPUTS")
+                .help("number of outputs (and fresh internal addresses) of each transaction")
+                .long("outputs")
+                .takes_value(true)
+                .value_name("NUMBER")
+                .default_value("1")
+            )
+            .arg(Arg::with_name("DRY_RUN")
+                .help("only prepare the staging transactions, do not sign nor send them")
+                .long("dry-run")
+            )
+        )
+        .subcommand(SubCommand::with_name("owns")
+            .about("find the wallet, and the derivation path, owning the given address")
+            .arg(Arg::with_name("ADDRESS").help("the address to look for").required(true))
+        )
+        .subcommand(SubCommand::with_name("sign-message")
+            .about("sign a message with the private key of an address of the wallet (proof of ownership)")
+            .arg(wallet_argument_name_definition())
+            .arg(Arg::with_name("SIGNER")
+                .help("the address, or its derivation path (`account/change/index' for BIP44 wallets, `account/index' for random index wallets, `'' marks the hardened indices)")
+                .value_name("ADDRESS|PATH")
+                .required(true)
+            )
+            .arg(Arg::with_name("MESSAGE").help("the message to sign").required(true))
+        )
+        .subcommand(SubCommand::with_name("redeem")
+            .about("redeem the funds of an AVVM redeem key (vending certificate) to a fresh address of the wallet")
+            .arg(wallet_argument_name_definition())
+            .arg(Arg::with_name("REDEEM_KEY")
+                .help("the redeem key (base64 or hexadecimal encoded), or a file containing it")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("migrate")
+            .about("move all the funds of a wallet to fresh addresses of another wallet, using as many transactions as needed")
+            .arg(Arg::with_name("MIGRATE_FROM").help("the wallet to move the funds from").required(true))
+            .arg(Arg::with_name("MIGRATE_TO").help("the wallet to move the funds to").required(true))
+            .arg(Arg::with_name("CONSOLIDATE_OUTPUTS")
+                .help("number of outputs (and fresh addresses of the destination wallet) of each transaction")
+                .long("outputs")
+                .takes_value(true)
+                .value_name("NUMBER")
+                .default_value("1")
+            )
+            .arg(Arg::with_name("MIGRATE_SEND")
+                .help("send the signed transactions (in order), otherwise they are left as staging transactions")
+                .long("send")
+            )
+        )
 }
 
 /* ------------------------------------------------------------------------- *
@@ -970,9 +1342,12 @@ pub enum TransactionCmd {
     Finalize,
     Send,
     InputSelect,
+    PreviewInputs,
     AddChange,
     AddInput,
     AddOutput,
+    AddOutputs,
+    Sweep,
     RmInput,
     RmOutput,
     RmChange,
@@ -990,9 +1365,12 @@ impl TransactionCmd {
             TransactionCmd::Sign => "sign",
             TransactionCmd::Finalize => "finalize",
             TransactionCmd::InputSelect => "input-select",
+            TransactionCmd::PreviewInputs => "preview-inputs",
             TransactionCmd::AddChange => "add-change",
             TransactionCmd::AddInput => "add-input",
             TransactionCmd::AddOutput => "add-output",
+            TransactionCmd::AddOutputs => "add-outputs",
+            TransactionCmd::Sweep => "sweep",
             TransactionCmd::RmInput => "rm-input",
             TransactionCmd::RmOutput => "rm-output",
             TransactionCmd::RmChange => "rm-change",
@@ -1068,8 +1446,8 @@ fn transaction_argument_output_match<'a>(
 }
 fn transaction_argument_selection_algorithm_match<'a>(
     matches: &ArgMatches<'a>,
-) -> ::cardano::wallet::scheme::SelectionPolicy {
-    use cardano::wallet::scheme::SelectionPolicy;
+) -> transaction::input_selection::SelectionPolicy {
+    use transaction::input_selection::SelectionPolicy;
     if matches.is_present("SELECT_HEAD_FIRST") {
         SelectionPolicy::FirstMatchFirst
     } else if matches.is_present("SELECT_LARGEST_FIRST") {
@@ -1077,11 +1455,123 @@ fn transaction_argument_selection_algorithm_match<'a>(
     } else if matches.is_present("SELECT_BLACKJACK") {
         let value = value_t!(matches, "SELECT_BLACKJACK", u32).unwrap_or_else(|e| e.exit());
         SelectionPolicy::Blackjack(value.into())
+    } else if matches.is_present("SELECT_RANDOM_IMPROVE") {
+        SelectionPolicy::RandomImprove
     } else {
         SelectionPolicy::LargestFirst
     }
 }
 
+fn transaction_input_filter_arguments<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    fn validate_txo_pointer(s: String) -> Result<(), String> {
+        transaction::coin_control::parse_txo_pointer(&s).map(|_| ())
+    }
+    vec![
+        Arg::with_name("INCLUDE_UTXO")
+            .long("include-utxo")
+            .takes_value(true)
+            .value_name("TXID.INDEX")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(validate_txo_pointer)
+            .help("only use the given UTxO (can be repeated)"),
+        Arg::with_name("EXCLUDE_UTXO")
+            .long("exclude-utxo")
+            .takes_value(true)
+            .value_name("TXID.INDEX")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(validate_txo_pointer)
+            .help("never use the given UTxO (can be repeated)"),
+        Arg::with_name("FROM_ADDRESS")
+            .long("from-address")
+            .takes_value(true)
+            .value_name("ADDRESS")
+            .multiple(true)
+            .number_of_values(1)
+            .help("only use the UTxOs credited to the given address (can be repeated)"),
+        Arg::with_name("FROM_DERIVATION_PATH")
+            .long("derivation-path")
+            .takes_value(true)
+            .value_name("PATH")
+            .multiple(true)
+            .number_of_values(1)
+            .help("only use the UTxOs credited to the given derivation path, as displayed by `wallet utxos' (can be repeated)"),
+        Arg::with_name("MIN_VALUE")
+            .long("min-value")
+            .takes_value(true)
+            .value_name("AMOUNT")
+            .help("ignore the UTxOs of value lower than the given amount (in Lovelace)"),
+        Arg::with_name("MAX_VALUE")
+            .long("max-value")
+            .takes_value(true)
+            .value_name("AMOUNT")
+            .help("ignore the UTxOs of value greater than the given amount (in Lovelace)"),
+        Arg::with_name("MIN_CONFIRMATIONS")
+            .long("min-confirmations")
+            .takes_value(true)
+            .value_name("BLOCKS")
+            .help("ignore the UTxOs received less than the given number of blocks before the tip of the local blockchain"),
+    ]
+}
+fn transaction_input_filter_match<'a>(
+    matches: &ArgMatches<'a>,
+) -> transaction::coin_control::InputFilter {
+    use transaction::coin_control::{parse_txo_pointer, InputFilter};
+    let txo_pointers = |name| -> Vec<cardano::tx::TxoPointer> {
+        matches
+            .values_of(name)
+            .map(|values| values.map(|v| parse_txo_pointer(v).unwrap()).collect())
+            .unwrap_or_default()
+    };
+
+    InputFilter {
+        include: txo_pointers("INCLUDE_UTXO"),
+        exclude: txo_pointers("EXCLUDE_UTXO"),
+        addresses: if matches.is_present("FROM_ADDRESS") {
+            values_t!(matches, "FROM_ADDRESS", cardano::address::ExtendedAddr)
+                .unwrap_or_else(|e| e.exit())
+        } else {
+            Vec::new()
+        },
+        derivation_paths: matches
+            .values_of("FROM_DERIVATION_PATH")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default(),
+        min_value: if matches.is_present("MIN_VALUE") {
+            Some(value_t!(matches, "MIN_VALUE", cardano::coin::Coin).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        },
+        max_value: if matches.is_present("MAX_VALUE") {
+            Some(value_t!(matches, "MAX_VALUE", cardano::coin::Coin).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        },
+        min_confirmations: if matches.is_present("MIN_CONFIRMATIONS") {
+            Some(value_t!(matches, "MIN_CONFIRMATIONS", u64).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        },
+    }
+}
+
+fn transaction_argument_change_strategy_match<'a>(
+    matches: &ArgMatches<'a>,
+) -> Option<transaction::core::ChangeStrategy> {
+    use transaction::core::ChangeStrategy;
+    if matches.is_present("SPLIT_EVEN") {
+        Some(ChangeStrategy::Even)
+    } else if matches.is_present("SPLIT_MATCH_PAYMENTS") {
+        Some(ChangeStrategy::MatchPayments)
+    } else if matches.is_present("SPLIT_CAP") {
+        let value = value_t!(matches, "SPLIT_CAP", cardano::coin::Coin).unwrap_or_else(|e| e.exit());
+        Some(ChangeStrategy::Cap(value))
+    } else {
+        None
+    }
+}
+
 fn subcommand_transaction<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgMatches<'a>) {
     let res = match matches.subcommand() {
         ("new", Some(matches)) => {
@@ -1115,8 +1605,11 @@ fn subcommand_transaction<'a>(mut term: term::Term, root_dir: PathBuf, matches:
         }
         ("sign", Some(matches)) => {
             let id = transaction_argument_name_match(&matches);
+            let redeem_key = matches.value_of("REDEEM_KEY").map(|key| {
+                transaction::redeem::read_key(key).unwrap_or_else(|e| term.fail_with(e))
+            });
 
-            transaction::commands::sign(&mut term, root_dir, id)
+            transaction::commands::sign(&mut term, root_dir, id, redeem_key)
         }
         ("add-input", Some(matches)) => {
             let id = transaction_argument_name_match(&matches);
@@ -1130,18 +1623,45 @@ fn subcommand_transaction<'a>(mut term: term::Term, root_dir: PathBuf, matches:
 
             transaction::commands::add_output(&mut term, root_dir, id, output)
         }
+        ("sweep", Some(matches)) => {
+            let blockchain = blockchain_argument_name_match(&mut term, &matches);
+            let file = matches.value_of("XPRV_FILE").unwrap();
+            let to = matches.value_of("SWEEP_TO").unwrap();
+            let destination = match to.parse::<cardano::address::ExtendedAddr>() {
+                Ok(address) => transaction::commands::SweepDestination::Address(address),
+                Err(_) => {
+                    let name = value_t!(matches, "SWEEP_TO", wallet::WalletName).unwrap_or_else(|e| e.exit());
+                    transaction::commands::SweepDestination::Wallet(name)
+                }
+            };
+
+            transaction::commands::sweep(&mut term, root_dir, blockchain, file, destination)
+        }
+        ("add-outputs", Some(matches)) => {
+            let id = transaction_argument_name_match(&matches);
+            let file = matches.value_of("OUTPUTS_FILE").unwrap();
+
+            transaction::commands::add_outputs(&mut term, root_dir, id, file)
+        }
         ("add-change", Some(matches)) => {
             let id = transaction_argument_name_match(&matches);
             let address = value_t!(matches, "CHANGE_ADDRESS", cardano::address::ExtendedAddr)
                 .unwrap_or_else(|e| e.exit());
+            let strategy = transaction_argument_change_strategy_match(&matches);
 
-            transaction::commands::add_change(&mut term, root_dir, id, address)
+            transaction::commands::add_change(&mut term, root_dir, id, address, strategy)
         }
         ("input-select", Some(matches)) => {
             let id = transaction_argument_name_match(&matches);
             let wallets =
                 values_t!(matches, "WALLET_NAME", wallet::WalletName).unwrap_or_else(|e| e.exit());
             let selection_algorithm = transaction_argument_selection_algorithm_match(&matches);
+            let change_from = if matches.is_present("CHANGE_FROM") {
+                Some(value_t!(matches, "CHANGE_FROM", wallet::WalletName).unwrap_or_else(|e| e.exit()))
+            } else {
+                None
+            };
+            let filter = transaction_input_filter_match(&matches);
 
             transaction::commands::input_select(
                 &mut term,
@@ -1149,8 +1669,17 @@ fn subcommand_transaction<'a>(mut term: term::Term, root_dir: PathBuf, matches:
                 id,
                 wallets,
                 selection_algorithm,
+                change_from,
+                filter,
             )
         }
+        ("preview-inputs", Some(matches)) => {
+            let wallets =
+                values_t!(matches, "WALLET_NAME", wallet::WalletName).unwrap_or_else(|e| e.exit());
+            let filter = transaction_input_filter_match(&matches);
+
+            transaction::commands::preview_inputs(&mut term, root_dir, wallets, filter)
+        }
         ("rm-output", Some(matches)) => {
             let id = transaction_argument_name_match(&matches);
             let address = value_t!(
@@ -1220,6 +1749,12 @@ fn transaction_commands_definition<'a, 'b>() -> App<'a, 'b> {
         .subcommand(SubCommand::with_name(TransactionCmd::Sign.as_string())
             .about("Finalize a staging a transaction into a transaction ready to send to the blockchain network")
             .arg(transaction_argument_name_definition())
+            .arg(Arg::with_name("REDEEM_KEY")
+                .help("redeem key (base64 or hexadecimal encoded, or a file containing it) to sign the inputs at redeem addresses (AVVM)")
+                .long("redeem-key")
+                .takes_value(true)
+                .value_name("FILE|KEY")
+            )
         )
         .subcommand(SubCommand::with_name(TransactionCmd::Send.as_string())
             .about("Send the transaction transaction to the blockchain")
@@ -1252,11 +1787,50 @@ fn transaction_commands_definition<'a, 'b>() -> App<'a, 'b> {
                 .value_name("MAX_EXTRA_FEES")
                 .help("select the exact necessary amount to perform the transaction. The optional parameter takes the accepted loss (in Lovelace, 1µ Ada).")
             )
+            .arg(Arg::with_name("SELECT_RANDOM_IMPROVE")
+                .long("--select-random-improve")
+                .group("SELECTION_ALGORITHM")
+                .required(false)
+                .help("Select random inputs, then improve the selection so the change is close to the payments (keeps the UTxO set healthy)")
+            )
+            .arg(Arg::with_name("CHANGE_FROM")
+                .long("change-from")
+                .takes_value(true)
+                .value_name("WALLET")
+                .required(false)
+                .help("add a fresh change address from the given wallet (the next unused internal address, or a new random index address) once the selection succeeds, the staging transaction must not have a change address yet")
+            )
+            .args(&transaction_input_filter_arguments())
+        )
+        .subcommand(SubCommand::with_name(TransactionCmd::PreviewInputs.as_string())
+            .about("List the UTxOs of the given wallets the input selection would use with the given coin control filters")
+            .arg(Arg::with_name("WALLET_NAME").required(true).multiple(true).help("wallet name to list the UTxOs of"))
+            .args(&transaction_input_filter_arguments())
         )
         .subcommand(SubCommand::with_name(TransactionCmd::AddChange.as_string())
-            .about("Add a change address to a transaction")
+            .about("Add a change address to a transaction, the change is split between all the change addresses")
             .arg(transaction_argument_name_definition())
             .arg(Arg::with_name("CHANGE_ADDRESS").required(true).help("address to send the change to"))
+            .arg(Arg::with_name("SPLIT_EVEN")
+                .long("split-even")
+                .group("CHANGE_STRATEGY")
+                .required(false)
+                .help("split the change evenly between the change addresses (default)")
+            )
+            .arg(Arg::with_name("SPLIT_MATCH_PAYMENTS")
+                .long("split-match-payments")
+                .group("CHANGE_STRATEGY")
+                .required(false)
+                .help("split the change in outputs of the same amounts as the payments, for privacy")
+            )
+            .arg(Arg::with_name("SPLIT_CAP")
+                .long("split-cap")
+                .group("CHANGE_STRATEGY")
+                .required(false)
+                .takes_value(true)
+                .value_name("MAX_AMOUNT")
+                .help("split the change in outputs of at most the given amount (in Lovelace)")
+            )
         )
         .subcommand(SubCommand::with_name(TransactionCmd::RmChange.as_string())
             .about("Remove a change address from a transaction")
@@ -1276,6 +1850,37 @@ fn transaction_commands_definition<'a, 'b>() -> App<'a, 'b> {
             .arg(transaction_argument_address_definition().requires("TRANSACTION_AMOUNT"))
             .arg(transaction_argument_amount_definition())
         )
+        .subcommand(SubCommand::with_name(TransactionCmd::AddOutputs.as_string())
+            .about("Add all the outputs listed in a CSV or JSON file to a transaction")
+            .arg(transaction_argument_name_definition())
+            .arg(Arg::with_name("OUTPUTS_FILE")
+                .help("file listing the outputs: `address,amount' per line, or a JSON array of `{\"address\": .., \"amount\": ..}' (if the file extension is `.json'). Amounts are in Lovelace.")
+                .long("from-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name(TransactionCmd::Sweep.as_string())
+            .about("Create a staging transaction moving all the funds of a standalone key (e.g. from `debug generate-xprv' or a paper wallet) to a wallet or an address")
+            .arg(Arg::with_name("XPRV_FILE")
+                .help("file containing the hexadecimal encoded XPrv, funds are looked for at its bootstrap address")
+                .long("xprv-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .required(true)
+            )
+            .arg(Arg::with_name("SWEEP_TO")
+                .help("the wallet (a fresh change address will be used) or the address to send the funds to")
+                .long("to")
+                .takes_value(true)
+                .value_name("WALLET|ADDRESS")
+                .required(true)
+            )
+            .arg(blockchain_argument_name_definition()
+                .help("The blockchain to look for the funds in (make sure it is synced)")
+            )
+        )
         .subcommand(SubCommand::with_name(TransactionCmd::RmInput.as_string())
             .about("Remove an input to a transaction")
             .arg(transaction_argument_name_definition())
@@ -1293,6 +1898,93 @@ fn transaction_commands_definition<'a, 'b>() -> App<'a, 'b> {
         )
 }
 
+/* ------------------------------------------------------------------------- *
+ *                Agent Sub Commands and helpers                             *
+ * ------------------------------------------------------------------------- */
+
+const AGENT_COMMAND: &'static str = "agent";
+
+fn agent_argument_timeout_definition<'a, 'b>() -> Arg<'a, 'b> {
+    fn validate_duration(s: String) -> Result<(), String> {
+        humantime::parse_duration(&s).map(|_| ()).map_err(|e| e.to_string())
+    }
+    Arg::with_name("AGENT_TIMEOUT")
+        .long("timeout")
+        .takes_value(true)
+        .value_name("DURATION")
+        .validator(validate_duration)
+}
+fn agent_argument_timeout_match<'a>(matches: &ArgMatches<'a>) -> Option<std::time::Duration> {
+    matches.value_of("AGENT_TIMEOUT").map(|timeout| {
+        // already checked by the validator
+        humantime::parse_duration(timeout).unwrap()
+    })
+}
+
+fn subcommand_agent<'a>(mut term: term::Term, root_dir: PathBuf, matches: &ArgMatches<'a>) {
+    match matches.subcommand() {
+        ("start", Some(matches)) => {
+            let timeout = agent_argument_timeout_match(&matches).unwrap();
+
+            agent::commands::start(&mut term, root_dir, timeout)
+                .unwrap_or_else(|e| term.fail_with(e));
+        }
+        ("add", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+            let timeout = agent_argument_timeout_match(&matches);
+
+            agent::commands::add(&mut term, root_dir, name, timeout)
+                .unwrap_or_else(|e| term.fail_with(e));
+        }
+        ("remove", Some(matches)) => {
+            let name = wallet_argument_name_match(&matches);
+
+            agent::commands::remove(&mut term, root_dir, name)
+                .unwrap_or_else(|e| term.fail_with(e));
+        }
+        ("list", Some(_)) => {
+            agent::commands::list(&mut term, root_dir)
+                .unwrap_or_else(|e| term.fail_with(e));
+        }
+        ("stop", Some(_)) => {
+            agent::commands::stop(&mut term, root_dir)
+                .unwrap_or_else(|e| term.fail_with(e));
+        }
+        _ => {
+            term.error(matches.usage()).unwrap();
+            ::std::process::exit(1)
+        }
+    }
+}
+fn agent_commands_definition<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(AGENT_COMMAND)
+        .about("Key agent, keeping the unlocked wallet keys between commands (Unix only).")
+        .subcommand(SubCommand::with_name("start")
+            .about("run the key agent (in the foreground) until `agent stop'. While a wallet is unlocked in the agent, its password is not asked and its private keys stay in the agent.")
+            .arg(agent_argument_timeout_definition()
+                .help("how long the unlocked keys are kept (e.g. `15min', `2h')")
+                .default_value("15min")
+            )
+        )
+        .subcommand(SubCommand::with_name("add")
+            .about("unlock the wallet in the key agent (the wallet password is asked)")
+            .arg(wallet_argument_name_definition())
+            .arg(agent_argument_timeout_definition()
+                .help("how long the unlocked key is kept, instead of the agent's timeout")
+            )
+        )
+        .subcommand(SubCommand::with_name("remove")
+            .about("wipe the key of the wallet from the key agent")
+            .arg(wallet_argument_name_definition())
+        )
+        .subcommand(SubCommand::with_name("list")
+            .about("list the wallets unlocked in the key agent")
+        )
+        .subcommand(SubCommand::with_name("stop")
+            .about("stop the key agent, wiping all the keys")
+        )
+}
+
 /* ------------------------------------------------------------------------- *
  *                Debug Sub Commands and helpers                            *
  * ------------------------------------------------------------------------- */
@@ -1319,6 +2011,14 @@ fn subcommand_debug<'a>(mut term: term::Term, _rootdir: PathBuf, matches: &ArgMa
             let xprv_out = matches.value_of("OUTPUT_FILE").expect("OUTPUT_FILE");
             debug::generate_xprv(xprv_out);
         }
+        ("verify-message", Some(matches)) => {
+            let address = value_t!(matches, "ADDRESS", String).unwrap_or_else(|e| e.exit());
+            let xpub = value_t!(matches, "XPUB", String).unwrap_or_else(|e| e.exit());
+            let signature = value_t!(matches, "SIGNATURE", String).unwrap_or_else(|e| e.exit());
+            let message = value_t!(matches, "MESSAGE", String).unwrap_or_else(|e| e.exit());
+
+            debug::verify_message(term, address, xpub, signature, message);
+        }
         ("xprv-to-xpub", Some(matches)) => {
             let xprv_in = matches.value_of("INPUT_FILE").expect("INPUT_FILE");
             let xpub_out = matches.value_of("OUTPUT_FILE").expect("OUTPUT_FILE");
@@ -1387,4 +2087,11 @@ fn debug_commands_definition<'a, 'b>() -> App<'a, 'b> {
                 .required(true)
             )
         )
+        .subcommand(SubCommand::with_name("verify-message")
+            .about("check a message signature made with `wallet sign-message'")
+            .arg(Arg::with_name("ADDRESS").help("the address the message was signed for").required(true))
+            .arg(Arg::with_name("XPUB").help("the public key of the address, in hexadecimal").required(true))
+            .arg(Arg::with_name("SIGNATURE").help("the signature, in hexadecimal").required(true))
+            .arg(Arg::with_name("MESSAGE").help("the signed message").required(true))
+        )
 }
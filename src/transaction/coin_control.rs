@@ -0,0 +1,169 @@
+//! coin control: restrict the UTxOs the input selection may use
+//!
+
+use cardano::{
+    address::ExtendedAddr,
+    block::BlockDate,
+    coin::Coin,
+    tx::{TxId, TxoPointer},
+};
+use std::fmt;
+
+use super::super::wallet::state::utxo::UTxO;
+
+/// filters to apply on the wallets' UTxOs before running the input selection
+///
+/// An empty filter accepts every UTxO.
+#[derive(Debug, Clone, Default)]
+pub struct InputFilter {
+    /// if not empty, only these UTxOs may be used
+    pub include: Vec<TxoPointer>,
+
+    /// these UTxOs will never be used
+    pub exclude: Vec<TxoPointer>,
+
+    /// if not empty, only the UTxOs credited to these addresses may be used
+    pub addresses: Vec<ExtendedAddr>,
+
+    /// if not empty, only the UTxOs credited to these derivation paths
+    /// (as displayed by `wallet utxos`) may be used
+    pub derivation_paths: Vec<String>,
+
+    /// ignore the UTxOs of lower value
+    pub min_value: Option<Coin>,
+
+    /// ignore the UTxOs of greater value
+    pub max_value: Option<Coin>,
+
+    /// ignore the UTxOs received less than the given number of blocks
+    /// before the local blockchain's tip
+    pub min_confirmations: Option<u64>,
+}
+impl InputFilter {
+    /// tell if the filter needs the depth of the UTxOs in the blockchain
+    pub fn needs_depth(&self) -> bool {
+        self.min_confirmations.is_some()
+    }
+
+    /// check the given UTxO passes the filter.
+    ///
+    /// `depth` is the number of blocks following the block where the UTxO
+    /// was received, up to the local blockchain's tip (if known).
+    pub fn accept<A: fmt::Display>(&self, utxo: &UTxO<A>, depth: Option<u64>) -> bool {
+        let txin = utxo.extract_txin();
+
+        if !self.include.is_empty() && !self.include.contains(&txin) {
+            return false;
+        }
+        if self.exclude.contains(&txin) {
+            return false;
+        }
+        if !self.addresses.is_empty() && !self.addresses.contains(&utxo.credited_address) {
+            return false;
+        }
+        if !self.derivation_paths.is_empty() {
+            let path = format!("{}", utxo.credited_addressing);
+            if !self.derivation_paths.contains(&path) {
+                return false;
+            }
+        }
+        if let Some(min_value) = self.min_value {
+            if utxo.credited_value < min_value {
+                return false;
+            }
+        }
+        if let Some(max_value) = self.max_value {
+            if utxo.credited_value > max_value {
+                return false;
+            }
+        }
+        if let Some(min_confirmations) = self.min_confirmations {
+            match depth {
+                Some(depth) if depth >= min_confirmations => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// number of blocks following the block of the given date
+///
+/// `latest_blocks` are the dates of the latest blocks of the blockchain, the
+/// tip first: the depth cannot be greater than their number.
+pub fn depth(latest_blocks: &[BlockDate], date: &BlockDate) -> u64 {
+    latest_blocks
+        .iter()
+        .take_while(|block| *block > date)
+        .count() as u64
+}
+
+/// parse a `TxoPointer` of the form `<TxId>.<index>`
+pub fn parse_txo_pointer(s: &str) -> Result<TxoPointer, String> {
+    let mut parts = s.splitn(2, '.');
+    let txid = parts.next().unwrap_or("");
+    let index = parts
+        .next()
+        .ok_or_else(|| format!("expected `<TxId>.<index>', got `{}'", s))?;
+
+    Ok(TxoPointer {
+        id: txid
+            .parse::<TxId>()
+            .map_err(|err| format!("invalid TxId `{}': {}", txid, err))?,
+        index: index
+            .parse::<u32>()
+            .map_err(|err| format!("invalid index `{}': {}", index, err))?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cardano::{block::EpochSlotId, config::NetworkMagic, hdwallet::XPub};
+
+    fn date(epoch: u64, slotid: u16) -> BlockDate {
+        BlockDate::Normal(EpochSlotId {
+            epoch: epoch,
+            slotid: slotid,
+        })
+    }
+
+    fn utxo(value: u64) -> UTxO<String> {
+        let xpub = XPub::from_slice(&[1; 64]).unwrap();
+        UTxO {
+            transaction_id: TxId::new(&[0]),
+            index_in_transaction: 0,
+            credited_address: ExtendedAddr::new_simple(xpub, NetworkMagic::NoMagic),
+            credited_addressing: String::from("m/0"),
+            credited_value: Coin::new(value).unwrap(),
+        }
+    }
+
+    #[test]
+    fn depth_counts_blocks() {
+        // empty slots and epoch changes do not count
+        let latest_blocks = vec![date(2, 3), date(2, 0), date(1, 21599), date(1, 10)];
+        assert_eq!(depth(&latest_blocks, &date(2, 3)), 0);
+        assert_eq!(depth(&latest_blocks, &date(2, 0)), 1);
+        assert_eq!(depth(&latest_blocks, &BlockDate::Boundary(2)), 2);
+        assert_eq!(depth(&latest_blocks, &date(1, 15)), 3);
+        assert_eq!(depth(&latest_blocks, &date(1, 10)), 3);
+        // older than the given blocks
+        assert_eq!(depth(&latest_blocks, &date(0, 5)), 4);
+        assert_eq!(depth(&[], &date(0, 5)), 0);
+    }
+
+    #[test]
+    fn min_confirmations() {
+        let filter = InputFilter {
+            min_confirmations: Some(3),
+            ..InputFilter::default()
+        };
+        assert!(filter.needs_depth());
+        assert!(!filter.accept(&utxo(1), None));
+        assert!(!filter.accept(&utxo(1), Some(2)));
+        assert!(filter.accept(&utxo(1), Some(3)));
+        assert!(InputFilter::default().accept(&utxo(1), None));
+    }
+}
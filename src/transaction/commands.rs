@@ -1,21 +1,43 @@
-use super::super::blockchain::{Blockchain, BlockchainName};
+use super::super::blockchain::{
+    self,
+    peer::{Outcomes, Peer},
+    Blockchain, BlockchainName,
+};
 use super::super::wallet::{
-    self, state::lookup, utils::create_wallet_state_from_logs, Wallet, WalletName, Wallets,
+    self,
+    state::{
+        log,
+        lookup::{self, singleaddress::SingleAddressLookup},
+        ptr::StatePtr,
+        state::State,
+        utxo::UTxO,
+    },
+    utils::create_wallet_state_from_logs,
+    Wallet, WalletName, Wallets,
 };
+use super::coin_control::{self, InputFilter};
 use super::core::{self, StagingId, StagingTransaction};
 use super::error::Error;
+use super::input_selection::{RandomImprove, SelectionPolicy};
+use super::payouts;
 use cardano::{
     address::ExtendedAddr,
     coin::{sum_coins, Coin},
+    config::NetworkMagic,
     fee::{FeeAlgorithm, LinearFee},
-    tx::{self, Tx, TxId, TxInWitness, TxoPointer},
-    wallet::scheme::SelectionPolicy,
+    hdwallet::{self, XPrv},
+    redeem,
+    tx::{self, Tx, TxId, TxInWitness, TxOut, TxoPointer},
+    util::hex,
+};
+use utils::{
+    secret::{xprv_from_bytes_verified, Secret},
+    term::{style::Style, Term},
 };
-use utils::term::{style::Style, Term};
 
 use std::{
     collections::BTreeMap,
-    io::Write,
+    io::{Read, Write},
     iter,
     path::{Path, PathBuf},
 };
@@ -84,15 +106,31 @@ pub fn send(
     writeln!(term, "sending transaction {}", style!(txaux.tx.id()))?;
 
     let mut sent = false;
+    let mut outcomes = Outcomes::new();
     for np in blockchain.peers() {
         if !np.is_native() {
             continue;
         }
 
-        let peer = super::super::blockchain::peer::Peer::prepare(&blockchain, np.name().to_owned());
-
-        sent = sent || peer.connect(term).unwrap().send_txaux(txaux.clone());
+        let name = np.name().to_owned();
+        let outcome = Peer::prepare(&blockchain, name.clone())
+            .and_then(|peer| peer.connect())
+            .and_then(|connected_peer| connected_peer.send_txaux(txaux.clone()))
+            .and_then(|accepted| {
+                if accepted {
+                    Ok("transaction sent".to_owned())
+                } else {
+                    Err(blockchain::Error::PeerTransactionRejected(name.clone()))
+                }
+            });
+        sent = outcome.is_ok();
+        outcomes.push(&name, outcome);
+        if sent {
+            break;
+        }
     }
+    term.info("remote nodes:\n")?;
+    outcomes.print(term)?;
 
     if sent {
         Ok(())
@@ -101,7 +139,15 @@ pub fn send(
     }
 }
 
-pub fn sign(term: &mut Term, root_dir: PathBuf, id_str: &str) -> Result<(), Error> {
+/// sign the inputs of the staging transaction with the keys of the local
+/// wallets, the input of the AVVM distribution of the given redeem key (if
+/// any) is signed with it.
+pub fn sign(
+    term: &mut Term,
+    root_dir: PathBuf,
+    id_str: &str,
+    redeem_key: Option<redeem::PrivateKey>,
+) -> Result<(), Error> {
     let mut signatures = Vec::new();
 
     let mut wallets = BTreeMap::new();
@@ -132,6 +178,12 @@ pub fn sign(term: &mut Term, root_dir: PathBuf, id_str: &str) -> Result<(), Erro
     let txid = tx.id();
     let protocol_magic = staging.protocol_magic;
 
+    // the UTxO of the redeem key in the genesis AVVM distribution
+    let redeem_txin = redeem_key.as_ref().map(|redeem_key| TxoPointer {
+        id: tx::redeem_pubkey_to_txid(&redeem_key.public(), protocol_magic).0,
+        index: 0,
+    });
+
     // TODO: ignore already signed inputs
     for input in staging.transaction().inputs() {
         let txin = input.extract_txin();
@@ -152,14 +204,31 @@ pub fn sign(term: &mut Term, root_dir: PathBuf, id_str: &str) -> Result<(), Erro
                     protocol_magic,
                     &txid,
                     &utxo.credited_addressing,
+                )?);
+            }
+        }
+
+        if signature.is_none() && redeem_txin.as_ref() == Some(&txin) {
+            if let Some(redeem_key) = &redeem_key {
+                term.info(&format!(
+                    "signing input {}.{} (redeem key {})\n",
+                    style!(input.transaction_id),
+                    style!(input.index_in_transaction),
+                    style!(redeem_key.public())
+                ))
+                .unwrap();
+
+                signature = Some(TxInWitness::new_redeem_pk(
+                    protocol_magic,
+                    redeem_key,
+                    &txid,
                 ));
             }
         }
 
-        if let Some(signature) = signature {
-            signatures.push(signature);
-        } else {
-            panic!("cannot sign input {:#?}", input)
+        match signature {
+            Some(signature) => signatures.push(signature),
+            None => return Err(Error::CannotSignTransactionUnknownInput(txin)),
         }
     }
 
@@ -217,6 +286,7 @@ pub fn status(term: &mut Term, root_dir: PathBuf, id_str: &str) -> Result<(), Er
     for input in tx.inputs.iter() {
         writeln!(term, "  {}.{}", style!(input.id), style!(input.index))?;
     }
+    writeln!(term, "change strategy: {}", trans.change_strategy)?;
     writeln!(term, "outputs:")?;
     for output in tx.outputs.iter() {
         writeln!(
@@ -230,6 +300,33 @@ pub fn status(term: &mut Term, root_dir: PathBuf, id_str: &str) -> Result<(), Er
     Ok(())
 }
 
+/// get the fee of the staging transaction: the difference between the
+/// total of the inputs and the total of the outputs (change included)
+pub fn fee(root_dir: PathBuf, id_str: &str) -> Result<Coin, Error> {
+    let staging = load_staging(root_dir, id_str)?;
+
+    let input_total = sum_coins(
+        staging
+            .transaction()
+            .inputs()
+            .iter()
+            .map(|x| x.expected_value),
+    )
+    .map_err(Error::CannotReportStatusInvalidInputTotal)?;
+    let tx = staging
+        .transaction()
+        .mk_txbuilder()
+        .map_err(Error::CannotReportStatusInvalidTxBuilder)?
+        .0
+        .make_tx()
+        .map_err(Error::CannotReportStatusInvalidTx)?;
+    let output_total = tx
+        .get_output_total()
+        .map_err(Error::CannotReportStatusInvalidOutputTotal)?;
+
+    (input_total - output_total).map_err(Error::CannotReportStatusInvalidOutputTotal)
+}
+
 pub fn add_input(
     term: &mut Term,
     root_dir: PathBuf,
@@ -276,17 +373,217 @@ pub fn add_output(
     staging.add_output(output).map_err(Error::CannotAddOutput)
 }
 
+/// maximum size (in bytes) of a transaction accepted by the network
+pub const MAX_TX_SIZE: usize = 65536;
+
+/// part of the `MAX_TX_SIZE` kept for the inputs, the witnesses and the
+/// change that are still to be added to the transaction
+const MAX_TX_SIZE_RESERVED: usize = MAX_TX_SIZE / 4;
+
+/// estimate the size of a transaction with the given outputs only
+fn estimate_outputs_size(outputs: &[TxOut]) -> usize {
+    let mut tx = Tx::new();
+    tx.outputs = outputs.to_vec();
+    tx::txaux_serialize_size(&tx, &[])
+}
+
+/// check the outputs can be added to the given transaction, without
+/// recording anything
+fn check_outputs(
+    mut transaction: core::Transaction,
+    outputs: &[core::Output],
+) -> Result<(), Error> {
+    for output in outputs {
+        transaction
+            .update_with(core::Operation::AddOutput(output.clone()))
+            .map_err(|e| {
+                Error::CannotAddOutput(
+                    core::staging_transaction::StagingUpdateError::TransactionIsInvalidState(e),
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// add all the outputs listed in the given file to the staging transaction
+///
+/// All the outputs are checked before anything is added. If the outputs
+/// do not fit in a single transaction, the remaining ones are added to
+/// new staging transactions (with the same changes and change strategy).
+pub fn add_outputs(
+    term: &mut Term,
+    root_dir: PathBuf,
+    id_str: &str,
+    outputs_file: &str,
+) -> Result<(), Error> {
+    let mut staging = load_staging(root_dir.clone(), id_str)?;
+
+    let format = payouts::Format::guess(outputs_file);
+    let reader = ::std::fs::OpenOptions::new()
+        .read(true)
+        .open(outputs_file)
+        .map_err(Error::CannotAddOutputsCannotOpenInputFile)?;
+    let payouts = payouts::read(reader, format).map_err(Error::CannotAddOutputsInvalidInputFile)?;
+
+    let network_magic = NetworkMagic::from(staging.protocol_magic);
+    for (address, _) in payouts.iter() {
+        if address.attributes.network_magic != network_magic {
+            return Err(Error::CannotAddOutputsInvalidNetworkMagic(address.clone()));
+        }
+    }
+    let total = sum_coins(payouts.iter().map(|(_, amount)| *amount))
+        .map_err(Error::CannotAddOutputsInvalidTotal)?;
+    let num_outputs = payouts.len();
+
+    // split the outputs in chunks small enough to fit in a transaction,
+    // the size of the outputs is accumulated as they are added
+    let empty_size = estimate_outputs_size(&[]);
+    let output_size = |output: &core::Output| estimate_outputs_size(&[output.into()]) - empty_size;
+    let max_size = MAX_TX_SIZE - MAX_TX_SIZE_RESERVED;
+    let mut chunks: Vec<Vec<core::Output>> = vec![Vec::new()];
+    let mut chunk_size = empty_size
+        + staging
+            .transaction()
+            .outputs()
+            .iter()
+            .map(|output| output_size(output))
+            .sum::<usize>();
+    for (address, amount) in payouts {
+        let output = core::Output {
+            address: address,
+            amount: amount,
+        };
+        let size = output_size(&output);
+        if chunk_size + size > max_size && !chunks.last().unwrap().is_empty() {
+            chunk_size = empty_size;
+            chunks.push(Vec::new());
+        }
+        chunk_size += size;
+        chunks.last_mut().unwrap().push(output);
+    }
+
+    // check every chunk before writing anything
+    let mut chunks = chunks.into_iter();
+    let first = chunks.next().unwrap();
+    let others: Vec<_> = chunks.collect();
+    check_outputs(staging.transaction().clone(), &first)?;
+    for chunk in others.iter() {
+        let mut transaction = core::Transaction::new();
+        for change in staging.transaction().changes() {
+            transaction
+                .update_with(core::Operation::AddChange(change.clone()))
+                .map_err(|e| {
+                    Error::CannotAddChange(
+                        core::staging_transaction::StagingUpdateError::TransactionIsInvalidState(e),
+                    )
+                })?;
+        }
+        check_outputs(transaction, chunk)?;
+    }
+
+    // create the extra staging transactions first, they are removed again
+    // if anything fails so no partial state is left behind
+    let mut new_stagings = Vec::with_capacity(others.len());
+    let mut summary = vec![(*staging.id(), first.len())];
+    for chunk in others {
+        let len = chunk.len();
+        match new_staging_with_outputs(&root_dir, &staging, chunk) {
+            Ok(new_staging) => {
+                summary.push((*new_staging.id(), len));
+                new_stagings.push(new_staging);
+            }
+            Err(err) => {
+                rollback(new_stagings);
+                return Err(err);
+            }
+        }
+    }
+    if let Err(err) = staging.add_outputs(first) {
+        rollback(new_stagings);
+        return Err(Error::CannotAddOutput(err));
+    }
+
+    writeln!(
+        term,
+        "{} outputs added, for a total of {}",
+        style!(num_outputs),
+        style!(total)
+    )?;
+    if summary.len() > 1 {
+        term.warn(&format!(
+            "the outputs do not fit in a single transaction, they have been split in {} staging transactions\n",
+            summary.len()
+        ))?;
+    }
+    for (id, count) in summary {
+        writeln!(term, "  {}: {} outputs", style!(id), style!(count))?;
+    }
+
+    Ok(())
+}
+
+/// a new staging transaction with the given outputs, and the changes and
+/// change strategy of `staging`
+fn new_staging_with_outputs(
+    root_dir: &Path,
+    staging: &StagingTransaction,
+    outputs: Vec<core::Output>,
+) -> Result<StagingTransaction, Error> {
+    let mut new_staging = StagingTransaction::new(root_dir.to_path_buf(), staging.protocol_magic)
+        .map_err(Error::CannotCreateNewTransaction)?;
+    let result = (|| {
+        for change in staging.transaction().changes() {
+            new_staging
+                .add_change(change.clone())
+                .map_err(Error::CannotAddChange)?;
+        }
+        if staging.transaction().change_strategy != core::ChangeStrategy::default() {
+            new_staging
+                .set_change_strategy(staging.transaction().change_strategy)
+                .map_err(Error::CannotAddChange)?;
+        }
+        new_staging
+            .add_outputs(outputs)
+            .map_err(Error::CannotAddOutput)
+    })();
+    match result {
+        Ok(()) => Ok(new_staging),
+        Err(err) => {
+            rollback(vec![new_staging]);
+            Err(err)
+        }
+    }
+}
+
+/// remove the given staging transactions, created by a command that failed
+fn rollback(stagings: Vec<StagingTransaction>) {
+    for staging in stagings {
+        let id = *staging.id();
+        if let Err(err) = staging.destroy() {
+            warn!("cannot remove the staging transaction {}: {}", id, err);
+        }
+    }
+}
+
 pub fn add_change(
     _term: &mut Term,
     root_dir: PathBuf,
     id_str: &str,
     change: ExtendedAddr,
+    strategy: Option<core::ChangeStrategy>,
 ) -> Result<(), Error> {
     let mut staging = load_staging(root_dir, id_str)?;
 
     staging
         .add_change(change.into())
-        .map_err(Error::CannotAddChange)
+        .map_err(Error::CannotAddChange)?;
+
+    if let Some(strategy) = strategy {
+        staging
+            .set_change_strategy(strategy)
+            .map_err(Error::CannotAddChange)?;
+    }
+    Ok(())
 }
 
 pub fn remove_input(
@@ -391,12 +688,19 @@ pub fn import(term: &mut Term, root_dir: PathBuf, import_file: Option<&str>) ->
     Ok(())
 }
 
+/// the amount of the extra outputs standing for the change outputs during
+/// the input selection: enough to cover the difference in the encoded size
+/// of the actual change amounts.
+const CHANGE_OUTPUT_PADDING: u64 = 1_000;
+
 pub fn input_select(
     term: &mut Term,
     root_dir: PathBuf,
     id_str: &str,
     wallets: Vec<WalletName>,
     selection_type: SelectionPolicy,
+    change_from: Option<WalletName>,
+    filter: InputFilter,
 ) -> Result<(), Error> {
     use cardano::input_selection::{Blackjack, HeadFirst, LargestFirst};
     use cardano::{fee, input_selection::InputSelectionAlgorithm, txutils};
@@ -405,43 +709,279 @@ pub fn input_select(
 
     let mut staging = load_staging(root_dir.clone(), id_str)?;
 
-    if !staging.transaction().has_change() {
+    // the fresh change address is only added to the staging transaction
+    // (and issued) once the selection succeeded
+    let mut transaction = staging.transaction().clone();
+    let change_wallet = match change_from {
+        None => None,
+        Some(_) if transaction.has_change() => {
+            return Err(Error::CannotInputSelectChangeAlreadySet)
+        }
+        Some(change_from) => {
+            let wallet = Wallet::load(&root_dir, change_from)?;
+            let lookup =
+                wallet::utils::WalletLookup::new(term, &wallet, staging.protocol_magic.into());
+            Some((wallet, lookup))
+        }
+    };
+    let mut change_addresses = change_wallet
+        .as_ref()
+        .map(|(wallet, lookup)| wallet::utils::ChangeAddresses::new(wallet, lookup));
+    let change = match change_addresses {
+        None => None,
+        Some(ref mut change_addresses) => {
+            let change = change_addresses
+                .derive(1)
+                .map_err(Error::CannotInputSelectCannotGetChangeAddress)?
+                .remove(0);
+            transaction
+                .update_with(core::Operation::AddChange(change.clone().into()))
+                .map_err(|e| {
+                    Error::CannotInputSelectCannotAddChange(
+                        core::staging_transaction::StagingUpdateError::TransactionIsInvalidState(e),
+                    )
+                })?;
+            Some(change)
+        }
+    };
+
+    if !transaction.has_change() {
         return Err(Error::CannotInputSelectNoChangeOption);
     }
 
-    let change_address = staging.transaction().changes()[0].address.clone();
+    let change_address = transaction.changes()[0].address.clone();
     let output_policy = txutils::OutputPolicy::One(change_address.clone());
 
-    let outputs = staging
-        .transaction()
+    let outputs = transaction
         .outputs()
         .iter()
         .map(|output| output.into())
-        .collect::<Vec<_>>();
-    let inputs = list_input_inputs(&root_dir, wallets)?;
-
-    let selection_result = match selection_type {
-        SelectionPolicy::Blackjack(threshold) => {
-            Blackjack::new(threshold, inputs).compute(&alg, outputs, &output_policy)
+        .collect::<Vec<TxOut>>();
+    let inputs = list_input_inputs(&root_dir, wallets, &filter)?;
+
+    // the output policy only accounts for one change output: the other
+    // change outputs of the change strategy are added as extra outputs so
+    // their fees are paid for too. The number of change outputs depends on
+    // the selected inputs (e.g. with a capped change), the selection is
+    // run again until it is known.
+    let changes = transaction.changes().to_vec();
+    let mut extra_changes = changes.len() - 1;
+    let selected_inputs = loop {
+        let mut targets = outputs.clone();
+        targets.extend(
+            changes
+                .iter()
+                .cycle()
+                .skip(1)
+                .take(extra_changes)
+                .map(|change| TxOut {
+                    address: change.address.clone(),
+                    value: Coin::new(CHANGE_OUTPUT_PADDING).unwrap(),
+                }),
+        );
+        let inputs = inputs.clone();
+        let selection_result = match selection_type {
+            SelectionPolicy::Blackjack(threshold) => {
+                Blackjack::new(threshold, inputs).compute(&alg, targets, &output_policy)
+            }
+            SelectionPolicy::LargestFirst => {
+                LargestFirst::from(inputs).compute(&alg, targets, &output_policy)
+            }
+            SelectionPolicy::FirstMatchFirst => {
+                HeadFirst::from(inputs).compute(&alg, targets, &output_policy)
+            }
+            SelectionPolicy::RandomImprove => {
+                RandomImprove::from(inputs).compute(&alg, targets, &output_policy)
+            }
+        };
+        let selected_inputs: Vec<core::Input> = selection_result
+            .map_err(Error::CannotInputSelectSelectionFailed)?
+            .selected_inputs
+            .into_iter()
+            .map(|input| core::Input {
+                transaction_id: input.ptr.id,
+                index_in_transaction: input.ptr.index,
+                expected_value: input.value.value,
+            })
+            .collect();
+
+        // check the change strategy can be honoured with these inputs
+        let mut transaction = transaction.clone();
+        for input in selected_inputs.iter() {
+            transaction
+                .update_with(core::Operation::AddInput(input.clone()))
+                .map_err(Error::CannotInputSelectCannotPayChanges)?;
+        }
+        match transaction.mk_txbuilder() {
+            Ok(_) => break selected_inputs,
+            Err(core::transaction::Error::NotEnoughInputsForChanges(count))
+                if count > extra_changes + 1 =>
+            {
+                extra_changes = count - 1;
+            }
+            Err(err) => return Err(Error::CannotInputSelectCannotPayChanges(err)),
         }
-        SelectionPolicy::LargestFirst => {
-            LargestFirst::from(inputs).compute(&alg, outputs, &output_policy)
+    };
+
+    if let (Some(change), Some(mut change_addresses)) = (change, change_addresses) {
+        change_addresses
+            .issue()
+            .map_err(Error::CannotInputSelectCannotGetChangeAddress)?;
+        writeln!(term, "change address {}", style!(&change))?;
+        staging
+            .add_change(change.into())
+            .map_err(Error::CannotInputSelectCannotAddChange)?;
+    }
+    for input in selected_inputs {
+        staging
+            .add_input(input)
+            .map_err(Error::CannotInputSelectCannotAddInput)?;
+    }
+    Ok(())
+}
+
+/// display the UTxOs of the given wallets that pass the given filter,
+/// i.e. the UTxOs `input-select` would select the inputs from.
+pub fn preview_inputs(
+    term: &mut Term,
+    root_dir: PathBuf,
+    wallets: Vec<WalletName>,
+    filter: InputFilter,
+) -> Result<(), Error> {
+    let utxos = list_utxos(&root_dir, wallets, &filter)?;
+    let total = sum_coins(utxos.iter().map(|utxo| utxo.credited_value))
+        .map_err(Error::CannotReportStatusInvalidInputTotal)?;
+
+    for utxo in utxos.iter() {
+        writeln!(
+            term,
+            "{}.{} {} {} ({})",
+            style!(utxo.transaction_id),
+            style!(utxo.index_in_transaction),
+            style!(utxo.credited_value),
+            style!(&utxo.credited_address),
+            utxo.credited_addressing
+        )?;
+    }
+    writeln!(
+        term,
+        "{} UTxOs, total: {}",
+        style!(utxos.len()),
+        style!(total)
+    )?;
+
+    Ok(())
+}
+
+/// where to send the funds of `sweep`
+pub enum SweepDestination {
+    /// a fresh change address of the given wallet
+    Wallet(WalletName),
+    Address(ExtendedAddr),
+}
+
+/// read an hexadecimal encoded `XPrv` (as generated by `debug generate-xprv`)
+fn read_xprv_file(xprv_file: &str) -> Result<XPrv, Error> {
+    let mut content = String::new();
+    ::std::fs::OpenOptions::new()
+        .read(true)
+        .open(xprv_file)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(Error::CannotSweepCannotOpenKeyFile)?;
+
+    let content = Secret::new(content);
+    let bytes =
+        Secret::new(hex::decode(content.trim()).map_err(|_| Error::CannotSweepInvalidKeyFile)?);
+    if bytes.len() != hdwallet::XPRV_SIZE {
+        return Err(Error::CannotSweepInvalidKeyFile);
+    }
+    xprv_from_bytes_verified(&bytes).map_err(Error::CannotSweepInvalidKey)
+}
+
+/// create a staging transaction moving all the funds of the bootstrap
+/// address of the given key to the destination.
+///
+/// The funds are looked for in the local copy of the blockchain. The
+/// staging transaction is finalized and signed, ready to be sent.
+pub fn sweep(
+    term: &mut Term,
+    root_dir: PathBuf,
+    blockchain: BlockchainName,
+    xprv_file: &str,
+    destination: SweepDestination,
+) -> Result<(), Error> {
+    let xprv = read_xprv_file(xprv_file)?;
+    let blockchain = Blockchain::load(root_dir.clone(), blockchain)?;
+    let protocol_magic = blockchain.config.protocol_magic;
+    let network_magic = NetworkMagic::from(protocol_magic);
+
+    let destination = match destination {
+        SweepDestination::Address(address) => {
+            if address.attributes.network_magic != network_magic {
+                return Err(Error::CannotSweepInvalidNetworkMagic(address));
+            }
+            address
         }
-        SelectionPolicy::FirstMatchFirst => {
-            HeadFirst::from(inputs).compute(&alg, outputs, &output_policy)
+        SweepDestination::Wallet(name) => {
+            let wallet = Wallet::load(root_dir.clone(), name)?;
+            wallet::utils::wallet_next_change_address(term, &wallet, network_magic)?
         }
     };
-    let selection_result = selection_result.map_err(Error::CannotInputSelectSelectionFailed)?;
 
-    for input in selection_result.selected_inputs {
+    let address = ExtendedAddr::new_simple(xprv.public(), network_magic);
+    let mut state = State::new(
+        StatePtr::new_before_genesis(blockchain.config.genesis.clone()),
+        SingleAddressLookup::new(address.clone()),
+    );
+    wallet::utils::scan_blockchain_utxos(term, &blockchain, &mut state);
+    if state.utxos.is_empty() {
+        return Err(Error::CannotSweepNoFunds(address));
+    }
+
+    let mut staging = StagingTransaction::new(root_dir, protocol_magic)
+        .map_err(Error::CannotCreateNewTransaction)?;
+    for (_, utxo) in state.utxos.iter() {
         staging
             .add_input(core::Input {
-                transaction_id: input.ptr.id,
-                index_in_transaction: input.ptr.index,
-                expected_value: input.value.value,
+                transaction_id: utxo.transaction_id,
+                index_in_transaction: utxo.index_in_transaction,
+                expected_value: utxo.credited_value,
             })
-            .map_err(Error::CannotInputSelectCannotAddInput)?;
+            .map_err(Error::CannotAddInput)?;
     }
+    staging
+        .add_change(destination.clone().into())
+        .map_err(Error::CannotAddChange)?;
+    staging.finalize().map_err(Error::CannotFinalize)?;
+
+    let tx = staging
+        .transaction()
+        .mk_txbuilder()
+        .map_err(Error::CannotSignTransactionNotFinalized)?
+        .0
+        .make_tx()
+        .map_err(Error::CannotSignTransactionInvalidTxAux)?;
+    let txid = tx.id();
+    // all the inputs are credited to the same address
+    for _ in 0..state.utxos.len() {
+        staging
+            .add_signature(TxInWitness::new(protocol_magic, &xprv, &txid))
+            .map_err(Error::CannotSignTransactionCannotAddSignature)?;
+    }
+
+    let total = state
+        .total()
+        .map_err(Error::CannotReportStatusInvalidInputTotal)?;
+    writeln!(
+        term,
+        "sweeping {} ({} UTxOs) from {} to {}",
+        style!(total),
+        style!(state.utxos.len()),
+        style!(address),
+        style!(destination)
+    )?;
+    writeln!(term, "{}", style!(staging.id()))?;
+
     Ok(())
 }
 
@@ -487,22 +1027,83 @@ fn find_input_in_all_utxos(root_dir: &Path, txid: TxId, index: u32) -> Result<co
     Err(Error::CannotFindInputsInAllLocalUtxos)
 }
 
-fn list_input_inputs(
+/// list the UTxOs of the given wallets passing the given filter
+fn list_utxos(
     root_dir: &Path,
     wallets: Vec<WalletName>,
-) -> Result<Vec<::cardano::txutils::Input<ExtendedAddr>>, Error> {
-    let mut inputs = Vec::new();
+    filter: &InputFilter,
+) -> Result<Vec<UTxO<lookup::Address>>, Error> {
+    let mut utxos = Vec::new();
     for wallet in wallets {
         let wallet = Wallet::load(root_dir, wallet)?;
         let state =
             create_wallet_state_from_logs(&wallet, &root_dir, lookup::accum::Accum::default())?;
 
-        inputs.extend(state.utxos.iter().map(|(_, utxo)| {
-            let txin = utxo.extract_txin();
-            let txout = utxo.extract_txout();
-            ::cardano::txutils::Input::new(txin, txout, utxo.credited_address.clone())
-        }))
+        let depths = if filter.needs_depth() {
+            received_depths(root_dir, &wallet, filter.min_confirmations.unwrap_or(0))?
+        } else {
+            BTreeMap::new()
+        };
+
+        utxos.extend(
+            state
+                .utxos
+                .into_iter()
+                .map(|(_, utxo)| utxo)
+                .filter(|utxo| filter.accept(utxo, depths.get(&utxo.extract_txin()).cloned())),
+        )
     }
 
-    Ok(inputs)
+    Ok(utxos)
+}
+
+/// get the depth (in blocks from the local blockchain's tip) of every
+/// fund received by the given wallet
+///
+/// Only the latest `limit` blocks are looked at: the older funds are given
+/// a depth of `limit`.
+fn received_depths(
+    root_dir: &Path,
+    wallet: &Wallet,
+    limit: u64,
+) -> Result<BTreeMap<TxoPointer, u64>, Error> {
+    let blockchain = wallet::utils::load_attached_blockchain(root_dir, &wallet.config)?;
+    let latest_blocks = blockchain.latest_block_dates(limit as usize)?;
+
+    let mut depths = BTreeMap::new();
+    let log_lock = wallet::utils::lock_wallet_log(wallet);
+    let reader = match log::LogReader::open(log_lock) {
+        Err(log::Error::LogNotFound) => return Ok(depths),
+        Err(err) => return Err(wallet::Error::from(err).into()),
+        Ok(reader) => reader,
+    };
+    let reader: log::LogIterator<lookup::Address> = reader.into_iter();
+    for entry in reader {
+        if let log::Log::ReceivedFund(ptr, utxo) = entry.map_err(wallet::Error::from)? {
+            let received = ptr.latest_block_date();
+            depths.insert(
+                utxo.extract_txin(),
+                coin_control::depth(&latest_blocks, &received),
+            );
+        }
+    }
+
+    Ok(depths)
+}
+
+fn list_input_inputs(
+    root_dir: &Path,
+    wallets: Vec<WalletName>,
+    filter: &InputFilter,
+) -> Result<Vec<::cardano::txutils::Input<ExtendedAddr>>, Error> {
+    let utxos = list_utxos(root_dir, wallets, filter)?;
+
+    Ok(utxos
+        .into_iter()
+        .map(|utxo| {
+            let txin = utxo.extract_txin();
+            let txout = utxo.extract_txout();
+            ::cardano::txutils::Input::new(txin, txout, utxo.credited_address)
+        })
+        .collect())
 }
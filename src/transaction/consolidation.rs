@@ -0,0 +1,222 @@
+//! plan the consolidation of a wallet's UTxOs
+//!
+//! Wallets receiving many small payments end up with a large UTxO set,
+//! making later transactions large and expensive. A consolidation merges
+//! these UTxOs into a few outputs, using as many transactions as needed so
+//! every one of them stays under the maximum transaction size.
+
+use super::commands::MAX_TX_SIZE;
+use cardano::{
+    address::ExtendedAddr,
+    coin::Coin,
+    fee::{self, Fee, FeeAlgorithm},
+    tx::{self, Tx, TxInWitness, TxOut},
+    txutils::Input,
+};
+use std::iter;
+
+/// one of the transactions of a consolidation plan
+pub struct Consolidation<A> {
+    pub inputs: Vec<Input<A>>,
+    pub total: Coin,
+    pub fee: Fee,
+}
+
+/// the transactions to perform to consolidate a set of UTxOs
+pub struct Plan<A> {
+    pub transactions: Vec<Consolidation<A>>,
+
+    /// the UTxOs worth less than the fee needed to spend them
+    pub dust: Vec<Input<A>>,
+
+    /// the UTxOs left as they are, as there were not enough of them for a
+    /// transaction (or not enough value to pay for its fee)
+    pub untouched: Vec<Input<A>>,
+}
+
+fn input_value<A>(input: &Input<A>) -> u64 {
+    input.value.value.into()
+}
+
+/// build a transaction spending the given inputs to the given addresses
+/// (with the full value of the inputs) along with fake witnesses
+fn mk_tx<A>(inputs: &[Input<A>], addresses: &[ExtendedAddr]) -> (Tx, Vec<TxInWitness>) {
+    let total: u64 = inputs.iter().map(input_value).sum();
+    let mut tx = Tx::new();
+    tx.inputs = inputs.iter().map(|input| input.ptr.clone()).collect();
+    tx.outputs = addresses
+        .iter()
+        .map(|address| TxOut {
+            address: address.clone(),
+            // the size of the encoded value is an upper bound of the final one
+            value: Coin::new(total).unwrap_or(Coin::zero()),
+        })
+        .collect();
+    let witnesses = iter::repeat(TxInWitness::fake())
+        .take(inputs.len())
+        .collect();
+    (tx, witnesses)
+}
+
+fn estimate_fee<A, F: FeeAlgorithm>(
+    fee_algorithm: &F,
+    inputs: &[Input<A>],
+    addresses: &[ExtendedAddr],
+) -> fee::Result<Fee> {
+    let (tx, witnesses) = mk_tx(inputs, addresses);
+    fee_algorithm.calculate_for_txaux_component(&tx, &witnesses)
+}
+
+fn estimate_size<A>(inputs: &[Input<A>], addresses: &[ExtendedAddr]) -> usize {
+    let (tx, witnesses) = mk_tx(inputs, addresses);
+    tx::txaux_serialize_size(&tx, &witnesses)
+}
+
+/// plan the transactions merging the given inputs into outputs to the
+/// given addresses.
+///
+/// The inputs worth less than the fee they add to a transaction are left
+/// aside as dust. The smallest inputs are merged first. The transactions
+/// that would spend less than `min_inputs` inputs are not planned.
+pub fn plan<A, F>(
+    fee_algorithm: &F,
+    inputs: Vec<Input<A>>,
+    addresses: &[ExtendedAddr],
+    min_inputs: usize,
+) -> fee::Result<Plan<A>>
+where
+    F: FeeAlgorithm,
+{
+    let base_fee: u64 = estimate_fee::<A, F>(fee_algorithm, &[], addresses)?
+        .to_coin()
+        .into();
+
+    let mut dust = Vec::new();
+    let mut candidates = Vec::new();
+    for input in inputs {
+        let with_input: u64 =
+            estimate_fee(fee_algorithm, ::std::slice::from_ref(&input), addresses)?
+                .to_coin()
+                .into();
+        if input_value(&input) <= with_input - base_fee {
+            dust.push(input);
+        } else {
+            candidates.push(input);
+        }
+    }
+    candidates.sort_by_key(input_value);
+
+    let mut chunks: Vec<Vec<Input<A>>> = vec![Vec::new()];
+    for input in candidates {
+        chunks.last_mut().unwrap().push(input);
+        if estimate_size(chunks.last().unwrap(), addresses) > MAX_TX_SIZE {
+            let input = chunks.last_mut().unwrap().pop().unwrap();
+            chunks.push(vec![input]);
+        }
+    }
+
+    let mut transactions = Vec::new();
+    let mut untouched = Vec::new();
+    for inputs in chunks {
+        if inputs.len() < min_inputs {
+            untouched.extend(inputs);
+            continue;
+        }
+
+        let fee = estimate_fee(fee_algorithm, &inputs, addresses)?;
+        let total: u64 = inputs.iter().map(input_value).sum();
+        let fee_value: u64 = fee.to_coin().into();
+        if total <= fee_value + addresses.len() as u64 {
+            untouched.extend(inputs);
+            continue;
+        }
+
+        transactions.push(Consolidation {
+            total: Coin::new(total).map_err(fee::Error::CoinError)?,
+            inputs: inputs,
+            fee: fee,
+        });
+    }
+
+    Ok(Plan {
+        transactions: transactions,
+        dust: dust,
+        untouched: untouched,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cardano::{
+        config::NetworkMagic,
+        fee::LinearFee,
+        hdwallet::XPub,
+        tx::{TxId, TxoPointer},
+    };
+
+    const ADA: u64 = 1_000_000;
+
+    fn address(index: u8) -> ExtendedAddr {
+        let xpub = XPub::from_slice(&[index; 64]).unwrap();
+        ExtendedAddr::new_simple(xpub, NetworkMagic::NoMagic)
+    }
+
+    fn input(id: u32, value: u64) -> Input<()> {
+        let ptr = TxoPointer {
+            id: TxId::new(&id.to_le_bytes()),
+            index: 0,
+        };
+        let txout = TxOut {
+            address: address(0),
+            value: Coin::new(value).unwrap(),
+        };
+        Input::new(ptr, txout, ())
+    }
+
+    #[test]
+    fn dust_is_skipped() {
+        let inputs = vec![input(0, 10), input(1, 2 * ADA), input(2, 3 * ADA)];
+        let plan = plan(&LinearFee::default(), inputs, &[address(1)], 2).unwrap();
+
+        assert_eq!(plan.dust.len(), 1);
+        assert_eq!(plan.transactions.len(), 1);
+        assert_eq!(plan.transactions[0].inputs.len(), 2);
+    }
+
+    #[test]
+    fn transactions_stay_under_max_size() {
+        let inputs = (0..2000).map(|id| input(id, ADA)).collect();
+        let addresses = [address(1), address(2)];
+        let plan = plan(&LinearFee::default(), inputs, &addresses, 3).unwrap();
+
+        assert!(plan.transactions.len() > 1);
+        let planned: usize = plan
+            .transactions
+            .iter()
+            .map(|consolidation| consolidation.inputs.len())
+            .sum();
+        assert_eq!(planned + plan.untouched.len(), 2000);
+        for consolidation in plan.transactions {
+            assert!(estimate_size(&consolidation.inputs, &addresses) <= MAX_TX_SIZE);
+        }
+    }
+
+    #[test]
+    fn not_enough_inputs() {
+        let inputs = vec![input(0, 2 * ADA), input(1, 3 * ADA)];
+        let plan = plan(&LinearFee::default(), inputs, &[address(1), address(2)], 3).unwrap();
+
+        assert!(plan.transactions.is_empty());
+        assert_eq!(plan.untouched.len(), 2);
+    }
+
+    #[test]
+    fn single_input_can_be_moved() {
+        let inputs = vec![input(0, 2 * ADA)];
+        let plan = plan(&LinearFee::default(), inputs, &[address(1)], 1).unwrap();
+
+        assert_eq!(plan.transactions.len(), 1);
+        assert!(plan.untouched.is_empty());
+    }
+}
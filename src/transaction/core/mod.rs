@@ -4,7 +4,7 @@ pub mod staging_id;
 pub mod staging_transaction;
 pub mod transaction;
 
-pub use self::operation::{Change, Input, Operation, Output};
+pub use self::operation::{Change, ChangeStrategy, Input, Operation, Output};
 pub use self::staging_id::StagingId;
 pub use self::staging_transaction::StagingTransaction;
 pub use self::transaction::Transaction;
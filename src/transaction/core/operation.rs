@@ -31,6 +31,9 @@ pub enum Operation {
     AddOutput(Output),
     AddChange(Change),
 
+    /// set how the change is split between the change addresses
+    SetChangeStrategy(ChangeStrategy),
+
     /// for now the unique identifier of a change address is the address itself
     RemoveChange(ExtendedAddr),
 
@@ -142,7 +145,8 @@ impl<'a> From<&'a Output> for TxOut {
 
 /// a change address in the transaction model
 ///
-/// TODO: adds support for percentage of the change to distribute
+/// How the change is distributed between the change addresses is
+/// given by the transaction's `ChangeStrategy`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Change {
     /// the address we are sending funds to.
@@ -153,3 +157,133 @@ impl From<ExtendedAddr> for Change {
         Change { address: o }
     }
 }
+
+/// strategy to split the change between the change addresses
+///
+/// The change outputs are distributed in a round robin fashion
+/// between the change addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeStrategy {
+    /// split the change evenly, one output per change address
+    Even,
+
+    /// split the change in outputs of the same amounts as the payments
+    /// (one output per change address), so the change outputs cannot be
+    /// told apart from the payments.
+    MatchPayments,
+
+    /// split the change in as many outputs as needed for each of them
+    /// not to be greater than the given amount.
+    Cap(Coin),
+}
+impl ChangeStrategy {
+    /// split the given change amount, for the given number of change
+    /// addresses and the given payments.
+    ///
+    /// The returned amounts sum up to `change`, none of them is zero.
+    pub fn split(self, change: Coin, num_addresses: usize, payments: &[Coin]) -> Vec<Coin> {
+        let change: u64 = change.into();
+        let mut amounts = Vec::new();
+        if change == 0 || num_addresses == 0 {
+            return amounts;
+        }
+
+        match self {
+            ChangeStrategy::Even => {
+                let num_addresses = num_addresses as u64;
+                let part = change / num_addresses;
+                amounts.push(part + change % num_addresses);
+                for _ in 1..num_addresses {
+                    amounts.push(part);
+                }
+            }
+            ChangeStrategy::MatchPayments => {
+                let mut remaining = change;
+                for payment in payments.iter().cycle().take(num_addresses - 1) {
+                    let payment: u64 = (*payment).into();
+                    if payment >= remaining {
+                        break;
+                    }
+                    amounts.push(payment);
+                    remaining -= payment;
+                }
+                amounts.push(remaining);
+            }
+            ChangeStrategy::Cap(max) => {
+                let max: u64 = max.into();
+                let mut remaining = change;
+                while max > 0 && remaining > max {
+                    amounts.push(max);
+                    remaining -= max;
+                }
+                amounts.push(remaining);
+            }
+        }
+
+        amounts
+            .into_iter()
+            .filter(|amount| *amount > 0)
+            .map(|amount| Coin::new(amount).unwrap())
+            .collect()
+    }
+}
+impl Default for ChangeStrategy {
+    fn default() -> Self {
+        ChangeStrategy::Even
+    }
+}
+impl fmt::Display for ChangeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChangeStrategy::Even => write!(f, "even"),
+            ChangeStrategy::MatchPayments => write!(f, "match payments"),
+            ChangeStrategy::Cap(max) => write!(f, "capped to {}", max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn coins(values: &[u64]) -> Vec<Coin> {
+        values.iter().map(|v| Coin::new(*v).unwrap()).collect()
+    }
+
+    #[test]
+    fn split_even() {
+        let split = ChangeStrategy::Even.split(Coin::new(10).unwrap(), 3, &[]);
+        assert_eq!(split, coins(&[4, 3, 3]));
+
+        let split = ChangeStrategy::Even.split(Coin::new(2).unwrap(), 3, &[]);
+        assert_eq!(split, coins(&[1, 1]));
+    }
+
+    #[test]
+    fn split_match_payments() {
+        let payments = coins(&[3, 5]);
+        let split = ChangeStrategy::MatchPayments.split(Coin::new(20).unwrap(), 3, &payments);
+        assert_eq!(split, coins(&[3, 5, 12]));
+
+        let split = ChangeStrategy::MatchPayments.split(Coin::new(4).unwrap(), 3, &payments);
+        assert_eq!(split, coins(&[3, 1]));
+
+        let split = ChangeStrategy::MatchPayments.split(Coin::new(4).unwrap(), 3, &[]);
+        assert_eq!(split, coins(&[4]));
+    }
+
+    #[test]
+    fn split_cap() {
+        let cap = ChangeStrategy::Cap(Coin::new(4).unwrap());
+        assert_eq!(cap.split(Coin::new(10).unwrap(), 1, &[]), coins(&[4, 4, 2]));
+        assert_eq!(cap.split(Coin::new(8).unwrap(), 2, &[]), coins(&[4, 4]));
+    }
+
+    #[test]
+    fn split_nothing() {
+        assert!(ChangeStrategy::Even.split(Coin::zero(), 2, &[]).is_empty());
+        assert!(ChangeStrategy::Even
+            .split(Coin::new(10).unwrap(), 0, &[])
+            .is_empty());
+    }
+}
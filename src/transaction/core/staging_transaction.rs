@@ -15,7 +15,7 @@ use storage_units::{
 
 use super::operation::ParsingOperationError;
 use super::transaction;
-use super::{config, Change, Input, Operation, Output, StagingId, Transaction};
+use super::{config, Change, ChangeStrategy, Input, Operation, Output, StagingId, Transaction};
 
 pub struct StagingTransaction {
     /// the unique Staging ID associated to this staging
@@ -231,12 +231,37 @@ impl StagingTransaction {
         self.append(Operation::AddChange(change))
     }
 
+    /// set how the change is split between the change addresses
+    pub fn set_change_strategy(
+        &mut self,
+        strategy: ChangeStrategy,
+    ) -> Result<(), StagingUpdateError> {
+        self.append(Operation::SetChangeStrategy(strategy))
+    }
+
     pub fn add_output(&mut self, output: Output) -> Result<(), StagingUpdateError> {
         // we don't need to check anything here, we don't mind
         // reusing twice the same address/output
         self.append(Operation::AddOutput(output))
     }
 
+    /// add all the given outputs to the transaction
+    ///
+    /// The outputs are first applied to a copy of the transaction, so
+    /// if one of them cannot be added none of them is recorded in the
+    /// staging file.
+    pub fn add_outputs(&mut self, outputs: Vec<Output>) -> Result<(), StagingUpdateError> {
+        let mut transaction = self.transaction.clone();
+        for output in outputs.iter() {
+            transaction.update_with(Operation::AddOutput(output.clone()))?;
+        }
+
+        for output in outputs {
+            self.add_output(output)?;
+        }
+        Ok(())
+    }
+
     /// remove the input associated to the given `TxIn`
     ///
     /// # panic
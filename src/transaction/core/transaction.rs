@@ -1,12 +1,12 @@
-use super::{Change, Input, Operation, Output};
+use super::{Change, ChangeStrategy, Input, Operation, Output};
 use cardano::{
     address::ExtendedAddr,
+    coin::Coin,
     tx::{TxInWitness, TxOut, TxWitness, TxoPointer},
 };
 use cardano::{
     fee::LinearFee,
     txbuild::{self, TxBuilder, TxFinalized},
-    txutils::OutputPolicy,
 };
 use std::{error, fmt};
 
@@ -26,6 +26,8 @@ pub enum Error {
 
     CannotAddChangeToAFinalizedTransaction,
 
+    CannotSetChangeStrategyOfAFinalizedTransaction,
+
     TransactionNotFinalized,
 
     /// when input is duplicated in the transaction
@@ -35,12 +37,12 @@ pub enum Error {
     CannotRemoveOutput,
     CannotRemoveChangeChangeNotFound,
 
-    /// TODO: this is temporary only until we can support selection
-    /// policy with multiple change addresses.
-    ///
-    /// In the mean time we need to ask users to remove the previous output
-    /// or to keep the current one.
-    MoreThanOneChangeAddressIsNotSupportedYet,
+    /// the inputs do not cover the outputs and the fees
+    NotEnoughInputs,
+
+    /// the change does not cover the fees of the given number of change
+    /// outputs required by the change strategy
+    NotEnoughInputsForChanges(usize),
 
     ErrorWhenApplyingOutputPolicy(txbuild::Error),
 
@@ -55,12 +57,14 @@ impl fmt::Display for Error {
             Error::CannotAddInputsToAFinalizedTransaction => write!(f, "Transaction is in a finalized state, cannot add more inputs"),
             Error::CannotAddOutputToAFinalizedTransaction => write!(f, "Transaction is in a finalized state, cannot add more outputs"),
             Error::CannotAddChangeToAFinalizedTransaction => write!(f, "Transaction is in a finalized state, cannot add more change addresses"),
+            Error::CannotSetChangeStrategyOfAFinalizedTransaction => write!(f, "Transaction is in a finalized state, cannot change the change strategy"),
             Error::TransactionNotFinalized => write!(f, "Transaction is not finalized, finalize it first"),
             Error::DoubleSpend => write!(f, "Input already used in the transaction"),
             Error::CannotRemoveInputInputNotFound => write!(f, "Cannot remove input, input not found"),
             Error::CannotRemoveOutput => write!(f, "Cannot remove output, output not found"),
             Error::CannotRemoveChangeChangeNotFound => write!(f, "Cannot remove change, change address not found"),
-            Error::MoreThanOneChangeAddressIsNotSupportedYet => write!(f, "Cannot add more than one output address for now, this feature is not yet supported"),
+            Error::NotEnoughInputs => write!(f, "Not enough inputs to cover the outputs and the fees"),
+            Error::NotEnoughInputsForChanges(count) => write!(f, "Not enough inputs to cover the fees of the {} change outputs, add inputs or remove change addresses", count),
             Error::ErrorWhenApplyingOutputPolicy(_) => write!(f, "Error when applying the output policy utilising the changes"),
             Error::CannotBuildTxFromBuilder(_) => write!(f, "Error when constructing the Tx, invalid data."),
         }
@@ -75,12 +79,14 @@ impl error::Error for Error {
             Error::CannotAddInputsToAFinalizedTransaction => None,
             Error::CannotAddOutputToAFinalizedTransaction => None,
             Error::CannotAddChangeToAFinalizedTransaction => None,
+            Error::CannotSetChangeStrategyOfAFinalizedTransaction => None,
             Error::TransactionNotFinalized => None,
             Error::DoubleSpend => None,
             Error::CannotRemoveInputInputNotFound => None,
             Error::CannotRemoveOutput => None,
             Error::CannotRemoveChangeChangeNotFound => None,
-            Error::MoreThanOneChangeAddressIsNotSupportedYet => None,
+            Error::NotEnoughInputsForChanges(_) => None,
+            Error::NotEnoughInputs => None,
             Error::ErrorWhenApplyingOutputPolicy(ref err) => Some(err),
             Error::CannotBuildTxFromBuilder(ref err) => Some(err),
         }
@@ -107,6 +113,8 @@ pub struct Transaction {
     pub inputs: Vec<Input>,
     pub outputs: Vec<Output>,
     pub changes: Vec<Change>,
+    #[serde(default)]
+    pub change_strategy: ChangeStrategy,
     pub witnesses: TxWitness,
     pub finalized: bool,
 }
@@ -117,12 +125,13 @@ impl Transaction {
             inputs: Vec::new(),
             outputs: Vec::new(),
             changes: Vec::new(),
+            change_strategy: ChangeStrategy::default(),
             witnesses: TxWitness::new(),
             finalized: false,
         }
     }
 
-    pub fn mk_txbuilder(&self) -> Result<(TxBuilder, Vec<TxOut>)> {
+    fn mk_base_txbuilder(&self) -> TxBuilder {
         let mut builder = TxBuilder::new();
         for input in self.inputs.iter() {
             let ptr = TxoPointer {
@@ -139,14 +148,90 @@ impl Transaction {
             };
             builder.add_output_value(&out);
         }
-        let changes_used = if self.changes.len() == 1 && (!self.inputs().is_empty()) {
-            let fee_algorithm = LinearFee::default();
-            builder
-                .add_output_policy(
-                    &fee_algorithm,
-                    &OutputPolicy::One(self.changes[0].address.clone()),
-                )
+        builder
+    }
+
+    /// compute the change outputs, with the amounts given by `split`.
+    ///
+    /// The fee is recomputed until it covers the change outputs too.
+    /// Returns the number of change outputs which could not be paid for if
+    /// the change cannot pay for its own outputs.
+    fn try_mk_changes<F>(&self, split: F) -> Result<::std::result::Result<Vec<TxOut>, usize>>
+    where
+        F: Fn(Coin) -> Vec<Coin>,
+    {
+        let fee_algorithm = LinearFee::default();
+        let builder = self.mk_base_txbuilder();
+        let input_total: u64 = builder
+            .get_input_total()
+            .map_err(Error::ErrorWhenApplyingOutputPolicy)?
+            .into();
+        let output_total: u64 = builder
+            .get_output_total()
+            .map_err(Error::ErrorWhenApplyingOutputPolicy)?
+            .into();
+        let mut fee: u64 = builder
+            .calculate_fee(&fee_algorithm)
+            .map_err(Error::ErrorWhenApplyingOutputPolicy)?
+            .to_coin()
+            .into();
+        if input_total < output_total + fee {
+            return Err(Error::NotEnoughInputs);
+        }
+
+        loop {
+            // cannot fail, this is less than the input total
+            let change = Coin::new(input_total - output_total - fee).unwrap();
+            let changes: Vec<TxOut> = split(change)
+                .into_iter()
+                .enumerate()
+                .map(|(index, amount)| TxOut {
+                    address: self.changes[index % self.changes.len()].address.clone(),
+                    value: amount,
+                })
+                .collect();
+
+            let mut builder = self.mk_base_txbuilder();
+            for change in changes.iter() {
+                builder.add_output_value(change);
+            }
+            let new_fee: u64 = builder
+                .calculate_fee(&fee_algorithm)
                 .map_err(Error::ErrorWhenApplyingOutputPolicy)?
+                .to_coin()
+                .into();
+
+            if new_fee <= fee {
+                return Ok(Ok(changes));
+            }
+            if input_total < output_total + new_fee {
+                return Ok(Err(changes.len()));
+            }
+            fee = new_fee;
+        }
+    }
+
+    /// compute the change outputs following the change strategy
+    ///
+    /// If the inputs cannot pay for the fees of all the change outputs, this
+    /// is an error: the change is never left to the fees.
+    fn mk_changes(&self) -> Result<Vec<TxOut>> {
+        let payments: Vec<Coin> = self.outputs.iter().map(|output| output.amount).collect();
+        let strategy = self.change_strategy;
+        let num_addresses = self.changes.len();
+
+        self.try_mk_changes(|change| strategy.split(change, num_addresses, &payments))?
+            .map_err(Error::NotEnoughInputsForChanges)
+    }
+
+    pub fn mk_txbuilder(&self) -> Result<(TxBuilder, Vec<TxOut>)> {
+        let mut builder = self.mk_base_txbuilder();
+        let changes_used = if self.has_change() && (!self.inputs().is_empty()) {
+            let changes = self.mk_changes()?;
+            for change in changes.iter() {
+                builder.add_output_value(change);
+            }
+            changes
         } else {
             Vec::new()
         };
@@ -178,6 +263,7 @@ impl Transaction {
             Operation::AddInput(input) => self.add_input(input),
             Operation::AddOutput(output) => self.add_output(output),
             Operation::AddChange(change) => self.add_change(change),
+            Operation::SetChangeStrategy(strategy) => self.set_change_strategy(strategy),
             Operation::RemoveInput(txin) => self.remove_input(txin),
             Operation::RemoveOutput(index) => self.remove_output(index),
             Operation::RemoveChange(addr) => self.remove_change(addr),
@@ -239,12 +325,16 @@ impl Transaction {
         if self.is_finalized() {
             return Err(Error::CannotAddChangeToAFinalizedTransaction);
         }
-        if !self.changes.is_empty() {
-            return Err(Error::MoreThanOneChangeAddressIsNotSupportedYet);
-        }
         self.changes.push(change);
         Ok(self)
     }
+    fn set_change_strategy(&mut self, strategy: ChangeStrategy) -> Result<&mut Self> {
+        if self.is_finalized() {
+            return Err(Error::CannotSetChangeStrategyOfAFinalizedTransaction);
+        }
+        self.change_strategy = strategy;
+        Ok(self)
+    }
     fn add_witness(&mut self, witness: TxInWitness) -> Result<&mut Self> {
         if !self.is_finalized() {
             return Err(Error::CannotAddWitnessesToAnOpenedTransaction);
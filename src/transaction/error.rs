@@ -1,6 +1,6 @@
 use super::super::{blockchain, wallet};
-use super::core;
-use cardano::{self, coin};
+use super::{core, payouts};
+use cardano::{self, address::ExtendedAddr, coin, tx::TxoPointer};
 use storage_units;
 
 use std::{error, fmt, io};
@@ -21,6 +21,7 @@ pub enum Error {
     CannotSignTransactionNotFinalized(core::transaction::Error),
     CannotSignTransactionInvalidTxAux(cardano::txbuild::Error),
     CannotSignTransactionCannotAddSignature(core::staging_transaction::StagingUpdateError),
+    CannotSignTransactionUnknownInput(TxoPointer),
     CannotReportStatusInvalidInputTotal(coin::Error),
     CannotReportStatusInvalidOutputTotal(coin::Error),
     CannotReportStatusInvalidTxBuilder(core::transaction::Error),
@@ -29,6 +30,10 @@ pub enum Error {
     CannotAddInput(core::staging_transaction::StagingUpdateError),
     CannotFindInputsInAllLocalUtxos,
     CannotAddOutput(core::staging_transaction::StagingUpdateError),
+    CannotAddOutputsCannotOpenInputFile(io::Error),
+    CannotAddOutputsInvalidInputFile(payouts::Error),
+    CannotAddOutputsInvalidNetworkMagic(ExtendedAddr),
+    CannotAddOutputsInvalidTotal(coin::Error),
     CannotAddChange(core::staging_transaction::StagingUpdateError),
     CannotRemoveInput(core::staging_transaction::StagingUpdateError),
     CannotRemoveOutput(core::staging_transaction::StagingUpdateError),
@@ -43,8 +48,19 @@ pub enum Error {
     CannotImportStaging(core::staging_transaction::StagingUpdateError),
 
     CannotInputSelectNoChangeOption,
+    CannotInputSelectChangeAlreadySet,
+    CannotInputSelectCannotGetChangeAddress(wallet::Error),
+    CannotInputSelectCannotAddChange(core::staging_transaction::StagingUpdateError),
     CannotInputSelectSelectionFailed(cardano::input_selection::Error),
     CannotInputSelectCannotAddInput(core::staging_transaction::StagingUpdateError),
+    CannotInputSelectCannotPayChanges(core::transaction::Error),
+    CannotConsolidate(cardano::fee::Error),
+    CannotConsolidateInvalidTransaction(core::transaction::Error),
+    CannotSweepCannotOpenKeyFile(io::Error),
+    CannotSweepInvalidKeyFile,
+    CannotSweepInvalidKey(cardano::hdwallet::Error),
+    CannotSweepInvalidNetworkMagic(ExtendedAddr),
+    CannotSweepNoFunds(ExtendedAddr),
 }
 impl From<::std::io::Error> for Error {
     fn from(e: ::std::io::Error) -> Self {
@@ -91,6 +107,11 @@ impl fmt::Display for Error {
             CannotSignTransactionCannotAddSignature(_) => {
                 write!(f, "Cannot add signature to the transaction")
             }
+            CannotSignTransactionUnknownInput(txin) => write!(
+                f,
+                "Cannot sign the input {}.{}, it belongs to no local wallet nor to the redeem key",
+                txin.id, txin.index
+            ),
             CannotReportStatusInvalidInputTotal(_) => {
                 write!(f, "Input total of the transaction is invalid")
             }
@@ -107,6 +128,20 @@ impl fmt::Display for Error {
                 write!(f, "Cannot find inputs within the local UTxOs")
             }
             CannotAddOutput(_) => write!(f, "Cannot add output to the staging transaction"),
+            CannotAddOutputsCannotOpenInputFile(_) => {
+                write!(f, "Cannot add outputs: cannot open input file")
+            }
+            CannotAddOutputsInvalidInputFile(_) => {
+                write!(f, "Cannot add outputs: invalid input file")
+            }
+            CannotAddOutputsInvalidNetworkMagic(address) => write!(
+                f,
+                "Cannot add outputs: address `{}' is not for the blockchain of the staging transaction",
+                address
+            ),
+            CannotAddOutputsInvalidTotal(_) => {
+                write!(f, "Cannot add outputs: total of the outputs is invalid")
+            }
             CannotAddChange(_) => write!(f, "Cannot add change to the staging transaction"),
             CannotRemoveInput(_) => write!(f, "Cannot remove input from the staging transaction"),
             CannotRemoveOutput(_) => write!(f, "Cannot remove output from the staging transaction"),
@@ -142,14 +177,48 @@ impl fmt::Display for Error {
             ),
             CannotInputSelectNoChangeOption => write!(
                 f,
-                "Add change before trying to run the input selection algorithm"
+                "Add change (or use `--change-from') before trying to run the input selection algorithm"
             ),
+            CannotInputSelectChangeAlreadySet => write!(
+                f,
+                "The staging transaction already has a change address, `--change-from' would add another one"
+            ),
+            CannotInputSelectCannotGetChangeAddress(_) => {
+                write!(f, "Cannot get a change address from the wallet")
+            }
+            CannotInputSelectCannotAddChange(_) => {
+                write!(f, "Cannot add change to the staging transaction")
+            }
             CannotInputSelectSelectionFailed(_) => {
                 write!(f, "Input selection algorithm failed to run")
             }
             CannotInputSelectCannotAddInput(_) => {
                 write!(f, "Cannot add input to the staging transaction")
             }
+            CannotInputSelectCannotPayChanges(_) => write!(
+                f,
+                "Cannot select enough inputs to pay for all the change outputs"
+            ),
+            CannotConsolidate(_) => write!(f, "Cannot plan the consolidation of the UTxOs"),
+            CannotConsolidateInvalidTransaction(_) => {
+                write!(f, "Cannot build the consolidation transaction")
+            }
+            CannotSweepCannotOpenKeyFile(_) => write!(f, "Cannot sweep: cannot open key file"),
+            CannotSweepInvalidKeyFile => write!(
+                f,
+                "Cannot sweep: the key file does not contain an hexadecimal encoded XPrv"
+            ),
+            CannotSweepInvalidKey(_) => write!(f, "Cannot sweep: invalid XPrv"),
+            CannotSweepInvalidNetworkMagic(address) => write!(
+                f,
+                "Cannot sweep: address `{}' is not for the given blockchain",
+                address
+            ),
+            CannotSweepNoFunds(address) => write!(
+                f,
+                "Cannot sweep: no funds found for address `{}' in the local blockchain",
+                address
+            ),
         }
     }
 }
@@ -170,6 +239,7 @@ impl error::Error for Error {
             CannotSignTransactionNotFinalized(ref err) => Some(err),
             CannotSignTransactionInvalidTxAux(ref err) => Some(err),
             CannotSignTransactionCannotAddSignature(ref err) => Some(err),
+            CannotSignTransactionUnknownInput(_) => None,
             CannotReportStatusInvalidInputTotal(ref err) => Some(err),
             CannotReportStatusInvalidOutputTotal(ref err) => Some(err),
             CannotReportStatusInvalidTxBuilder(ref err) => Some(err),
@@ -178,6 +248,10 @@ impl error::Error for Error {
             CannotAddInput(ref err) => Some(err),
             CannotFindInputsInAllLocalUtxos => None,
             CannotAddOutput(ref err) => Some(err),
+            CannotAddOutputsCannotOpenInputFile(ref err) => Some(err),
+            CannotAddOutputsInvalidInputFile(ref err) => Some(err),
+            CannotAddOutputsInvalidNetworkMagic(_) => None,
+            CannotAddOutputsInvalidTotal(ref err) => Some(err),
             CannotAddChange(ref err) => Some(err),
             CannotRemoveInput(ref err) => Some(err),
             CannotRemoveOutput(ref err) => Some(err),
@@ -191,8 +265,19 @@ impl error::Error for Error {
             CannotImportFromStdin(ref err) => Some(err),
             CannotImportStaging(ref err) => Some(err),
             CannotInputSelectNoChangeOption => None,
+            CannotInputSelectChangeAlreadySet => None,
+            CannotInputSelectCannotGetChangeAddress(ref err) => Some(err),
+            CannotInputSelectCannotAddChange(ref err) => Some(err),
             CannotInputSelectSelectionFailed(ref err) => Some(err),
             CannotInputSelectCannotAddInput(ref err) => Some(err),
+            CannotInputSelectCannotPayChanges(ref err) => Some(err),
+            CannotConsolidate(ref err) => Some(err),
+            CannotConsolidateInvalidTransaction(ref err) => Some(err),
+            CannotSweepCannotOpenKeyFile(ref err) => Some(err),
+            CannotSweepInvalidKeyFile => None,
+            CannotSweepInvalidKey(ref err) => Some(err),
+            CannotSweepInvalidNetworkMagic(_) => None,
+            CannotSweepNoFunds(_) => None,
         }
     }
 }
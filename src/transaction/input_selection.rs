@@ -0,0 +1,383 @@
+//! input selection policies available to the `input-select` command
+//!
+//! On top of the algorithms provided by `cardano::input_selection`, this
+//! module provides the Random-Improve algorithm, used by the Cardano wallets
+//! to keep the UTxO set healthy: the change outputs are kept close to the
+//! payments, so the UTxO set remains able to handle future payments of the
+//! same order of magnitude.
+
+use cardano::{
+    coin::Coin,
+    fee::{Fee, FeeAlgorithm},
+    input_selection::{Error, InputSelectionAlgorithm, InputSelectionResult, Result},
+    tx::{Tx, TxInWitness, TxOut},
+    txutils::{Input, OutputPolicy},
+};
+use rand::{rngs::StdRng, FromEntropy, Rng};
+use std::iter;
+
+/// the input selection algorithm to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionPolicy {
+    /// select the inputs in the order they are given
+    FirstMatchFirst,
+
+    /// select the largest inputs first
+    LargestFirst,
+
+    /// select the exact necessary amount, accepting to lose at most the
+    /// given amount in extra fees (and producing no change)
+    Blackjack(Coin),
+
+    /// see `RandomImprove`
+    RandomImprove,
+}
+
+/// the Random-Improve input selection algorithm
+///
+/// 1. random phase: for each output (largest first), select random inputs
+///    until the selected value covers the output;
+/// 2. improve phase: for each output (smallest first), keep on selecting
+///    random inputs as long as they bring the selected value closer to twice
+///    the output value, without going above three times the output value;
+/// 3. select random inputs until the fees are covered too.
+///
+/// The change of the transaction is then close to the payments, so the UTxO
+/// set will likely contain inputs for payments of the same sizes.
+pub struct RandomImprove<A, R> {
+    available: Vec<Input<A>>,
+    rng: R,
+}
+impl<A, R: Rng> RandomImprove<A, R> {
+    /// create a `RandomImprove` selection using the given random number
+    /// generator (use a seeded RNG to have deterministic selections).
+    pub fn with_rng(inputs: Vec<Input<A>>, rng: R) -> Self {
+        RandomImprove {
+            available: inputs,
+            rng: rng,
+        }
+    }
+
+    fn pick_random(&mut self) -> Option<Input<A>> {
+        if self.available.is_empty() {
+            None
+        } else {
+            let index = self.rng.gen_range(0, self.available.len());
+            Some(self.available.swap_remove(index))
+        }
+    }
+}
+impl<A> From<Vec<Input<A>>> for RandomImprove<A, StdRng> {
+    fn from(inputs: Vec<Input<A>>) -> Self {
+        RandomImprove::with_rng(inputs, StdRng::from_entropy())
+    }
+}
+
+fn input_value<A>(input: &Input<A>) -> u64 {
+    input.value.value.into()
+}
+
+fn distance(a: u64, b: u64) -> u64 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+/// estimate the fee of a transaction with the given inputs, outputs and
+/// change (if any)
+fn estimate_fee<A, F: FeeAlgorithm>(
+    fee_algorithm: &F,
+    inputs: &[Input<A>],
+    outputs: &[TxOut],
+    change: Option<TxOut>,
+) -> Result<Fee> {
+    let mut tx = Tx::new();
+    tx.inputs = inputs.iter().map(|input| input.ptr.clone()).collect();
+    tx.outputs = outputs.iter().cloned().chain(change).collect();
+    let fake_witnesses: Vec<TxInWitness> = iter::repeat(TxInWitness::fake())
+        .take(inputs.len())
+        .collect();
+    fee_algorithm
+        .calculate_for_txaux_component(&tx, &fake_witnesses)
+        .map_err(Error::FeeError)
+}
+
+impl<A, R: Rng> InputSelectionAlgorithm<A> for RandomImprove<A, R> {
+    fn select_input<F>(
+        &mut self,
+        _fee_algorithm: &F,
+        _estimated_needed_output: Coin,
+    ) -> Result<Option<Input<A>>>
+    where
+        F: FeeAlgorithm,
+    {
+        Ok(self.pick_random())
+    }
+
+    fn compute<F>(
+        &mut self,
+        fee_algorithm: &F,
+        outputs: Vec<TxOut>,
+        output_policy: &OutputPolicy,
+    ) -> Result<InputSelectionResult<A>>
+    where
+        F: FeeAlgorithm,
+    {
+        if outputs.is_empty() {
+            return Err(Error::NoOutputs);
+        }
+
+        let mut targets: Vec<u64> = outputs.iter().map(|output| output.value.into()).collect();
+        targets.sort_unstable_by(|a, b| b.cmp(a));
+
+        // 1. random phase, largest output first
+        let mut selections = Vec::with_capacity(targets.len());
+        for target in targets {
+            let mut selected = Vec::new();
+            let mut total = 0;
+            while total < target {
+                let input = self.pick_random().ok_or(Error::NotEnoughInput)?;
+                total += input_value(&input);
+                selected.push(input);
+            }
+            selections.push((target, total, selected));
+        }
+
+        // 2. improve phase, smallest output first
+        for (target, total, selected) in selections.iter_mut().rev() {
+            let ideal = *target * 2;
+            let maximum = *target * 3;
+            while let Some(input) = self.pick_random() {
+                let new_total = *total + input_value(&input);
+                if new_total <= maximum && distance(new_total, ideal) < distance(*total, ideal) {
+                    *total = new_total;
+                    selected.push(input);
+                } else {
+                    self.available.push(input);
+                    break;
+                }
+            }
+        }
+
+        let mut selected_inputs: Vec<Input<A>> = selections
+            .into_iter()
+            .flat_map(|(_, _, selected)| selected)
+            .collect();
+        let mut input_total: u64 = selected_inputs.iter().map(input_value).sum();
+        let output_total: u64 = outputs.iter().map(|output| u64::from(output.value)).sum();
+
+        // 3. cover the fees
+        loop {
+            let change = match output_policy {
+                OutputPolicy::One(address) => TxOut {
+                    address: address.clone(),
+                    value: Coin::new(input_total - output_total).map_err(Error::CoinError)?,
+                },
+            };
+            let fee = estimate_fee(fee_algorithm, &selected_inputs, &outputs, Some(change))?;
+            let needed = output_total + u64::from(fee.to_coin());
+
+            if input_total >= needed {
+                let change = input_total - needed;
+                return Ok(InputSelectionResult {
+                    estimated_fees: fee,
+                    estimated_change: if change > 0 {
+                        Some(Coin::new(change).map_err(Error::CoinError)?)
+                    } else {
+                        None
+                    },
+                    selected_inputs: selected_inputs,
+                });
+            }
+
+            let input = self.pick_random().ok_or(Error::NotEnoughInput)?;
+            input_total += input_value(&input);
+            selected_inputs.push(input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cardano::{
+        address::ExtendedAddr,
+        config::NetworkMagic,
+        fee::LinearFee,
+        hdwallet::XPub,
+        input_selection::{Blackjack, HeadFirst, LargestFirst},
+        tx::{TxId, TxoPointer},
+    };
+    use rand::SeedableRng;
+
+    const ADA: u64 = 1_000_000;
+
+    fn address(index: u8) -> ExtendedAddr {
+        let xpub = XPub::from_slice(&[index; 64]).unwrap();
+        ExtendedAddr::new_simple(xpub, NetworkMagic::NoMagic)
+    }
+
+    fn input(id: u32, value: u64) -> Input<()> {
+        let ptr = TxoPointer {
+            id: TxId::new(&id.to_le_bytes()),
+            index: 0,
+        };
+        let txout = TxOut {
+            address: address(0),
+            value: Coin::new(value).unwrap(),
+        };
+        Input::new(ptr, txout, ())
+    }
+
+    fn utxos<R: Rng>(rng: &mut R, count: u32) -> Vec<Input<()>> {
+        (0..count)
+            .map(|id| input(id, rng.gen_range(1, 100) * ADA))
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_with_seed() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let inputs = utxos(&mut rng, 50);
+        let outputs = vec![TxOut {
+            address: address(1),
+            value: Coin::new(30 * ADA).unwrap(),
+        }];
+        let policy = OutputPolicy::One(address(2));
+
+        let select = || {
+            RandomImprove::with_rng(inputs.clone(), StdRng::seed_from_u64(1))
+                .compute(&LinearFee::default(), outputs.clone(), &policy)
+                .unwrap()
+                .selected_inputs
+                .into_iter()
+                .map(|input| input.ptr)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(select(), select());
+    }
+
+    #[test]
+    fn not_enough_input() {
+        let inputs = vec![input(0, 10 * ADA), input(1, 5 * ADA)];
+        let outputs = vec![TxOut {
+            address: address(1),
+            value: Coin::new(20 * ADA).unwrap(),
+        }];
+        let policy = OutputPolicy::One(address(2));
+
+        let result = RandomImprove::with_rng(inputs, StdRng::seed_from_u64(1)).compute(
+            &LinearFee::default(),
+            outputs,
+            &policy,
+        );
+        assert!(result.is_err());
+    }
+
+    /// the size of the initial UTxO set of the simulations
+    const UTXOS: usize = 100;
+
+    /// run a sequence of random payments, feeding the change back into the
+    /// UTxO set, and return the mean distance between the change and the
+    /// payment (relative to the payment) along with the size of the UTxO
+    /// set after each payment. The payments the policy cannot make are
+    /// skipped.
+    fn simulate<S>(mut select: S) -> (f64, Vec<usize>)
+    where
+        S: FnMut(Vec<Input<()>>, Vec<TxOut>, &OutputPolicy) -> Option<InputSelectionResult<()>>,
+    {
+        let mut rng = StdRng::seed_from_u64(2019);
+        let mut utxos = utxos(&mut rng, UTXOS as u32);
+        let policy = OutputPolicy::One(address(2));
+        let mut next_id = utxos.len() as u32;
+        let mut distances = 0.0;
+        let mut payments = 0;
+        let mut sizes = Vec::new();
+
+        for _ in 0..200 {
+            let payment = rng.gen_range(1, 20) * ADA;
+            let outputs = vec![TxOut {
+                address: address(1),
+                value: Coin::new(payment).unwrap(),
+            }];
+
+            if let Some(result) = select(utxos.clone(), outputs, &policy) {
+                utxos.retain(|utxo| {
+                    !result
+                        .selected_inputs
+                        .iter()
+                        .any(|input| input.ptr == utxo.ptr)
+                });
+                let change = result.estimated_change.map(u64::from).unwrap_or(0);
+                if change > 0 {
+                    utxos.push(input(next_id, change));
+                    next_id += 1;
+                }
+
+                distances += (change as f64 - payment as f64).abs() / payment as f64;
+                payments += 1;
+            }
+            sizes.push(utxos.len());
+        }
+
+        (distances / payments as f64, sizes)
+    }
+
+    #[test]
+    fn utxo_set_evolution() {
+        let fee_algorithm = LinearFee::default();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let (random_improve, random_improve_utxos) = simulate(|inputs, outputs, policy| {
+            let seed = rng.gen();
+            RandomImprove::with_rng(inputs, StdRng::seed_from_u64(seed))
+                .compute(&fee_algorithm, outputs, policy)
+                .ok()
+        });
+        let (largest_first, largest_first_utxos) = simulate(|inputs, outputs, policy| {
+            LargestFirst::from(inputs)
+                .compute(&fee_algorithm, outputs, policy)
+                .ok()
+        });
+        let (_, head_first_utxos) = simulate(|inputs, outputs, policy| {
+            HeadFirst::from(inputs)
+                .compute(&fee_algorithm, outputs, policy)
+                .ok()
+        });
+        let (_, blackjack_utxos) = simulate(|inputs, outputs, policy| {
+            Blackjack::new(Coin::new(ADA).unwrap(), inputs)
+                .compute(&fee_algorithm, outputs, policy)
+                .ok()
+        });
+
+        // the change is closer to the payments than with largest first
+        assert!(
+            random_improve < largest_first,
+            "random improve: {}, largest first: {}",
+            random_improve,
+            largest_first
+        );
+
+        // largest first trades the largest input for its change: the UTxO
+        // set never shrinks, while the other policies consolidate it
+        assert!(largest_first_utxos.iter().all(|&size| size == UTXOS));
+        for (name, sizes) in vec![
+            ("random improve", &random_improve_utxos),
+            ("head first", &head_first_utxos),
+            ("blackjack", &blackjack_utxos),
+        ] {
+            assert!(
+                sizes.windows(2).all(|pair| pair[1] <= pair[0]),
+                "{}: {:?}",
+                name,
+                sizes
+            );
+            assert!(sizes[sizes.len() - 1] < UTXOS, "{}: {:?}", name, sizes);
+        }
+        // random improve still keeps enough inputs for the next payments
+        assert!(random_improve_utxos[random_improve_utxos.len() - 1] > UTXOS / 2);
+    }
+}
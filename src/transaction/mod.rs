@@ -1,3 +1,8 @@
+pub mod coin_control;
 pub mod commands;
+pub mod consolidation;
 pub mod core;
 pub mod error;
+pub mod input_selection;
+pub mod payouts;
+pub mod redeem;
@@ -0,0 +1,261 @@
+//! parsing of payout files: a list of addresses and amounts to send funds to
+//!
+//! Two formats are supported:
+//!
+//! * CSV: one `address,amount` per line. Empty lines, lines starting with `#`
+//!   and an optional `address,amount` header line are ignored;
+//! * JSON: an array of objects `{ "address": "...", "amount": 42 }`.
+//!
+//! The amounts are expressed in Lovelace.
+
+use cardano::{address::ExtendedAddr, coin::Coin};
+use serde_json;
+use std::{error, fmt, io::Read, path::Path, str::FromStr};
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(::std::io::Error),
+    InvalidJson(serde_json::Error),
+    /// the line (starting from 1) does not contain the expected number of fields
+    InvalidLine(usize),
+    /// the address of the given line (or entry) is not valid
+    InvalidAddress(usize, String),
+    /// the amount of the given line (or entry) is not valid
+    InvalidAmount(usize, String),
+    NoPayouts,
+}
+impl From<::std::io::Error> for Error {
+    fn from(e: ::std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::IoError(_) => write!(f, "I/O Error"),
+            Error::InvalidJson(_) => write!(f, "Invalid JSON payouts file"),
+            Error::InvalidLine(line) => {
+                write!(f, "Line {}: expected `address,amount'", line)
+            }
+            Error::InvalidAddress(line, err) => {
+                write!(f, "Line {}: invalid address ({})", line, err)
+            }
+            Error::InvalidAmount(line, err) => write!(f, "Line {}: invalid amount ({})", line, err),
+            Error::NoPayouts => write!(f, "No payouts found in the file"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::IoError(ref err) => Some(err),
+            Error::InvalidJson(ref err) => Some(err),
+            Error::InvalidLine(_) => None,
+            Error::InvalidAddress(_, _) => None,
+            Error::InvalidAmount(_, _) => None,
+            Error::NoPayouts => None,
+        }
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+impl Format {
+    /// guess the format of the payouts file from its extension, default to CSV
+    pub fn guess<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Csv,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonPayout {
+    address: String,
+    amount: u64,
+}
+
+/// read all the payouts from the given reader
+pub fn read<R: Read>(mut reader: R, format: Format) -> Result<Vec<(ExtendedAddr, Coin)>> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+
+    let payouts = match format {
+        Format::Csv => parse_csv(&content)?,
+        Format::Json => parse_json(&content)?,
+    };
+
+    if payouts.is_empty() {
+        Err(Error::NoPayouts)
+    } else {
+        Ok(payouts)
+    }
+}
+
+fn parse_csv(content: &str) -> Result<Vec<(ExtendedAddr, Coin)>> {
+    let mut payouts = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<_> = line.split(',').map(|field| field.trim()).collect();
+        if fields.len() != 2 {
+            return Err(Error::InvalidLine(line_number));
+        }
+        if payouts.is_empty() && fields[0].eq_ignore_ascii_case("address") {
+            // skip the header
+            continue;
+        }
+
+        payouts.push(parse_payout(line_number, fields[0], fields[1])?);
+    }
+    Ok(payouts)
+}
+
+fn parse_json(content: &str) -> Result<Vec<(ExtendedAddr, Coin)>> {
+    let entries: Vec<JsonPayout> = serde_json::from_str(content).map_err(Error::InvalidJson)?;
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(idx, entry)| parse_payout(idx + 1, &entry.address, &entry.amount.to_string()))
+        .collect()
+}
+
+fn parse_payout(line: usize, address: &str, amount: &str) -> Result<(ExtendedAddr, Coin)> {
+    let address = ExtendedAddr::from_str(address)
+        .map_err(|err| Error::InvalidAddress(line, format!("{}", err)))?;
+    let amount =
+        Coin::from_str(amount).map_err(|err| Error::InvalidAmount(line, format!("{}", err)))?;
+    Ok((address, amount))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cardano::{config::NetworkMagic, hdwallet::XPub};
+
+    fn address(index: u8) -> ExtendedAddr {
+        let xpub = XPub::from_slice(&[index; 64]).unwrap();
+        ExtendedAddr::new_simple(xpub, NetworkMagic::NoMagic)
+    }
+
+    fn read_str(content: &str, format: Format) -> Result<Vec<(ExtendedAddr, Coin)>> {
+        read(content.as_bytes(), format)
+    }
+
+    #[test]
+    fn csv() {
+        let content = format!(
+            "address,amount\n\n# the first payout\n{},42\n  {} , 1000000  \n",
+            address(1),
+            address(2)
+        );
+        let payouts = read_str(&content, Format::Csv).unwrap();
+        assert_eq!(
+            payouts,
+            vec![
+                (address(1), Coin::new(42).unwrap()),
+                (address(2), Coin::new(1_000_000).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_header_only_first() {
+        let content = format!("{},42\naddress,amount\n", address(1));
+        match read_str(&content, Format::Csv) {
+            Err(Error::InvalidAddress(2, _)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn csv_invalid_line() {
+        let content = format!("{},42\n{},1,2\n", address(1), address(2));
+        match read_str(&content, Format::Csv) {
+            Err(Error::InvalidLine(2)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn csv_invalid_address() {
+        match read_str("# comment\nnot-an-address,42\n", Format::Csv) {
+            Err(Error::InvalidAddress(2, _)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn csv_invalid_amount() {
+        let content = format!("{},-1\n", address(1));
+        match read_str(&content, Format::Csv) {
+            Err(Error::InvalidAmount(1, _)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        let content = format!("{},45000000000000001\n", address(1));
+        match read_str(&content, Format::Csv) {
+            Err(Error::InvalidAmount(1, _)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn json() {
+        let content = format!(
+            r#"[ {{ "address": "{}", "amount": 42 }}, {{ "address": "{}", "amount": 7 }} ]"#,
+            address(1),
+            address(2)
+        );
+        let payouts = read_str(&content, Format::Json).unwrap();
+        assert_eq!(
+            payouts,
+            vec![
+                (address(1), Coin::new(42).unwrap()),
+                (address(2), Coin::new(7).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_invalid() {
+        match read_str(r#"[ { "address": "x" } ]"#, Format::Json) {
+            Err(Error::InvalidJson(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match read_str(r#"[ { "address": "x", "amount": 1 } ]"#, Format::Json) {
+            Err(Error::InvalidAddress(1, _)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn no_payouts() {
+        match read_str("address,amount\n# nothing\n\n", Format::Csv) {
+            Err(Error::NoPayouts) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match read_str("[]", Format::Json) {
+            Err(Error::NoPayouts) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn guess() {
+        assert_eq!(Format::guess("payouts.json"), Format::Json);
+        assert_eq!(Format::guess("payouts.JSON"), Format::Json);
+        assert_eq!(Format::guess("payouts.csv"), Format::Csv);
+        assert_eq!(Format::guess("payouts"), Format::Csv);
+    }
+}
@@ -0,0 +1,138 @@
+//! redeem keys, to spend the funds of the AVVM distribution (vending
+//! certificates) held at redeem addresses.
+
+use cardano::{
+    address::ExtendedAddr,
+    config::{GenesisData, ProtocolMagic},
+    redeem, tx,
+    util::hex,
+};
+use std::{error, fmt, io, path::Path};
+
+use super::super::wallet::state::{lookup, utxo::UTxO};
+
+#[derive(Debug)]
+pub enum Error {
+    CannotOpenKeyFile(io::Error),
+    InvalidKeyEncoding,
+    InvalidKey(redeem::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::CannotOpenKeyFile(_) => write!(f, "Cannot open the redeem key file"),
+            Error::InvalidKeyEncoding => write!(
+                f,
+                "The redeem key is neither base64 nor hexadecimal encoded"
+            ),
+            Error::InvalidKey(_) => write!(f, "Invalid redeem key"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::CannotOpenKeyFile(ref err) => Some(err),
+            Error::InvalidKeyEncoding => None,
+            Error::InvalidKey(ref err) => Some(err),
+        }
+    }
+}
+
+/// read a redeem key, either from the given file or from the given string
+/// directly. The key is base64 (as found in the vending certificates) or
+/// hexadecimal encoded.
+pub fn read_key(key: &str) -> Result<redeem::PrivateKey, Error> {
+    let encoded = if Path::new(key).is_file() {
+        ::std::fs::read_to_string(key).map_err(Error::CannotOpenKeyFile)?
+    } else {
+        key.to_owned()
+    };
+    decode_key(encoded.trim())
+}
+
+/// decode a redeem key, hexadecimal or base64 encoded
+///
+/// A hexadecimal encoded key is also valid base64 (of the wrong length):
+/// the hexadecimal encoding is tried first for the keys of the length of
+/// a hexadecimal encoded key.
+fn decode_key(encoded: &str) -> Result<redeem::PrivateKey, Error> {
+    let is_hex = encoded.len() == redeem::PRIVATEKEY_SIZE * 2
+        && encoded.chars().all(|c| c.is_ascii_hexdigit());
+    let bytes = if is_hex {
+        hex::decode(encoded).map_err(|_| Error::InvalidKeyEncoding)?
+    } else {
+        ::base64::decode(encoded)
+            .or_else(|_| ::base64::decode_config(encoded, ::base64::URL_SAFE))
+            .map_err(|_| Error::InvalidKeyEncoding)?
+    };
+    redeem::PrivateKey::from_slice(&bytes).map_err(Error::InvalidKey)
+}
+
+/// get the UTxO created by the genesis AVVM distribution for the given
+/// redeem public key, if any.
+pub fn genesis_utxo(
+    genesis: &GenesisData,
+    public_key: &redeem::PublicKey,
+) -> Option<UTxO<lookup::Address>> {
+    let value = genesis.avvm_distr.get(public_key)?;
+    let (txid, address) = tx::redeem_pubkey_to_txid(public_key, genesis.protocol_magic);
+
+    Some(UTxO {
+        transaction_id: txid,
+        index_in_transaction: 0,
+        credited_address: address.clone(),
+        credited_addressing: address.into(),
+        credited_value: *value,
+    })
+}
+
+/// the redeem address of the given redeem public key
+pub fn address(public_key: &redeem::PublicKey, protocol_magic: ProtocolMagic) -> ExtendedAddr {
+    tx::redeem_pubkey_to_txid(public_key, protocol_magic).1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: [u8; redeem::PRIVATEKEY_SIZE] = [
+        0x3f, 0x2a, 0x91, 0x07, 0xc4, 0x5e, 0x18, 0xd6, 0x7b, 0x00, 0xee, 0x42, 0x9c, 0x13, 0x68,
+        0xa5, 0x21, 0xf0, 0x5d, 0x8e, 0x37, 0xb9, 0x64, 0x0c, 0xd1, 0x7a, 0x2f, 0x96, 0x4b, 0xe3,
+        0x08, 0x55,
+    ];
+
+    fn expected() -> redeem::PublicKey {
+        redeem::PrivateKey::from_slice(&KEY).unwrap().public()
+    }
+
+    #[test]
+    fn hex_key() {
+        let encoded = hex::encode(&KEY);
+        assert_eq!(decode_key(&encoded).unwrap().public(), expected());
+        assert_eq!(
+            decode_key(&encoded.to_uppercase()).unwrap().public(),
+            expected()
+        );
+    }
+
+    #[test]
+    fn base64_key() {
+        let encoded = ::base64::encode(&KEY);
+        assert_eq!(decode_key(&encoded).unwrap().public(), expected());
+        let encoded = ::base64::encode_config(&KEY, ::base64::URL_SAFE);
+        assert_eq!(decode_key(&encoded).unwrap().public(), expected());
+    }
+
+    #[test]
+    fn invalid_key() {
+        match decode_key("not a key!") {
+            Err(Error::InvalidKeyEncoding) => {}
+            result => panic!("unexpected result: {:?}", result.map(|key| key.public())),
+        }
+        match decode_key(&hex::encode(&KEY[..16])) {
+            Err(Error::InvalidKey(_)) => {}
+            result => panic!("unexpected result: {:?}", result.map(|key| key.public())),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! catch the interruption (`Ctrl-C`) of the long running commands, so they
+//! can save their progress and stop cleanly
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// while it is alive, an interruption does not kill the process: it is only
+/// recorded, the command is to check `interrupted` regularly.
+pub struct Interruptible(());
+impl Interruptible {
+    pub fn new() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        catch();
+        Interruptible(())
+    }
+
+    pub fn interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+}
+impl Drop for Interruptible {
+    fn drop(&mut self) {
+        release()
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: ::libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst)
+}
+#[cfg(unix)]
+fn catch() {
+    unsafe {
+        ::libc::signal(::libc::SIGINT, on_interrupt as ::libc::sighandler_t);
+    }
+}
+#[cfg(unix)]
+fn release() {
+    unsafe {
+        ::libc::signal(::libc::SIGINT, ::libc::SIG_DFL);
+    }
+}
+#[cfg(not(unix))]
+fn catch() {}
+#[cfg(not(unix))]
+fn release() {}
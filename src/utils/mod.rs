@@ -1,7 +1,9 @@
 #[macro_use]
 pub mod term;
 // pub mod action;
+pub mod interrupt;
 pub mod password_encrypted;
 pub mod pretty;
 pub mod prompt;
+pub mod secret;
 pub mod time;
@@ -10,6 +10,8 @@ use std::{
 
 use cryptoxide::{chacha20poly1305::ChaCha20Poly1305, hmac::Hmac, pbkdf2::pbkdf2, sha2::Sha512};
 
+use super::secret::Secret;
+
 const PASSWORD_DERIVATION_ITERATIONS: u32 = 10_000;
 const SALT_SIZE: usize = 16;
 
@@ -18,14 +20,13 @@ const TAG_SIZE: usize = 16;
 const KEY_SIZE: usize = 32;
 
 pub type Password = [u8];
-type Key = [u8; KEY_SIZE];
 type Salt = [u8; SALT_SIZE];
 type Nonce = [u8; NONCE_SIZE];
 
 pub fn encrypt(password: &Password, data: &[u8]) -> Vec<u8> {
     let salt = generate_salt();
     let nonce = generate_nonce();
-    let mut key = [0; KEY_SIZE];
+    let mut key = Secret::new(vec![0; KEY_SIZE]);
     let mut tag = [0; TAG_SIZE];
     let len = data.len();
 
@@ -48,13 +49,14 @@ pub fn encrypt(password: &Password, data: &[u8]) -> Vec<u8> {
     bytes
 }
 
-pub fn decrypt(password: &Password, data: &[u8]) -> Option<Vec<u8>> {
+/// decrypt the data, the decrypted data being wiped from memory when dropped
+pub fn decrypt(password: &Password, data: &[u8]) -> Option<Secret<Vec<u8>>> {
     let mut reader = data;
     let mut salt = [0; SALT_SIZE];
     let mut nonce = [0; NONCE_SIZE];
-    let mut key = [0; KEY_SIZE];
+    let mut key = Secret::new(vec![0; KEY_SIZE]);
     let len = data.len() - TAG_SIZE - SALT_SIZE - NONCE_SIZE;
-    let mut bytes: Vec<u8> = repeat(0).take(len).collect();
+    let mut bytes = Secret::new(repeat(0).take(len).collect::<Vec<u8>>());
 
     reader.read_exact(&mut salt[..]).unwrap();
     reader.read_exact(&mut nonce[..]).unwrap();
@@ -68,7 +70,7 @@ pub fn decrypt(password: &Password, data: &[u8]) -> Option<Vec<u8>> {
     }
 }
 
-fn password_to_key(password: &Password, salt: Salt, key: &mut Key) {
+fn password_to_key(password: &Password, salt: Salt, key: &mut [u8]) {
     let mut mac = Hmac::new(Sha512::new(), password);
 
     pbkdf2(&mut mac, &salt[..], PASSWORD_DERIVATION_ITERATIONS, key);
@@ -2,6 +2,10 @@ use super::super::term::Term;
 use cardano::bip::bip39::{self, dictionary::Language};
 use console::style;
 use dialoguer::{Confirmation, Input};
+use std::collections::BTreeSet;
+
+/// the number of words in a BIP39 dictionary
+const DICTIONARY_SIZE: u16 = 2048;
 
 fn interactive_input_word<D>(term: &mut Term, dic: &D, idx: usize, count: usize) -> String
 where
@@ -37,7 +41,7 @@ where
     }
 }
 
-type PromptedMnemonics = (bip39::MnemonicString, bip39::Mnemonics, bip39::Entropy);
+pub type PromptedMnemonics = (bip39::MnemonicString, bip39::Mnemonics, bip39::Entropy);
 
 fn process_mnemonics<D>(dic: &D, string: String) -> bip39::Result<PromptedMnemonics>
 where
@@ -126,20 +130,23 @@ where
     }
 }
 
+/// prompt for the whole mnemonic phrase at once (not validated)
+fn prompt_mnemonic_phrase(size: bip39::Type) -> String {
+    Input::new()
+        .with_prompt(&format!(
+            "Please enter all your {} mnemonics",
+            style(size.mnemonic_count()).bold().red()
+        ))
+        .interact()
+        .unwrap()
+}
+
 pub fn input_mnemonic_phrase<D>(term: &mut Term, dic: &D, size: bip39::Type) -> PromptedMnemonics
 where
     D: Language,
 {
-    let count = size.mnemonic_count();
-
     loop {
-        let string = Input::new()
-            .with_prompt(&format!(
-                "Please enter all your {} mnemonics",
-                style(count).bold().red()
-            ))
-            .interact()
-            .unwrap();
+        let string = prompt_mnemonic_phrase(size);
 
         match validate_mnemonics(dic, size, string) {
             Ok(res) => {
@@ -157,3 +164,99 @@ where
         }
     }
 }
+
+/// prompt for the mnemonic phrase, and if it is not valid, look for the
+/// phrases the user may have meant (see `repair_candidates`).
+///
+/// Returns all the candidates (only the entered phrase if it is valid).
+pub fn input_mnemonic_phrase_to_repair<D>(
+    term: &mut Term,
+    dic: &D,
+    size: bip39::Type,
+) -> Vec<PromptedMnemonics>
+where
+    D: Language,
+{
+    let string = prompt_mnemonic_phrase(size);
+
+    match validate_mnemonics(dic, size, string.clone()) {
+        Ok(res) => vec![res],
+        Err(prompt) => {
+            term.warn(&format!(
+                "{}, looking for similar valid mnemonics\n",
+                prompt
+            ))
+            .unwrap();
+            repair_candidates(dic, size, &string)
+        }
+    }
+}
+
+/// list the valid mnemonic phrases of the given size that differ from the
+/// given (invalid) phrase by either:
+///
+/// * one wrong word (or one word not in the dictionary);
+/// * two adjacent words swapped;
+/// * one missing word.
+pub fn repair_candidates<D>(dic: &D, size: bip39::Type, string: &str) -> Vec<PromptedMnemonics>
+where
+    D: Language,
+{
+    let count = size.mnemonic_count();
+    let indices: Vec<Option<u16>> = string
+        .split_whitespace()
+        .map(|word| dic.lookup_mnemonic(word).ok().map(|index| index.0))
+        .collect();
+    let unknown: Vec<usize> = (0..indices.len())
+        .filter(|i| indices[*i].is_none())
+        .collect();
+    let known = |indices: &[Option<u16>]| -> Vec<u16> {
+        indices.iter().map(|index| index.unwrap_or(0)).collect()
+    };
+
+    let mut tries: BTreeSet<Vec<u16>> = BTreeSet::new();
+    if indices.len() == count && unknown.len() <= 1 {
+        let positions: Vec<usize> = if unknown.is_empty() {
+            (0..count).collect()
+        } else {
+            unknown.clone()
+        };
+        for position in positions {
+            for word in 0..DICTIONARY_SIZE {
+                let mut candidate = known(&indices);
+                candidate[position] = word;
+                tries.insert(candidate);
+            }
+        }
+        if unknown.is_empty() {
+            for position in 1..count {
+                let mut candidate = known(&indices);
+                candidate.swap(position - 1, position);
+                tries.insert(candidate);
+            }
+        }
+    } else if indices.len() + 1 == count && unknown.is_empty() {
+        for position in 0..count {
+            for word in 0..DICTIONARY_SIZE {
+                let mut candidate = known(&indices);
+                candidate.insert(position, word);
+                tries.insert(candidate);
+            }
+        }
+    }
+
+    tries
+        .into_iter()
+        .filter_map(|candidate| {
+            let mnemonics = bip39::Mnemonics::from_mnemonics(
+                candidate
+                    .into_iter()
+                    .map(|index| bip39::MnemonicIndex::new(index).unwrap())
+                    .collect(),
+            )
+            .ok()?;
+            let entropy = bip39::Entropy::from_mnemonics(&mnemonics).ok()?;
+            Some((mnemonics.to_string(dic), mnemonics, entropy))
+        })
+        .collect()
+}
@@ -0,0 +1,238 @@
+//! secret holding types: passwords, seeds and decrypted keys
+//!
+//! The memory of a `Secret` is overwritten with zeros when it is dropped, so
+//! the secret does not linger in the memory freed by the process.
+//!
+//! The keys held in the structures of `cardano` (e.g. the `XPrv` of the
+//! wallets) cannot be wiped field by field: a `Wiped` value has its memory
+//! overwritten with zeros once dropped instead.
+//!
+//! **Caveat:** the copies made before the secret was wrapped, and the memory
+//! owned (on the heap) by a `Wiped` value, are not wiped.
+
+use cardano::hdwallet::{self, XPrv, XPRV_SIZE};
+use std::{
+    fmt,
+    mem::{self, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{self, Ordering},
+};
+
+/// how much of the stack is overwritten after building an `XPrv`, see
+/// `burn_stack`
+const BURN_STACK_SIZE: usize = 4096;
+
+/// types whose memory can be overwritten with zeros
+pub trait Zeroize {
+    fn zeroize(&mut self);
+}
+impl Zeroize for [u8] {
+    fn zeroize(&mut self) {
+        for byte in self.iter_mut() {
+            // volatile writes are not optimized away, even if the memory is
+            // not read afterward
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+impl Zeroize for Vec<u8> {
+    fn zeroize(&mut self) {
+        self.as_mut_slice().zeroize();
+        self.clear();
+    }
+}
+impl Zeroize for String {
+    fn zeroize(&mut self) {
+        // the string is emptied afterward, it remains valid UTF-8
+        unsafe { self.as_mut_vec() }.zeroize();
+    }
+}
+
+/// a secret, wiped from the memory when dropped
+pub struct Secret<T: Zeroize>(T);
+impl<T: Zeroize> Secret<T> {
+    pub fn new(secret: T) -> Self {
+        Secret(secret)
+    }
+}
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(secret: T) -> Self {
+        Secret::new(secret)
+    }
+}
+impl<T: Zeroize> Deref for Secret<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl<T: Zeroize> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize()
+    }
+}
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(<redacted>)")
+    }
+}
+
+/// a value holding secrets it does not allow to wipe, its memory is
+/// overwritten with zeros once it is dropped
+///
+/// Only the memory of the value itself is wiped, not the memory it owns.
+pub struct Wiped<T>(ManuallyDrop<T>);
+impl<T> Wiped<T> {
+    pub fn new(value: T) -> Self {
+        Wiped(ManuallyDrop::new(value))
+    }
+}
+impl<T> Deref for Wiped<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl<T> DerefMut for Wiped<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+impl<T> Drop for Wiped<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.0);
+            // the value is dropped: its memory is only bytes now
+            let bytes = &mut self.0 as *mut ManuallyDrop<T> as *mut u8;
+            for offset in 0..mem::size_of::<T>() {
+                ptr::write_volatile(bytes.add(offset), 0);
+            }
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+impl<T> fmt::Debug for Wiped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Wiped(<redacted>)")
+    }
+}
+
+/// overwrite with zeros the stack the functions called before were using
+///
+/// `cardano` only builds an `XPrv` from an array given by value: the copies
+/// of the array are left in the stack frames of the building functions, which
+/// are called (not inlined) just before this.
+#[inline(never)]
+fn burn_stack() {
+    let mut stack = [0u8; BURN_STACK_SIZE];
+    (&mut stack[..]).zeroize();
+}
+
+/// build the `XPrv` of the given bytes (`XPRV_SIZE` of them), see
+/// `XPrv::from_bytes_verified`, leaving no copy of them behind
+pub fn xprv_from_bytes_verified(bytes: &[u8]) -> Result<XPrv, hdwallet::Error> {
+    #[inline(never)]
+    fn build(bytes: &[u8]) -> Result<XPrv, hdwallet::Error> {
+        if bytes.len() != XPRV_SIZE {
+            return Err(hdwallet::Error::InvalidXPrvSize(bytes.len()));
+        }
+        let mut array = [0; XPRV_SIZE];
+        array.copy_from_slice(bytes);
+        XPrv::from_bytes_verified(array)
+    }
+    let xprv = build(bytes);
+    burn_stack();
+    xprv
+}
+
+/// build the `XPrv` of the given seed (`XPRV_SIZE` bytes), see
+/// `XPrv::normalize_bytes`, leaving no copy of it behind
+pub fn xprv_normalize_bytes(seed: &[u8]) -> XPrv {
+    #[inline(never)]
+    fn build(seed: &[u8]) -> XPrv {
+        let mut array = [0; XPRV_SIZE];
+        array.copy_from_slice(seed);
+        XPrv::normalize_bytes(array)
+    }
+    let xprv = build(seed);
+    burn_stack();
+    xprv
+}
+
+/// keep the memory of the process (and so the secrets it holds) out of the
+/// core dumps, where the platform allows it
+#[cfg(unix)]
+pub fn disable_core_dumps() {
+    let limit = ::libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { ::libc::setrlimit(::libc::RLIMIT_CORE, &limit) } != 0 {
+        warn!(
+            "cannot disable the core dumps: {}",
+            ::std::io::Error::last_os_error()
+        );
+    }
+    // also prevents other processes of the user from attaching to ours
+    #[cfg(target_os = "linux")]
+    {
+        if unsafe { ::libc::prctl(::libc::PR_SET_DUMPABLE, 0) } != 0 {
+            warn!(
+                "cannot prevent the process from being attached to: {}",
+                ::std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+#[cfg(not(unix))]
+pub fn disable_core_dumps() {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zeroize() {
+        let mut bytes = [0xffu8; 32];
+        (&mut bytes[..]).zeroize();
+        assert_eq!(bytes, [0; 32]);
+
+        let mut password = String::from("password");
+        password.zeroize();
+        assert!(password.is_empty());
+
+        let mut data = vec![1, 2, 3];
+        data.zeroize();
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn wiped_drops_the_value() {
+        use std::rc::Rc;
+
+        let counter = Rc::new(());
+        let wiped = Wiped::new(counter.clone());
+        assert_eq!(Rc::strong_count(&counter), 2);
+        drop(wiped);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn xprv_from_bytes() {
+        let seed = Secret::new(vec![0xffu8; XPRV_SIZE]);
+        let xprv = xprv_normalize_bytes(&seed);
+        assert_eq!(
+            xprv_from_bytes_verified(xprv.as_ref()).unwrap().as_ref(),
+            xprv.as_ref()
+        );
+        assert!(xprv_from_bytes_verified(&seed).is_err());
+        assert!(xprv_from_bytes_verified(&seed[1..]).is_err());
+    }
+}
@@ -12,6 +12,8 @@ use indicatif;
 
 pub use self::config::{ColorChoice, Config};
 
+use super::secret::Secret;
+
 use std::{
     error::Error,
     io::{self, Write},
@@ -81,11 +83,45 @@ impl Term {
         pb
     }
 
+    /// like `progress_bar`, named after the given peer: to be drawn with the
+    /// progress bars of the other peers (see `indicatif::MultiProgress`)
+    pub fn peer_progress_bar(&self, peer: &str, count: u64) -> indicatif::ProgressBar {
+        let pb = indicatif::ProgressBar::new(count);
+        pb.enable_steady_tick(100);
+        pb.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(&format!(
+                    "{{spinner:.green}} [{{elapsed_precise}}] [{{bar:40.cyan/blue}}] {{pos}}/{{len}} {} {{msg}}",
+                    peer.replace('{', "{{").replace('}', "}}")
+                ))
+                .progress_chars("#>-"),
+        );
+        pb
+    }
+
     pub fn prompt(&mut self, prompt: &str) -> io::Result<String> {
         dialoguer::Input::new().with_prompt(prompt).interact()
     }
 
-    pub fn password(&mut self, prompt: &str) -> io::Result<String> {
+    /// like `prompt`, but an empty answer (or the end of the input) is
+    /// accepted and returned as `None`
+    pub fn prompt_optional(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let line: String = dialoguer::Input::new()
+            .with_prompt(prompt)
+            .default("".to_owned())
+            .interact()?;
+        if line.trim().is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(line))
+        }
+    }
+
+    pub fn clear_screen(&mut self) -> io::Result<()> {
+        self.term.clear_screen()
+    }
+
+    pub fn password(&mut self, prompt: &str) -> io::Result<Secret<String>> {
         #[cfg(windows)]
         {
             // TODO: there seems to be an issue with rust crate: console
@@ -97,7 +133,7 @@ impl Term {
                 .interact()?;
             self.term.move_cursor_up(1)?;
             self.term.clear_line()?;
-            Ok(line)
+            Ok(Secret::new(line))
         }
         #[cfg(not(windows))]
         {
@@ -105,6 +141,7 @@ impl Term {
                 .with_prompt(prompt)
                 .allow_empty_password(true)
                 .interact()
+                .map(Secret::new)
         }
     }
 
@@ -113,26 +150,30 @@ impl Term {
         prompt: &str,
         confirmation: &str,
         mismatch_err: &str,
-    ) -> io::Result<String> {
+    ) -> io::Result<Secret<String>> {
         #[cfg(windows)]
         {
             loop {
                 // TODO: there seems to be an issue with rust crate: console
                 //       the password read line is not working or not returning
                 //       at all on windows 10 's `cmd` or `PowerShell`
-                let line = dialoguer::Input::new()
-                    .with_prompt(prompt)
-                    .default("".to_owned())
-                    .interact()?;
+                let line = Secret::new(
+                    dialoguer::Input::new()
+                        .with_prompt(prompt)
+                        .default("".to_owned())
+                        .interact()?,
+                );
                 self.term.move_cursor_up(1)?;
                 self.term.clear_line()?;
-                let line2 = dialoguer::Input::new()
-                    .with_prompt(confirmation)
-                    .default("".to_owned())
-                    .interact()?;
+                let line2 = Secret::new(
+                    dialoguer::Input::new()
+                        .with_prompt(confirmation)
+                        .default("".to_owned())
+                        .interact()?,
+                );
                 self.term.move_cursor_up(1)?;
                 self.term.clear_line()?;
-                if line == line2 {
+                if *line == *line2 {
                     return Ok(line);
                 }
                 self.error(mismatch_err)?;
@@ -145,6 +186,7 @@ impl Term {
                 .allow_empty_password(true)
                 .with_confirmation(confirmation, mismatch_err)
                 .interact()
+                .map(Secret::new)
         }
     }
 
@@ -0,0 +1,132 @@
+//! record of the addresses handed out by `wallet address --next` and of the
+//! change addresses of the transactions built by the wallet commands
+//!
+//! The pool is kept next to the wallet's configuration so the addresses
+//! given to payers (or used as change) are never handed out twice, even
+//! before they appear in the wallet's log, and so we can tell which of them
+//! have been used already.
+//!
+//! The pool is to be loaded, updated and saved with the wallet's log lock
+//! held (see `utils::read_wallet_log_locked`).
+
+use super::config;
+use super::error::{Error, Result};
+use super::state::{log, lookup};
+use super::Wallet;
+
+use cardano::{address::ExtendedAddr, coin::Coin};
+use serde_yaml;
+use std::{collections::BTreeMap, fs, io::ErrorKind};
+use storage_units::utils::tmpfile::TmpFile;
+
+static WALLET_ADDRESS_POOL_FILE: &'static str = "addresses.yml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedAddress {
+    pub address: ExtendedAddr,
+    pub addressing: lookup::Address,
+}
+impl IssuedAddress {
+    /// tell if the address was issued as a change address (internal
+    /// address of a BIP44 wallet)
+    pub fn is_change(&self) -> bool {
+        match self.addressing {
+            lookup::Address::Bip44(ref addressing) => addressing.change == 1,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressPool {
+    /// the addresses, in the order they were issued
+    pub issued: Vec<IssuedAddress>,
+}
+impl AddressPool {
+    /// load the address pool of the given wallet (empty if no address
+    /// has been issued yet)
+    pub fn load(wallet: &Wallet) -> Result<Self> {
+        let dir = config::directory(&wallet.root_dir, &wallet.name.as_dirname());
+        let path = dir.join(WALLET_ADDRESS_POOL_FILE);
+        let mut file = match fs::File::open(&path) {
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+            Ok(file) => file,
+        };
+        serde_yaml::from_reader(&mut file).map_err(|e| Error::ConfigReadFailed(path, e))
+    }
+
+    pub fn save(&self, wallet: &Wallet) -> Result<()> {
+        let dir = config::directory(&wallet.root_dir, &wallet.name.as_dirname());
+        let mut tmpfile = TmpFile::create(dir.clone())?;
+        serde_yaml::to_writer(&mut tmpfile, self)
+            .map_err(|e| Error::ConfigWriteFailed(dir.clone(), e))?;
+        tmpfile.render_permanent(&dir.join(WALLET_ADDRESS_POOL_FILE))?;
+        Ok(())
+    }
+
+    pub fn issue(&mut self, address: ExtendedAddr, addressing: lookup::Address) {
+        self.issued.push(IssuedAddress {
+            address: address,
+            addressing: addressing,
+        })
+    }
+
+    /// the index following the last address of the given chain (0 for the
+    /// external addresses, 1 for the internal ones) of the first BIP44
+    /// account, either seen in the wallet's log or issued already
+    pub fn next_bip44_index(&self, entries: &[log::Log<lookup::Address>], change: u32) -> u32 {
+        let issued = self
+            .issued
+            .iter()
+            .filter_map(|issued| bip44_index(&issued.addressing, change))
+            .map(|index| index + 1)
+            .max()
+            .unwrap_or(0);
+        ::std::cmp::max(issued, next_logged_bip44_index(entries, change))
+    }
+}
+
+/// the index of the given address if it is in the given chain of the
+/// first BIP44 account
+fn bip44_index(addressing: &lookup::Address, change: u32) -> Option<u32> {
+    match addressing {
+        lookup::Address::Bip44(addressing)
+            if addressing.account.get_account_number() == 0 && addressing.change == change =>
+        {
+            Some(addressing.index.get_scheme_value())
+        }
+        _ => None,
+    }
+}
+
+/// the index following the last address of the given chain of the first
+/// BIP44 account seen in the given entries of the wallet's log
+pub fn next_logged_bip44_index(entries: &[log::Log<lookup::Address>], change: u32) -> u32 {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            log::Log::Checkpoint(_) => None,
+            log::Log::ReceivedFund(_, utxo) => bip44_index(&utxo.credited_addressing, change),
+            log::Log::SpentFund(_, utxo) => bip44_index(&utxo.credited_addressing, change),
+        })
+        .map(|index| index + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// total of the funds received by every address of the wallet, as recorded
+/// in the given entries of the wallet's log (addresses are given in base58).
+pub fn received_totals(entries: &[log::Log<lookup::Address>]) -> Result<BTreeMap<String, Coin>> {
+    let mut totals = BTreeMap::new();
+    for entry in entries {
+        if let log::Log::ReceivedFund(_, utxo) = entry {
+            let total = totals
+                .entry(utxo.credited_address.to_string())
+                .or_insert(Coin::zero());
+            *total = (*total + utxo.credited_value)?;
+        }
+    }
+
+    Ok(totals)
+}
@@ -1,23 +1,32 @@
-use super::config::{encrypt_primary_key, Config, HDWalletModel};
+use super::address_pool::{self, AddressPool};
+use super::config::{decrypt_primary_key, encrypt_primary_key, Config, HDWalletModel};
 use super::error::{Error, Result};
-use super::state::lookup;
+use super::shamir;
+use super::state::{self, lookup};
 use super::utils::*;
-use super::{Wallet, WalletName, Wallets};
+use super::{daedalus_keyfile, entropy, EntropySource, Wallet, WalletName, Wallets};
 
 use cardano::{
+    address::ExtendedAddr,
     bip::bip39,
+    coin::Coin,
     hdwallet::{self, DerivationScheme},
-    wallet,
+    paperwallet, wallet,
 };
 use rand::random;
-use std::{io::Write, path::PathBuf};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
 
 use utils::{
     prompt,
+    secret::{xprv_normalize_bytes, Secret},
     term::{style::Style, Term},
 };
 
 use blockchain::{Blockchain, BlockchainName};
+use transaction;
 
 pub fn list(term: &mut Term, root_dir: PathBuf, detailed: bool) -> Result<()> {
     let wallets = Wallets::load(root_dir.clone())?;
@@ -59,6 +68,8 @@ pub fn new<D>(
     wallet_scheme: HDWalletModel,
     derivation_scheme: DerivationScheme,
     mnemonic_size: bip39::Type,
+    entropy_source: EntropySource,
+    mix_system_entropy: bool,
     languages: Vec<D>,
 ) -> Result<()>
 where
@@ -72,7 +83,7 @@ where
 
     // 1. generate the mnemonics
 
-    let entropy = bip39::Entropy::generate(mnemonic_size, random);
+    let entropy = input_entropy(term, entropy_source, mix_system_entropy, mnemonic_size)?;
     // 2. perform the seed generation from the entropy
 
     term.info("You can add a recovery wallet password. You can set no password, however you won't benefit from plausible deniability\n").unwrap();
@@ -83,7 +94,7 @@ where
             "password mismatch ",
         )
         .unwrap();
-    let mut seed = [0; hdwallet::XPRV_SIZE];
+    let mut seed = Secret::new(vec![0; hdwallet::XPRV_SIZE]);
     wallet::keygen::generate_seed(&entropy, recovery_password.as_bytes(), &mut seed);
 
     term.info("Please, note carefully the following mnemonic words. They will be needed to recover your wallet.\n").unwrap();
@@ -95,7 +106,7 @@ where
 
     // 3. normalize the seed to make it a valid private key
 
-    let xprv = hdwallet::XPrv::normalize_bytes(seed);
+    let xprv = xprv_normalize_bytes(&seed);
 
     // create the root public key
     let public_key = match wallet_scheme {
@@ -129,6 +140,54 @@ where
     Ok(())
 }
 
+/// collect the entropy of the mnemonics from the given source
+fn input_entropy(
+    term: &mut Term,
+    source: EntropySource,
+    mix_system_entropy: bool,
+    mnemonic_size: bip39::Type,
+) -> Result<bip39::Entropy> {
+    let mut bytes = match source {
+        EntropySource::System => return Ok(bip39::Entropy::generate(mnemonic_size, random)),
+        EntropySource::Dice => {
+            let needed = entropy::dice_rolls_needed(mnemonic_size);
+            let mut rolls = String::new();
+            while rolls.len() < needed {
+                let line = term
+                    .prompt(&format!("dice rolls [{}/{}]", rolls.len(), needed))
+                    .unwrap();
+                match entropy::parse_dice_rolls(&line) {
+                    Ok(line_rolls) => rolls.push_str(&line_rolls),
+                    Err(err) => term.error(&format!("{}, line ignored\n", err)).unwrap(),
+                }
+            }
+            entropy::from_dice_rolls(&rolls, mnemonic_size)?
+        }
+        EntropySource::Hex => {
+            let digits = term
+                .prompt(&format!(
+                    "entropy ({} hexadecimal digits)",
+                    entropy::entropy_size(mnemonic_size) * 2
+                ))
+                .unwrap();
+            entropy::from_hex(&digits, mnemonic_size)?
+        }
+        EntropySource::Stdin => {
+            let mut data = Vec::new();
+            ::std::io::stdin().read_to_end(&mut data)?;
+            entropy::from_bytes(&data, mnemonic_size)?
+        }
+    };
+
+    if mix_system_entropy {
+        let random_bytes: Vec<u8> = (0..bytes.len()).map(|_| random()).collect();
+        entropy::mix(&mut bytes, &random_bytes);
+    }
+
+    // the entropy has the size of the mnemonics
+    Ok(bip39::Entropy::from_slice(&bytes).unwrap())
+}
+
 pub fn recover<D>(
     term: &mut Term,
     root_dir: PathBuf,
@@ -138,6 +197,11 @@ pub fn recover<D>(
     mnemonic_size: bip39::Type,
     interactive: bool,
     daedalus_seed: bool,
+    paper: bool,
+    from_shares: bool,
+    repair: bool,
+    address: Option<ExtendedAddr>,
+    blockchain: Option<BlockchainName>,
     language: D,
 ) -> Result<()>
 where
@@ -149,48 +213,715 @@ where
         hdwallet_model: wallet_scheme,
     };
 
+    // 1. retrieve the root private key
+    let xprv = if from_shares {
+        input_shares(term, derivation_scheme, daedalus_seed, &language)?
+    } else if repair {
+        repair_mnemonics(
+            term,
+            &root_dir,
+            wallet_scheme,
+            derivation_scheme,
+            mnemonic_size,
+            daedalus_seed,
+            address,
+            blockchain,
+            &language,
+        )?
+    } else {
+        input_mnemonics(
+            term,
+            derivation_scheme,
+            mnemonic_size,
+            interactive,
+            daedalus_seed,
+            paper,
+            &language,
+        )?
+    };
+
+    // create the root public key
+    let public_key = match wallet_scheme {
+        HDWalletModel::BIP44 => None,
+        HDWalletModel::RandomIndex2Levels => Some(xprv.public()),
+    };
+
+    // 2. encrypt the private key
+    term.info("Set a wallet password. This is for local usage only, allows you to protect your cached private key and prevent from creating non desired transactions.\n").unwrap();
+    let password = term
+        .new_password(
+            "spending password",
+            "confirm spending password",
+            "password mismatch",
+        )
+        .unwrap();
+    let encrypted_xprv = encrypt_primary_key(password.as_bytes(), &xprv);
+
+    // 3. create the wallet
+    let wallet = Wallet::new(root_dir, name, config, encrypted_xprv, public_key);
+
+    // 4. save the wallet
+    wallet.save()?;
+
+    term.success(&format!(
+        "wallet `{}' successfully recovered.\n",
+        &wallet.name
+    ))
+    .unwrap();
+
+    Ok(())
+}
+
+/// retrieve the root private key from the mnemonic words
+fn input_mnemonics<D>(
+    term: &mut Term,
+    derivation_scheme: DerivationScheme,
+    mnemonic_size: bip39::Type,
+    interactive: bool,
+    daedalus_seed: bool,
+    paper: bool,
+    language: &D,
+) -> Result<hdwallet::XPrv>
+where
+    D: bip39::dictionary::Language,
+{
     // 1. generate the mnemonics
     term.info("enter your mnemonics\n").unwrap();
 
     let (string, _, entropy) = if interactive {
-        prompt::mnemonics::interactive_input_words(term, &language, mnemonic_size)
+        prompt::mnemonics::interactive_input_words(term, language, mnemonic_size)
     } else {
-        prompt::mnemonics::input_mnemonic_phrase(term, &language, mnemonic_size)
+        prompt::mnemonics::input_mnemonic_phrase(term, language, mnemonic_size)
+    };
+
+    // 2. unscramble the mnemonics of a paper wallet
+    let entropy = if paper {
+        let words: Vec<&str> = string.split_whitespace().collect();
+        term.info(&format!(
+            "paper wallet checksum: {}\n",
+            style!(paper_wallet_checksum(&words.join(" ")))
+        ))
+        .unwrap();
+        term.info("Enter the paper wallet passphrase.\n").unwrap();
+        let passphrase = term.password("paper wallet passphrase: ").unwrap();
+        let bytes = paperwallet::unscramble(passphrase.as_bytes(), &entropy);
+        bip39::Entropy::from_slice(&bytes).map_err(Error::CannotUnscramblePaperWallet)?
+    } else {
+        entropy
     };
 
     // 3. perform the seed generation from the entropy
-    let xprv = if daedalus_seed {
-        match wallet::rindex::RootKey::from_daedalus_mnemonics(
-            derivation_scheme,
-            &language,
-            &string,
-        ) {
-            Ok(root_key) => (*root_key).clone(),
-            Err(e) => {
-                return Err(Error::CannotRecoverFromDaedalusMnemonics(e));
-            }
-        }
+    let recovery_password = input_recovery_password(term, daedalus_seed);
+    mnemonics_root_key(
+        derivation_scheme,
+        daedalus_seed,
+        language,
+        &string,
+        &entropy,
+        &recovery_password,
+    )
+}
+
+/// ask the recovery password of the mnemonics (the mnemonics of Daedalus
+/// have none)
+fn input_recovery_password(term: &mut Term, daedalus_seed: bool) -> Secret<String> {
+    if daedalus_seed {
+        Secret::new(String::new())
     } else {
         term.info(
             "Enter the wallet recovery password (if the password is wrong, you won't know).\n",
         )
         .unwrap();
-        let recovery_password = term.password("recovery password: ").unwrap();
+        term.password("recovery password: ").unwrap()
+    }
+}
 
-        let mut seed = [0; hdwallet::XPRV_SIZE];
-        wallet::keygen::generate_seed(&entropy, recovery_password.as_bytes(), &mut seed);
+/// compute the root private key of the given mnemonics
+fn mnemonics_root_key<D>(
+    derivation_scheme: DerivationScheme,
+    daedalus_seed: bool,
+    language: &D,
+    string: &bip39::MnemonicString,
+    entropy: &bip39::Entropy,
+    recovery_password: &str,
+) -> Result<hdwallet::XPrv>
+where
+    D: bip39::dictionary::Language,
+{
+    if daedalus_seed {
+        match wallet::rindex::RootKey::from_daedalus_mnemonics(derivation_scheme, language, string)
+        {
+            Ok(root_key) => Ok((*root_key).clone()),
+            Err(e) => Err(Error::CannotRecoverFromDaedalusMnemonics(e)),
+        }
+    } else {
+        let mut seed = Secret::new(vec![0; hdwallet::XPRV_SIZE]);
+        wallet::keygen::generate_seed(entropy, recovery_password.as_bytes(), &mut seed);
 
         // normalize the seed to make it a valid private key
-        hdwallet::XPrv::normalize_bytes(seed)
+        Ok(xprv_normalize_bytes(&seed))
+    }
+}
+
+/// retrieve the root private key from mnemonics with one wrong, swapped or
+/// missing word: the valid candidates are listed with their first address
+/// for the user to pick the right one.
+///
+/// The candidates are narrowed down cheaply first: if an address of the
+/// wallet is known, only the candidates owning it are kept; with a blockchain,
+/// only the candidates whose first address received funds (any address of
+/// the wallet for random index wallets) are kept.
+fn repair_mnemonics<D>(
+    term: &mut Term,
+    root_dir: &PathBuf,
+    wallet_scheme: HDWalletModel,
+    derivation_scheme: DerivationScheme,
+    mnemonic_size: bip39::Type,
+    daedalus_seed: bool,
+    address: Option<ExtendedAddr>,
+    blockchain: Option<BlockchainName>,
+    language: &D,
+) -> Result<hdwallet::XPrv>
+where
+    D: bip39::dictionary::Language,
+{
+    use cardano::{bip::bip44, config::ProtocolMagic, wallet::rindex};
+
+    term.info("enter your mnemonics\n").unwrap();
+    let candidates =
+        prompt::mnemonics::input_mnemonic_phrase_to_repair(term, language, mnemonic_size);
+    if candidates.is_empty() {
+        return Err(Error::CannotRepairMnemonics);
+    }
+
+    let recovery_password = input_recovery_password(term, daedalus_seed);
+    let xprvs = candidates
+        .iter()
+        .map(|(string, _, entropy)| {
+            mnemonics_root_key(
+                derivation_scheme,
+                daedalus_seed,
+                language,
+                string,
+                entropy,
+                &recovery_password,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let blockchain = match blockchain {
+        None => None,
+        Some(name) => Some(Blockchain::load(root_dir.clone(), name)?),
+    };
+    let network_magic = match &blockchain {
+        None => ProtocolMagic::default().into(),
+        Some(blockchain) => blockchain.config.protocol_magic.into(),
     };
 
-    // create the root public key
-    let public_key = match wallet_scheme {
-        HDWalletModel::BIP44 => None,
-        HDWalletModel::RandomIndex2Levels => Some(xprv.public()),
+    let mut shown: Vec<usize> = (0..candidates.len()).collect();
+    let mut counts = None;
+    let addresses = match wallet_scheme {
+        HDWalletModel::BIP44 => {
+            let addressing = bip44::Account::new(0)?.external()?.index(0)?;
+            let mut lookups: Vec<_> = xprvs
+                .iter()
+                .map(|xprv| {
+                    let wallet = bip44::Wallet::from_root_key(xprv.clone(), derivation_scheme);
+                    lookup::sequentialindex::SequentialBip44Lookup::new(wallet, network_magic)
+                })
+                .collect();
+            if let Some(address) = &address {
+                shown.retain(|index| recognizes_address(&mut lookups[*index], address));
+            }
+            let addresses: Vec<_> = lookups
+                .iter()
+                .map(|lookup| Ok(Some(lookup.get_address(&addressing)?)))
+                .collect::<Result<_>>()?;
+            if let Some(blockchain) = &blockchain {
+                let first_addresses: Vec<_> = shown
+                    .iter()
+                    .filter_map(|index| addresses[*index].clone())
+                    .collect();
+                counts = Some(count_outputs_to_addresses(
+                    term,
+                    blockchain,
+                    &first_addresses,
+                ));
+            }
+            addresses
+        }
+        HDWalletModel::RandomIndex2Levels => {
+            // the addresses of random index wallets have no order
+            let mut lookups: Vec<_> = xprvs
+                .iter()
+                .map(|xprv| {
+                    let root_key = rindex::RootKey::new(xprv.clone(), derivation_scheme);
+                    let wallet = rindex::Wallet::from_root_key(derivation_scheme, root_key);
+                    lookup::randomindex::RandomIndexLookup::from_wallet(wallet, network_magic)
+                })
+                .collect();
+            if let Some(address) = &address {
+                shown.retain(|index| recognizes_address(&mut lookups[*index], address));
+            }
+            if let Some(blockchain) = &blockchain {
+                let mut lookups: Vec<_> = lookups
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| shown.contains(index))
+                    .map(|(_, lookup)| lookup)
+                    .collect();
+                counts = Some(count_recognized_outputs(term, blockchain, &mut lookups));
+            }
+            vec![None; xprvs.len()]
+        }
+    };
+
+    if shown.is_empty() {
+        term.error("None of the candidates owns the given address\n")
+            .unwrap();
+        return Err(Error::CannotRepairMnemonics);
+    }
+    // the counts are given in the order of the shown candidates
+    let counts: Option<Vec<(usize, usize)>> =
+        counts.map(|counts| shown.iter().cloned().zip(counts.into_iter()).collect());
+
+    // only keep the candidates with on-chain activity, if any
+    if let Some(counts) = &counts {
+        if counts.iter().any(|(_, count)| *count > 0) {
+            shown.retain(|index| counts.iter().any(|(i, count)| i == index && *count > 0));
+        } else {
+            term.warn("None of the candidates has received funds in the blockchain\n")
+                .unwrap();
+        }
+    }
+
+    term.info(&format!("{} candidate(s) found:\n", shown.len()))
+        .unwrap();
+    for (number, index) in shown.iter().enumerate() {
+        let words: Vec<&str> = candidates[*index].0.split_whitespace().collect();
+        writeln!(term, "{}. {}", style!(number + 1).yellow(), words.join(" ")).unwrap();
+        if let Some(address) = &addresses[*index] {
+            writeln!(term, "   first address: {}", style!(address)).unwrap();
+        }
+        if let Some(counts) = &counts {
+            if let Some((_, count)) = counts.iter().find(|(i, _)| i == index) {
+                writeln!(term, "   outputs found: {}", style!(*count)).unwrap();
+            }
+        }
+    }
+
+    loop {
+        let choice = term
+            .prompt(&format!("select the mnemonics [1-{}]", shown.len()))
+            .unwrap();
+        match choice.trim().parse::<usize>() {
+            Ok(number) if number >= 1 && number <= shown.len() => {
+                return Ok(xprvs[shown[number - 1]].clone());
+            }
+            _ => term.error("invalid choice\n").unwrap(),
+        }
+    }
+}
+
+/// retrieve the root private key from the shares created by `wallet split`,
+/// checking every share as soon as it is entered
+///
+/// An empty line (or the end of the input) stops the input. The shares give
+/// the entropy of the mnemonics, the root key is then computed as usual and
+/// checked against the wallet fingerprint printed by `wallet split`.
+fn input_shares<D>(
+    term: &mut Term,
+    derivation_scheme: DerivationScheme,
+    daedalus_seed: bool,
+    language: &D,
+) -> Result<hdwallet::XPrv>
+where
+    D: bip39::dictionary::Language,
+{
+    let mut shares: Vec<shamir::Share> = Vec::new();
+    let mut threshold = None;
+
+    while Some(shares.len()) != threshold.map(|threshold: u8| threshold as usize) {
+        let prompt = match threshold {
+            None => "share [1/?]".to_owned(),
+            Some(threshold) => format!("share [{}/{}]", shares.len() + 1, threshold),
+        };
+        let line = match term.prompt_optional(&prompt)? {
+            None => {
+                return Err(Error::ShamirError(shamir::Error::NotEnoughShares {
+                    threshold: threshold.unwrap_or(1),
+                    shares: shares.len(),
+                }))
+            }
+            Some(line) => line,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let share = match shamir::Share::from_words(language, &words)
+            .and_then(|share| shamir::check_compatible(&shares, &share).map(|()| share))
+        {
+            Ok(share) => share,
+            Err(err) => {
+                term.error(&format!("{}\n", err)).unwrap();
+                continue;
+            }
+        };
+        term.success(&format!(
+            "share #{} accepted ({} shares needed)\n",
+            share.index(),
+            share.threshold()
+        ))
+        .unwrap();
+        threshold = Some(share.threshold());
+        shares.push(share);
+    }
+
+    let secret = Secret::new(shamir::combine(&shares)?);
+    let entropy = bip39::Entropy::from_slice(&secret)
+        .map_err(Error::CannotRecoverFromSharesInvalidEntropy)?;
+    let string = entropy.to_mnemonics().to_string(language);
+
+    let recovery_password = input_recovery_password(term, daedalus_seed);
+    let xprv = mnemonics_root_key(
+        derivation_scheme,
+        daedalus_seed,
+        language,
+        &string,
+        &entropy,
+        &recovery_password,
+    )?;
+
+    writeln!(
+        term,
+        "recovered wallet fingerprint: {}",
+        style!(wallet_fingerprint(&xprv.public())).cyan()
+    )
+    .unwrap();
+    let confirmation = ::dialoguer::Confirmation::new()
+        .with_text("Is it the fingerprint written with the shares?")
+        .default(false)
+        .interact()?;
+    if !confirmation {
+        return Err(Error::CannotRecoverFromSharesFingerprintMismatch);
+    }
+    Ok(xprv)
+}
+
+/// print the root public key, the fingerprint and the first addresses of the
+/// wallet of the given mnemonics, without creating the wallet.
+pub fn inspect_mnemonic<D>(
+    term: &mut Term,
+    wallet_scheme: HDWalletModel,
+    derivation_scheme: DerivationScheme,
+    mnemonic_size: bip39::Type,
+    interactive: bool,
+    daedalus_seed: bool,
+    protocol_magic: Option<u32>,
+    count: u32,
+    language: D,
+) -> Result<()>
+where
+    D: bip39::dictionary::Language,
+{
+    use cardano::{bip::bip44, config::ProtocolMagic, wallet::rindex};
+
+    let xprv = input_mnemonics(
+        term,
+        derivation_scheme,
+        mnemonic_size,
+        interactive,
+        daedalus_seed,
+        false,
+        &language,
+    )?;
+    let network_magic = protocol_magic
+        .map(ProtocolMagic::from)
+        .unwrap_or_default()
+        .into();
+
+    let root_public_key = xprv.public();
+    writeln!(term, "root public key: {}", style!(root_public_key)).unwrap();
+    writeln!(
+        term,
+        "fingerprint:     {}",
+        style!(wallet_fingerprint(&root_public_key)).cyan()
+    )
+    .unwrap();
+
+    let addresses: Vec<(String, ExtendedAddr)> = match wallet_scheme {
+        HDWalletModel::BIP44 => {
+            let wallet = bip44::Wallet::from_root_key(xprv, derivation_scheme);
+            let lookup_struct =
+                lookup::sequentialindex::SequentialBip44Lookup::new(wallet, network_magic);
+            let account = bip44::Account::new(0)?.external()?;
+            (0..count)
+                .map(|index| {
+                    let addressing = account.index(index)?;
+                    let path = addressing.to_string();
+                    Ok((path, lookup_struct.get_address(&addressing)?))
+                })
+                .collect::<Result<_>>()?
+        }
+        HDWalletModel::RandomIndex2Levels => {
+            term.info("random index wallets have no address order, these addresses may not be the ones in use\n").unwrap();
+            let root_key = rindex::RootKey::new(xprv, derivation_scheme);
+            let wallet = rindex::Wallet::from_root_key(derivation_scheme, root_key);
+            let lookup_struct =
+                lookup::randomindex::RandomIndexLookup::from_wallet(wallet, network_magic);
+            (0..count)
+                .map(|index| {
+                    let addressing = rindex::Addressing::new(0x8000_0000, 0x8000_0000 | index);
+                    let path = addressing.to_string();
+                    Ok((path, lookup_struct.get_address(&addressing)?))
+                })
+                .collect::<Result<_>>()?
+        }
+    };
+    for (path, address) in addresses {
+        writeln!(term, "{}\t{}", path, style!(address)).unwrap();
+    }
+
+    Ok(())
+}
+
+/// size of the random initialization vector prepended to the scrambled
+/// entropy of a paper wallet
+const PAPER_WALLET_IV_SIZE: usize = 8;
+
+/// generate a new wallet and print it on a paper sheet (the mnemonic words,
+/// the first external address and a checksum line).
+///
+/// Nothing is saved locally: use `wallet recover` (with `--paper` if the
+/// mnemonics have been scrambled with a passphrase) to use the funds.
+pub fn paper<D>(
+    term: &mut Term,
+    name: WalletName,
+    derivation_scheme: DerivationScheme,
+    mnemonic_size: bip39::Type,
+    protocol_magic: Option<u32>,
+    output: Option<PathBuf>,
+    language: D,
+) -> Result<()>
+where
+    D: bip39::dictionary::Language,
+{
+    use cardano::{bip::bip44, config::ProtocolMagic};
+
+    // 1. generate the mnemonics
+    let entropy = bip39::Entropy::generate(mnemonic_size, random);
+
+    // 2. perform the seed generation from the entropy
+    term.info("You can add a recovery wallet password. You can set no password, however you won't benefit from plausible deniability\n").unwrap();
+    let recovery_password = term
+        .new_password(
+            "recovery password",
+            "confirm password",
+            "password mismatch ",
+        )
+        .unwrap();
+    let mut seed = Secret::new(vec![0; hdwallet::XPRV_SIZE]);
+    wallet::keygen::generate_seed(&entropy, recovery_password.as_bytes(), &mut seed);
+    let xprv = xprv_normalize_bytes(&seed);
+
+    // 3. compute the first external address
+    let protocol_magic = protocol_magic.map(ProtocolMagic::from).unwrap_or_default();
+    let bip44_wallet = bip44::Wallet::from_root_key(xprv, derivation_scheme);
+    let lookup_struct =
+        lookup::sequentialindex::SequentialBip44Lookup::new(bip44_wallet, protocol_magic.into());
+    let address = lookup_struct.get_address(&bip44::Account::new(0)?.external()?.index(0)?)?;
+
+    // 4. scramble the mnemonics with the paper wallet passphrase
+    term.info("You can scramble the mnemonic words with a passphrase, they will be useless without it. Set no passphrase to print them as is.\n").unwrap();
+    let passphrase = term
+        .new_password(
+            "paper wallet passphrase",
+            "confirm passphrase",
+            "passphrase mismatch",
+        )
+        .unwrap();
+    let printed = if passphrase.is_empty() {
+        entropy
+    } else {
+        let iv: [u8; PAPER_WALLET_IV_SIZE] = random();
+        let bytes = paperwallet::scramble(&iv, passphrase.as_bytes(), &entropy);
+        bip39::Entropy::from_slice(&bytes).map_err(Error::CannotScramblePaperWallet)?
+    };
+
+    // 5. render the sheet
+    let phrase = printed.to_mnemonics().to_string(&language);
+    let mut sheet = String::new();
+    sheet.push_str(&format!("Cardano paper wallet: {}\n\n", name));
+    for (index, word) in phrase.split_whitespace().enumerate() {
+        sheet.push_str(&format!("{:>2}. {}\n", index + 1, word));
+    }
+    sheet.push_str(&format!(
+        "\nscrambled: {}\n",
+        if passphrase.is_empty() { "no" } else { "yes" }
+    ));
+    sheet.push_str(&format!("address:   {}\n", address));
+    sheet.push_str(&format!(
+        "checksum:  {}\n",
+        paper_wallet_checksum(&phrase.split_whitespace().collect::<Vec<_>>().join(" "))
+    ));
+
+    match output {
+        None => term.simply(&sheet).unwrap(),
+        Some(path) => {
+            write_private_file(&path, sheet.as_bytes())?;
+            term.success(&format!("paper wallet written to `{}'.\n", path.display()))
+                .unwrap();
+        }
+    }
+
+    Ok(())
+}
+
+/// write the given content to a file only the user can read (if the
+/// platform allows it)
+fn write_private_file(path: &::std::path::Path, content: &[u8]) -> Result<()> {
+    let mut options = ::std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // the mode is only set when the file is created
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(::std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content)?;
+    Ok(())
+}
+
+/// split the entropy of the wallet's mnemonics in `shares` mnemonic shares,
+/// any `threshold` of them allowing to recover the wallet with
+/// `wallet recover --from-shares`.
+///
+/// The wallet only holds its root key: the mnemonics are asked, and checked
+/// against it.
+pub fn split<D>(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    threshold: u8,
+    shares: u8,
+    mnemonic_size: bip39::Type,
+    daedalus_seed: bool,
+    language: D,
+) -> Result<()>
+where
+    D: bip39::dictionary::Language,
+{
+    let wallet = Wallet::load(root_dir, name)?;
+
+    term.info("Enter the wallet password.\n").unwrap();
+    let password = term.password("wallet password: ").unwrap();
+    let root_public_key = decrypt_primary_key(password.as_bytes(), &wallet.encrypted_key)?.public();
+
+    term.info("enter the mnemonics of the wallet\n").unwrap();
+    let (string, _, entropy) =
+        prompt::mnemonics::input_mnemonic_phrase(term, &language, mnemonic_size);
+    let recovery_password = input_recovery_password(term, daedalus_seed);
+    let xprv = mnemonics_root_key(
+        wallet.config.derivation_scheme,
+        daedalus_seed,
+        &language,
+        &string,
+        &entropy,
+        &recovery_password,
+    )?;
+    if xprv.public().as_ref() != root_public_key.as_ref() {
+        return Err(Error::CannotSplitMnemonicsMismatch);
+    }
+
+    let shares = shamir::split(&mut ::rand::thread_rng(), &entropy, threshold, shares)?;
+
+    let fingerprint = wallet_fingerprint(&root_public_key);
+    term.warn(&format!(
+        "Any {} of the following {} shares allow to recover the wallet, give each of them to a different person.\n",
+        threshold,
+        shares.len()
+    ))
+    .unwrap();
+    term.info(&format!(
+        "Write the wallet fingerprint `{}' with every share, it is checked when recovering the wallet.\n",
+        fingerprint
+    ))
+    .unwrap();
+    let count = shares.len();
+    for share in shares {
+        term.prompt_optional(&format!(
+            "press enter to display share #{}/{}",
+            share.index(),
+            count
+        ))?;
+        term.clear_screen()?;
+        term.info(&format!("share #{}:\n", share.index())).unwrap();
+        term.simply(&format!(
+            "{}\n",
+            share.to_words(&language).join(language.separator())
+        ))
+        .unwrap();
+        writeln!(term, "wallet fingerprint: {}", style!(&fingerprint).cyan()).unwrap();
+        term.prompt_optional("press enter once the share is written down")?;
+        term.clear_screen()?;
+    }
+
+    Ok(())
+}
+
+/// import the root key of a Daedalus wallet from Daedalus' `secret.key`
+/// user secret file, as a random index wallet.
+///
+/// If the file holds several wallet keys, `key_index` tells which one to
+/// import (in the order displayed by the error message listing them).
+pub fn import_daedalus_keyfile(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    keyfile: PathBuf,
+    key_index: Option<usize>,
+) -> Result<()> {
+    let keys = daedalus_keyfile::read_root_keys(&keyfile)?;
+    let key = match (key_index, keys.len()) {
+        (_, 0) => return Err(Error::DaedalusKeyfileNoKey),
+        (None, 1) => &keys[0],
+        (None, count) => {
+            for (index, key) in keys.iter().enumerate() {
+                writeln!(
+                    term,
+                    "{}: fingerprint {}",
+                    style!(index).yellow(),
+                    style!(wallet_fingerprint(&key.root_public_key()))
+                )
+                .unwrap();
+            }
+            return Err(Error::DaedalusKeyfileAmbiguousKey(count));
+        }
+        (Some(index), count) => match keys.get(index) {
+            Some(key) => key,
+            None => return Err(Error::DaedalusKeyfileAmbiguousKey(count)),
+        },
+    };
+
+    term.info(
+        "Enter the spending password of the wallet in Daedalus (none if it had no password).\n",
+    )
+    .unwrap();
+    let daedalus_password = term.password("Daedalus spending password: ").unwrap();
+    let xprv = key.decrypt(&daedalus_password)?;
+
+    let config = Config {
+        attached_blockchain: None,
+        derivation_scheme: DerivationScheme::V1,
+        hdwallet_model: HDWalletModel::RandomIndex2Levels,
     };
+    let public_key = Some(xprv.public());
 
-    // 4. encrypt the private key
     term.info("Set a wallet password. This is for local usage only, allows you to protect your cached private key and prevent from creating non desired transactions.\n").unwrap();
     let password = term
         .new_password(
@@ -201,14 +932,11 @@ where
         .unwrap();
     let encrypted_xprv = encrypt_primary_key(password.as_bytes(), &xprv);
 
-    // 5. create the wallet
     let wallet = Wallet::new(root_dir, name, config, encrypted_xprv, public_key);
-
-    // 6. save the wallet
     wallet.save()?;
 
     term.success(&format!(
-        "wallet `{}' successfully recovered.\n",
+        "wallet `{}' successfully imported.\n",
         &wallet.name
     ))
     .unwrap();
@@ -379,6 +1107,152 @@ pub fn utxos(term: &mut Term, root_dir: PathBuf, name: WalletName) -> Result<()>
     Ok(())
 }
 
+/// report how every address of the wallet has been used, according to the
+/// wallet's log, flagging the practices hurting the wallet's privacy:
+///
+/// * addresses receiving funds more than once (especially change addresses);
+/// * UTxOs of different accounts spent in the same transaction. The log does
+///   not record the spending transactions, they are found in the blocks of
+///   the attached blockchain. Only BIP44 wallets have accounts.
+pub fn addresses_report(term: &mut Term, root_dir: PathBuf, name: WalletName) -> Result<()> {
+    use cardano::tx::TxoPointer;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    struct Usage {
+        address: ExtendedAddr,
+        addressing: lookup::Address,
+        received: usize,
+        spent: usize,
+        total: Coin,
+    }
+
+    let wallet = Wallet::load(root_dir.clone(), name)?;
+
+    let mut usages: BTreeMap<String, Usage> = BTreeMap::new();
+    // the UTxOs spent (and their account) by the block spending them
+    let mut spending_blocks: BTreeMap<String, (_, Vec<(TxoPointer, u32)>)> = BTreeMap::new();
+    for entry in read_wallet_log(&wallet)? {
+        let (received, utxo) = match entry {
+            state::log::Log::Checkpoint(_) => continue,
+            state::log::Log::ReceivedFund(_, utxo) => (true, utxo),
+            state::log::Log::SpentFund(ptr, utxo) => {
+                if let lookup::Address::Bip44(addressing) = &utxo.credited_addressing {
+                    spending_blocks
+                        .entry(ptr.latest_known_hash.to_string())
+                        .or_insert((ptr.latest_known_hash.clone(), Vec::new()))
+                        .1
+                        .push((utxo.extract_txin(), addressing.account.get_account_number()));
+                }
+                (false, utxo)
+            }
+        };
+
+        let usage = usages
+            .entry(utxo.credited_address.to_string())
+            .or_insert(Usage {
+                address: utxo.credited_address.clone(),
+                addressing: utxo.credited_addressing.clone(),
+                received: 0,
+                spent: 0,
+                total: Coin::zero(),
+            });
+        if received {
+            usage.received += 1;
+            usage.total = (usage.total + utxo.credited_value)?;
+        } else {
+            usage.spent += 1;
+        }
+    }
+
+    // the accounts of the UTxOs spent by every transaction
+    let mut spending_transactions = BTreeMap::new();
+    if !spending_blocks.is_empty() {
+        let blockchain = load_attached_blockchain(&root_dir, &wallet.config)?;
+        for (_, (hash, spent)) in spending_blocks {
+            let block = blockchain
+                .storage
+                .read_block(hash.as_hash_bytes())
+                .map_err(::blockchain::Error::from)?
+                .decode()
+                .map_err(::blockchain::Error::VerifyMalformedBlock)?;
+            let date = block.get_header().get_blockdate();
+            let txs = match block.get_transactions() {
+                None => continue,
+                Some(txs) => txs,
+            };
+            for txaux in txs.iter() {
+                for (txin, account) in spent.iter() {
+                    if txaux.tx.inputs.contains(txin) {
+                        spending_transactions
+                            .entry(txaux.tx.id())
+                            .or_insert((date, BTreeSet::new()))
+                            .1
+                            .insert(*account);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut reused = 0;
+    for usage in usages.values() {
+        write!(
+            term,
+            "{} {} received {} times, spent {} times, total {}",
+            style!(usage.address),
+            usage.addressing,
+            style!(usage.received),
+            style!(usage.spent),
+            style!(usage.total)
+        )?;
+        if usage.received > 1 {
+            reused += 1;
+            let is_change = match &usage.addressing {
+                lookup::Address::Bip44(addressing) => addressing.change == 1,
+                _ => false,
+            };
+            if is_change {
+                write!(term, " {}", style!("change reused").red())?;
+            } else {
+                write!(term, " {}", style!("reused").yellow())?;
+            }
+        }
+        writeln!(term)?;
+    }
+
+    let mut co_spent = 0;
+    for (txid, (date, accounts)) in spending_transactions {
+        if accounts.len() > 1 {
+            co_spent += 1;
+            let accounts: Vec<String> = accounts.iter().map(|a| a.to_string()).collect();
+            writeln!(
+                term,
+                "{} transaction {} (block {}): spent UTxOs of accounts {} together",
+                style!("co-spent").red(),
+                style!(txid),
+                style!(date),
+                accounts.join(", ")
+            )?;
+        }
+    }
+
+    writeln!(
+        term,
+        "{} addresses used, {} reused, {} transactions co-spending accounts",
+        style!(usages.len()),
+        style!(reused),
+        style!(co_spent)
+    )?;
+    if let HDWalletModel::RandomIndex2Levels = wallet.config.hdwallet_model {
+        writeln!(
+            term,
+            "random index wallets have no accounts, the UTxOs spent together are not reported"
+        )?;
+    }
+
+    Ok(())
+}
+
 pub fn sync(term: &mut Term, root_dir: PathBuf, name: WalletName) -> Result<()> {
     // 0. load the wallet
     let wallet = Wallet::load(root_dir.clone(), name)?;
@@ -440,12 +1314,12 @@ pub fn address(
                 account.external()?
             };
             let addressing = change.index(index)?;
-            lookup_struct.get_address(&addressing)
+            lookup_struct.get_address(&addressing)?
         }
         HDWalletModel::RandomIndex2Levels => {
             let lookup_struct = load_randomindex_lookup_structure(term, protocol_magic, &wallet);
             let addressing = ::cardano::wallet::rindex::Addressing::new(account, index);
-            lookup_struct.get_address(&addressing)
+            lookup_struct.get_address(&addressing)?
         }
     };
 
@@ -453,3 +1327,794 @@ pub fn address(
 
     Ok(())
 }
+
+/// send funds from the given wallet to the given address
+///
+/// This composes the `transaction` commands: a new staging transaction is
+/// created with the output and a fresh change address of the wallet, the
+/// inputs are selected from the wallet's UTxOs, then the transaction is
+/// finalized, signed and sent to the attached blockchain's peers.
+///
+/// The wallet is unlocked once, for both the change address and the
+/// signatures. The change address is only issued once the transaction is
+/// confirmed.
+pub fn send(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    address: ExtendedAddr,
+    amount: Coin,
+    dry_run: bool,
+) -> Result<()> {
+    use transaction::{
+        self, coin_control::InputFilter, core::StagingTransaction,
+        error::Error as TransactionError, input_selection::SelectionPolicy,
+    };
+
+    let wallet = Wallet::load(root_dir.clone(), name.clone())?;
+    let blockchain = load_attached_blockchain(&root_dir, &wallet.config)?;
+    let protocol_magic = blockchain.config.protocol_magic;
+
+    let lookup = WalletLookup::new(term, &wallet, protocol_magic.into());
+    let mut change_addresses = ChangeAddresses::new(&wallet, &lookup);
+    let change = change_addresses.derive(1)?.remove(0);
+
+    let id = {
+        let staging = StagingTransaction::new(root_dir.clone(), protocol_magic)
+            .map_err(TransactionError::CannotCreateNewTransaction)?;
+        *staging.id()
+    };
+    let id_str = id.to_string();
+
+    let prepared = transaction::commands::add_output(
+        term,
+        root_dir.clone(),
+        &id_str,
+        Some((address.clone(), amount)),
+    )
+    .and_then(|()| {
+        transaction::commands::add_change(term, root_dir.clone(), &id_str, change.clone(), None)
+    })
+    .and_then(|()| {
+        transaction::commands::input_select(
+            term,
+            root_dir.clone(),
+            &id_str,
+            vec![name],
+            SelectionPolicy::LargestFirst,
+            None,
+            InputFilter::default(),
+        )
+    })
+    .and_then(|()| transaction::commands::fee(root_dir.clone(), &id_str));
+    let fee = match prepared {
+        Ok(fee) => fee,
+        Err(err) => {
+            transaction::commands::destroy(term, root_dir.clone(), &id_str)?;
+            return Err(err.into());
+        }
+    };
+
+    writeln!(term, "sending {} to {}", style!(amount), style!(address))?;
+    writeln!(term, "change address {}", style!(change))?;
+    writeln!(term, "fee {}", style!(fee))?;
+
+    if !dry_run {
+        if !confirm()? {
+            transaction::commands::destroy(term, root_dir.clone(), &id_str)?;
+            return Ok(());
+        }
+    }
+    change_addresses.issue()?;
+
+    let txaux = {
+        let mut staging = StagingTransaction::read_from_file(root_dir.clone(), id)
+            .map_err(TransactionError::CannotLoadStagingTransaction)?;
+        staging
+            .finalize()
+            .map_err(TransactionError::CannotFinalize)?;
+        let txid = staging
+            .transaction()
+            .mk_txbuilder()
+            .map_err(TransactionError::CannotSignTransactionNotFinalized)?
+            .0
+            .make_tx()
+            .map_err(TransactionError::CannotSignTransactionInvalidTxAux)?
+            .id();
+
+        // the inputs were selected from the wallet's UTxOs
+        let state =
+            create_wallet_state_from_logs(&wallet, &root_dir, lookup::accum::Accum::default())?;
+        let addressings = staging
+            .transaction()
+            .inputs()
+            .iter()
+            .map(|input| {
+                let txin = input.extract_txin();
+                match state.utxos.get(&txin) {
+                    None => Err(TransactionError::CannotSignTransactionUnknownInput(txin).into()),
+                    Some(utxo) => Ok(utxo.credited_addressing.clone()),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for signature in lookup.sign_tx_inputs(protocol_magic, &txid, &addressings)? {
+            staging
+                .add_signature(signature)
+                .map_err(TransactionError::CannotSignTransactionCannotAddSignature)?;
+        }
+
+        let (finalized, _) = staging
+            .transaction()
+            .mk_finalized()
+            .map_err(TransactionError::CannotSendTransactionNotFinalized)?;
+        finalized
+            .make_txaux()
+            .map_err(TransactionError::CannotSendTransactionInvalidTxAux)?
+    };
+
+    if dry_run {
+        let mut serializer = ::cbor_event::se::Serializer::new_vec();
+        serializer
+            .serialize(&txaux)
+            .expect("a TxAux can always be serialized");
+        writeln!(term, "{}", ::base64::encode(&serializer.finalize()))?;
+        writeln!(
+            term,
+            "transaction not sent, it is still available in the staging transaction {}",
+            style!(id)
+        )?;
+        return Ok(());
+    }
+
+    transaction::commands::send(term, root_dir.clone(), &id_str, blockchain.name.clone())?;
+    transaction::commands::destroy(term, root_dir, &id_str)?;
+
+    writeln!(term, "{}", style!(txaux.tx.id()))?;
+
+    Ok(())
+}
+
+/// issue the next unused external address of the wallet, and record it in
+/// the wallet's address pool.
+///
+/// For BIP44 wallets, the address follows the last one either used (in the
+/// wallet's log) or issued already. No more than the gap limit of addresses
+/// may be unused (past the last used address), unless `force` is set: the
+/// wallet would not find the funds sent to the addresses past the gap limit.
+pub fn address_next(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    protocol_magic: Option<u32>,
+    force: bool,
+) -> Result<()> {
+    use super::state::lookup::sequentialindex::DEFAULT_GAP_LIMIT;
+    use cardano::{bip::bip44, wallet::rindex};
+
+    let wallet = Wallet::load(root_dir.clone(), name)?;
+    let network_magic = if let Some(protocol_magic) = protocol_magic {
+        cardano::config::ProtocolMagic::from(protocol_magic).into()
+    } else {
+        let blockchain = load_attached_blockchain(&root_dir, &wallet.config)?;
+        blockchain.config.protocol_magic.into()
+    };
+
+    // the password is asked before locking the wallet's log, the pool is
+    // updated with the lock held so no address is issued twice
+    let lookup = WalletLookup::new(term, &wallet, network_magic);
+    let log_lock = lock_wallet_log(&wallet);
+    let (entries, _log_lock) = read_wallet_log_locked(log_lock)?;
+    let mut pool = AddressPool::load(&wallet)?;
+
+    let (address, addressing) = match lookup {
+        WalletLookup::Bip44(lookup_struct) => {
+            // every address past the last one used is unused, whether
+            // it was issued or not
+            let next_index = pool.next_bip44_index(&entries, 0);
+            let unused = next_index - address_pool::next_logged_bip44_index(&entries, 0);
+            if unused >= *DEFAULT_GAP_LIMIT && !force {
+                return Err(Error::AddressPoolGapLimitReached(*DEFAULT_GAP_LIMIT));
+            }
+
+            let addressing = bip44::Account::new(0)?.external()?.index(next_index)?;
+            (lookup_struct.get_address(&addressing)?, addressing.into())
+        }
+        WalletLookup::RandomIndex(lookup_struct) => {
+            // Daedalus uses hardened indices for both levels
+            let addressing = rindex::Addressing::new(
+                0x8000_0000 | random::<u32>(),
+                0x8000_0000 | random::<u32>(),
+            );
+            (lookup_struct.get_address(&addressing)?, addressing.into())
+        }
+    };
+
+    pool.issue(address.clone(), addressing);
+    pool.save(&wallet)?;
+
+    writeln!(term, "{}", style!(address))?;
+
+    Ok(())
+}
+
+/// list the addresses issued with `wallet address --next`, along with the
+/// funds they received (according to the wallet's log)
+pub fn addresses(term: &mut Term, root_dir: PathBuf, name: WalletName) -> Result<()> {
+    let wallet = Wallet::load(root_dir, name)?;
+    let pool = AddressPool::load(&wallet)?;
+    let received = address_pool::received_totals(&read_wallet_log(&wallet)?)?;
+
+    for issued in pool.issued.iter().filter(|issued| !issued.is_change()) {
+        match received.get(&issued.address.to_string()) {
+            Some(total) => writeln!(
+                term,
+                "{} {} {} {}",
+                style!(issued.address),
+                issued.addressing,
+                style!("used").green(),
+                style!(total)
+            )?,
+            None => writeln!(
+                term,
+                "{} {} {}",
+                style!(issued.address),
+                issued.addressing,
+                style!("unused").yellow()
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+/// redeem the funds of the given redeem key (AVVM vending certificate) to
+/// a fresh address of the wallet
+///
+/// The funds are looked for in the genesis AVVM distribution and in the
+/// local copy of the blockchain.
+pub fn redeem(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    redeem_key: ::cardano::redeem::PrivateKey,
+) -> Result<()> {
+    use super::state::{lookup::singleaddress::SingleAddressLookup, ptr::StatePtr, state::State};
+    use cardano::tx::TxInWitness;
+    use transaction::{
+        core::{self, StagingTransaction},
+        error::Error as TransactionError,
+        redeem,
+    };
+
+    let wallet = Wallet::load(root_dir.clone(), name)?;
+    let blockchain = load_attached_blockchain(&root_dir, &wallet.config)?;
+    let protocol_magic = blockchain.config.protocol_magic;
+
+    let public_key = redeem_key.public();
+    let address = redeem::address(&public_key, protocol_magic);
+
+    let mut state = State::new(
+        StatePtr::new_before_genesis(blockchain.config.genesis.clone()),
+        SingleAddressLookup::new(address.clone()),
+    );
+    if let Some(utxo) = redeem::genesis_utxo(&blockchain.load_genesis_data()?, &public_key) {
+        state.utxos.insert(utxo.extract_txin(), utxo);
+    }
+    // the genesis UTxO may have been redeemed already
+    scan_blockchain_utxos(term, &blockchain, &mut state);
+    if state.utxos.is_empty() {
+        return Err(Error::CannotRedeemNoFunds(address));
+    }
+    let total = state.total()?;
+
+    let destination = wallet_next_change_address(term, &wallet, protocol_magic.into())?;
+
+    writeln!(
+        term,
+        "redeeming {} from {} to {}",
+        style!(total),
+        style!(address),
+        style!(destination)
+    )?;
+    if !confirm()? {
+        return Ok(());
+    }
+
+    let mut staging = StagingTransaction::new(root_dir.clone(), protocol_magic)
+        .map_err(TransactionError::CannotCreateNewTransaction)?;
+    for (_, utxo) in state.utxos.iter() {
+        staging
+            .add_input(core::Input {
+                transaction_id: utxo.transaction_id,
+                index_in_transaction: utxo.index_in_transaction,
+                expected_value: utxo.credited_value,
+            })
+            .map_err(TransactionError::CannotAddInput)?;
+    }
+    staging
+        .add_change(destination.into())
+        .map_err(TransactionError::CannotAddChange)?;
+    staging
+        .finalize()
+        .map_err(TransactionError::CannotFinalize)?;
+
+    let txid = staging
+        .transaction()
+        .mk_txbuilder()
+        .map_err(TransactionError::CannotSignTransactionNotFinalized)?
+        .0
+        .make_tx()
+        .map_err(TransactionError::CannotSignTransactionInvalidTxAux)?
+        .id();
+    for _ in 0..state.utxos.len() {
+        staging
+            .add_signature(TxInWitness::new_redeem_pk(
+                protocol_magic,
+                &redeem_key,
+                &txid,
+            ))
+            .map_err(TransactionError::CannotSignTransactionCannotAddSignature)?;
+    }
+
+    let id = *staging.id();
+    ::std::mem::drop(staging);
+    send_staging_transactions(term, &root_dir, &blockchain, vec![id])?;
+    writeln!(term, "{}", style!(txid))?;
+
+    Ok(())
+}
+
+/// find the wallet (and the derivation path) owning the given address
+///
+/// Only the wallets of the matching model are checked: random index
+/// addresses embed an encrypted derivation path, BIP44 addresses do not.
+/// The password of every checked wallet is asked.
+pub fn owns(term: &mut Term, root_dir: PathBuf, address: ExtendedAddr) -> Result<()> {
+    let network_magic = address.attributes.network_magic;
+    let is_rindex = address.attributes.derivation_path.is_some();
+
+    let mut found = false;
+    for (name, wallet) in Wallets::load(root_dir.clone())? {
+        let addressing = match wallet.config.hdwallet_model {
+            HDWalletModel::BIP44 if !is_rindex => {
+                term.info(&format!("checking wallet {}\n", style!(&name)))?;
+                let mut lookup_struct = load_bip44_lookup_structure(term, network_magic, &wallet);
+                lookup_struct.prepare_next_account()?;
+                // acknowledge the addresses already used, to search up to
+                // the gap limit after them
+                let mut lookup_struct = if wallet.config.attached_blockchain.is_some() {
+                    create_wallet_state_from_logs(&wallet, &root_dir, lookup_struct)?.lookup_struct
+                } else {
+                    lookup_struct
+                };
+                lookup_address(&mut lookup_struct, address.clone())?
+            }
+            HDWalletModel::RandomIndex2Levels if is_rindex => {
+                term.info(&format!("checking wallet {}\n", style!(&name)))?;
+                let mut lookup_struct =
+                    load_randomindex_lookup_structure(term, network_magic, &wallet);
+                lookup_address(&mut lookup_struct, address.clone())?
+            }
+            _ => continue,
+        };
+
+        match addressing {
+            Some(lookup::Address::Bip44(addressing)) => writeln!(
+                term,
+                "{} account {} change {} index {}",
+                style!(name),
+                style!(addressing.account.get_account_number()),
+                style!(addressing.change),
+                style!(addressing.index.get_scheme_value())
+            )?,
+            Some(lookup::Address::RIndex(addressing)) => {
+                writeln!(term, "{} {}", style!(name), addressing)?
+            }
+            _ => continue,
+        }
+        found = true;
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(Error::AddressNotOwned(address))
+    }
+}
+
+/// the key to sign a message with: the one of the given address, or the
+/// one at the given derivation path
+pub enum AddressOrPath {
+    Address(ExtendedAddr),
+    Path(String),
+}
+
+/// sign the given message with the private key of the given address (or
+/// derivation path) of the wallet, proving the ownership of the address.
+///
+/// The address, the public key and the signature are displayed, see
+/// `debug verify-message` to check the signature.
+pub fn sign_message(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    signer: AddressOrPath,
+    message: &str,
+) -> Result<()> {
+    use cardano::{bip::bip44, hdwallet::Signature, wallet::rindex};
+
+    let wallet = Wallet::load(root_dir.clone(), name)?;
+    let blockchain = load_attached_blockchain(&root_dir, &wallet.config)?;
+    let network_magic = blockchain.config.protocol_magic.into();
+    let data = message_signing_data(message);
+
+    let invalid_path = |path: &str| Error::CannotSignMessageInvalidPath(path.to_owned());
+
+    let (address, xpub, signature): (_, _, Signature<Vec<u8>>) = match wallet.config.hdwallet_model
+    {
+        HDWalletModel::BIP44 => {
+            let lookup_struct = load_bip44_lookup_structure(term, network_magic, &wallet);
+            // acknowledge the addresses already used by the wallet
+            let mut lookup_struct =
+                create_wallet_state_from_logs(&wallet, &root_dir, lookup_struct)?.lookup_struct;
+            let addressing = match signer {
+                AddressOrPath::Address(address) => {
+                    match lookup_address(&mut lookup_struct, address.clone())? {
+                        Some(lookup::Address::Bip44(addressing)) => addressing,
+                        _ => return Err(Error::CannotSignMessageUnknownAddress(address)),
+                    }
+                }
+                AddressOrPath::Path(path) => {
+                    let indices = parse_derivation_path(&path).ok_or(invalid_path(&path))?;
+                    if indices.len() != 3 {
+                        return Err(invalid_path(&path));
+                    }
+                    let account = bip44::Account::new(indices[0] & 0x7fff_ffff)?;
+                    let change = match indices[1] {
+                        0 => account.external()?,
+                        1 => account.internal()?,
+                        _ => return Err(invalid_path(&path)),
+                    };
+                    change.index(indices[2])?
+                }
+            };
+            (
+                lookup_struct.get_address(&addressing)?,
+                lookup_struct.get_public_key(&addressing)?,
+                lookup_struct.sign(&addressing, &data)?,
+            )
+        }
+        HDWalletModel::RandomIndex2Levels => {
+            let mut lookup_struct = load_randomindex_lookup_structure(term, network_magic, &wallet);
+            let addressing = match signer {
+                AddressOrPath::Address(address) => {
+                    match lookup_address(&mut lookup_struct, address.clone())? {
+                        Some(lookup::Address::RIndex(addressing)) => addressing,
+                        _ => return Err(Error::CannotSignMessageUnknownAddress(address)),
+                    }
+                }
+                AddressOrPath::Path(path) => {
+                    let indices = parse_derivation_path(&path).ok_or(invalid_path(&path))?;
+                    if indices.len() != 2 {
+                        return Err(invalid_path(&path));
+                    }
+                    rindex::Addressing::new(indices[0], indices[1])
+                }
+            };
+            (
+                lookup_struct.get_address(&addressing)?,
+                lookup_struct.get_public_key(&addressing)?,
+                lookup_struct.sign(&addressing, &data)?,
+            )
+        }
+    };
+
+    writeln!(term, "address:   {}", style!(address))?;
+    writeln!(term, "xpub:      {}", style!(xpub))?;
+    writeln!(term, "signature: {}", style!(signature))?;
+
+    Ok(())
+}
+
+/// list the UTxOs of the wallet as inputs for the consolidation plans
+fn consolidation_inputs(
+    root_dir: &PathBuf,
+    wallet: &Wallet,
+) -> Result<Vec<::cardano::txutils::Input<lookup::Address>>> {
+    let state = create_wallet_state_from_logs(wallet, root_dir, lookup::accum::Accum::default())?;
+    Ok(state
+        .utxos
+        .into_iter()
+        .map(|(_, utxo)| {
+            let txin = utxo.extract_txin();
+            let txout = utxo.extract_txout();
+            ::cardano::txutils::Input::new(txin, txout, utxo.credited_addressing)
+        })
+        .collect())
+}
+
+fn display_consolidation_plan(
+    term: &mut Term,
+    plan: &transaction::consolidation::Plan<lookup::Address>,
+    addresses: &[ExtendedAddr],
+) -> Result<()> {
+    for (index, consolidation) in plan.transactions.iter().enumerate() {
+        writeln!(
+            term,
+            "transaction {}: spending {} UTxOs ({}), fee {}",
+            style!(index + 1),
+            style!(consolidation.inputs.len()),
+            style!(consolidation.total),
+            style!(consolidation.fee.to_coin())
+        )?;
+    }
+    for address in addresses.iter() {
+        writeln!(term, "to {}", style!(address))?;
+    }
+    if plan.transactions.len() > 1 {
+        writeln!(
+            term,
+            "(the other transactions are sent to fresh addresses too)"
+        )?;
+    }
+    if !plan.dust.is_empty() {
+        writeln!(
+            term,
+            "skipping {} UTxOs worth less than the fee to spend them",
+            style!(plan.dust.len())
+        )?;
+    }
+    if !plan.untouched.is_empty() {
+        writeln!(
+            term,
+            "leaving {} UTxOs untouched",
+            style!(plan.untouched.len())
+        )?;
+    }
+    Ok(())
+}
+
+/// create a staging transaction for every transaction of the plan, the
+/// value of the inputs (minus the fee) is split evenly between fresh
+/// addresses: the given ones (the plan was made with) for the first
+/// transaction, the next ones of `change_addresses` for the others.
+///
+/// If `signer` is given, the staging transactions are finalized and signed
+/// with the keys of this (unlocked) wallet.
+fn stage_consolidation_plan(
+    root_dir: &PathBuf,
+    protocol_magic: ::cardano::config::ProtocolMagic,
+    plan: transaction::consolidation::Plan<lookup::Address>,
+    addresses: Vec<ExtendedAddr>,
+    change_addresses: &mut ChangeAddresses,
+    signer: Option<&WalletLookup>,
+) -> Result<Vec<transaction::core::StagingId>> {
+    use transaction::{
+        core::{self, StagingTransaction},
+        error::Error as TransactionError,
+    };
+
+    let count = addresses.len() as u32;
+    let mut addresses = Some(addresses);
+    let mut ids = Vec::with_capacity(plan.transactions.len());
+    for consolidation in plan.transactions {
+        let addresses = match addresses.take() {
+            Some(addresses) => addresses,
+            None => change_addresses.next(count)?,
+        };
+        let mut staging = StagingTransaction::new(root_dir.clone(), protocol_magic)
+            .map_err(TransactionError::CannotCreateNewTransaction)?;
+        for input in consolidation.inputs.iter() {
+            staging
+                .add_input(core::Input {
+                    transaction_id: input.ptr.id,
+                    index_in_transaction: input.ptr.index,
+                    expected_value: input.value.value,
+                })
+                .map_err(TransactionError::CannotAddInput)?;
+        }
+        for address in addresses.iter() {
+            staging
+                .add_change(address.clone().into())
+                .map_err(TransactionError::CannotAddChange)?;
+        }
+        ids.push(*staging.id());
+
+        let signer = match signer {
+            None => continue,
+            Some(signer) => signer,
+        };
+
+        staging
+            .finalize()
+            .map_err(TransactionError::CannotFinalize)?;
+        let txid = staging
+            .transaction()
+            .mk_txbuilder()
+            .map_err(TransactionError::CannotConsolidateInvalidTransaction)?
+            .0
+            .make_tx()
+            .map_err(TransactionError::CannotSignTransactionInvalidTxAux)?
+            .id();
+        let addressings: Vec<lookup::Address> = consolidation
+            .inputs
+            .into_iter()
+            .map(|input| input.addressing)
+            .collect();
+        for signature in signer.sign_tx_inputs(protocol_magic, &txid, &addressings)? {
+            staging
+                .add_signature(signature)
+                .map_err(TransactionError::CannotSignTransactionCannotAddSignature)?;
+        }
+    }
+
+    Ok(ids)
+}
+
+/// send the given staging transactions in order, destroying them once sent
+fn send_staging_transactions(
+    term: &mut Term,
+    root_dir: &PathBuf,
+    blockchain: &Blockchain,
+    ids: Vec<transaction::core::StagingId>,
+) -> Result<()> {
+    for id in ids {
+        let id_str = id.to_string();
+        transaction::commands::send(term, root_dir.clone(), &id_str, blockchain.name.clone())?;
+        transaction::commands::destroy(term, root_dir.clone(), &id_str)?;
+    }
+    Ok(())
+}
+
+fn confirm() -> Result<bool> {
+    let confirmation = ::dialoguer::Confirmation::new()
+        .with_text("Are you sure?")
+        .default(false)
+        .interact()?;
+    Ok(confirmation)
+}
+
+/// merge the UTxOs of the wallet into `outputs` fresh internal addresses
+///
+/// The plan (possibly several transactions) is displayed and confirmed
+/// before the transactions are signed and sent. In dry-run mode the
+/// transactions are left as staging transactions.
+pub fn consolidate(
+    term: &mut Term,
+    root_dir: PathBuf,
+    name: WalletName,
+    outputs: u32,
+    dry_run: bool,
+) -> Result<()> {
+    use cardano::fee::LinearFee;
+    use transaction::{consolidation, error::Error as TransactionError};
+
+    let wallet = Wallet::load(root_dir.clone(), name)?;
+    let blockchain = load_attached_blockchain(&root_dir, &wallet.config)?;
+    let protocol_magic = blockchain.config.protocol_magic;
+
+    let inputs = consolidation_inputs(&root_dir, &wallet)?;
+    // the same lookup structure derives the addresses and signs, the
+    // password is asked only once
+    let lookup = WalletLookup::new(term, &wallet, protocol_magic.into());
+    let mut change_addresses = ChangeAddresses::new(&wallet, &lookup);
+    let addresses = change_addresses.next(outputs)?;
+    // merging no more inputs than there are outputs does not reduce the
+    // number of UTxOs
+    let plan = consolidation::plan(
+        &LinearFee::default(),
+        inputs,
+        &addresses,
+        addresses.len() + 1,
+    )
+    .map_err(TransactionError::CannotConsolidate)?;
+
+    display_consolidation_plan(term, &plan, &addresses)?;
+    if plan.transactions.is_empty() {
+        writeln!(term, "nothing to consolidate")?;
+        return Ok(());
+    }
+
+    if dry_run {
+        let ids = stage_consolidation_plan(
+            &root_dir,
+            protocol_magic,
+            plan,
+            addresses,
+            &mut change_addresses,
+            None,
+        )?;
+        for id in ids {
+            writeln!(
+                term,
+                "transaction not sent, it is available in the staging transaction {}",
+                style!(id)
+            )?;
+        }
+        return Ok(());
+    }
+
+    if !confirm()? {
+        return Ok(());
+    }
+    let ids = stage_consolidation_plan(
+        &root_dir,
+        protocol_magic,
+        plan,
+        addresses,
+        &mut change_addresses,
+        Some(&lookup),
+    )?;
+    send_staging_transactions(term, &root_dir, &blockchain, ids)
+}
+
+/// move all the UTxOs of the wallet `from` to fresh addresses of the
+/// wallet `to` (e.g. when rotating keys)
+///
+/// The plan (possibly several transactions) is displayed and confirmed
+/// before the transactions are signed with the keys of `from`. The signed
+/// transactions are sent in order if `send` is set, otherwise they are left
+/// as staging transactions.
+pub fn migrate(
+    term: &mut Term,
+    root_dir: PathBuf,
+    from: WalletName,
+    to: WalletName,
+    outputs: u32,
+    send: bool,
+) -> Result<()> {
+    use cardano::fee::LinearFee;
+    use transaction::{consolidation, error::Error as TransactionError};
+
+    let from = Wallet::load(root_dir.clone(), from)?;
+    let to = Wallet::load(root_dir.clone(), to)?;
+    let blockchain = load_attached_blockchain(&root_dir, &from.config)?;
+    let protocol_magic = blockchain.config.protocol_magic;
+
+    // the funds cannot be sent to the addresses of another network
+    let to_blockchain = load_attached_blockchain(&root_dir, &to.config)?;
+    if to_blockchain.config.protocol_magic != protocol_magic {
+        return Err(Error::CannotMigrateToOtherNetwork(
+            blockchain.name.as_ref().to_owned(),
+            to_blockchain.name.as_ref().to_owned(),
+        ));
+    }
+
+    let inputs = consolidation_inputs(&root_dir, &from)?;
+    let to_lookup = WalletLookup::new(term, &to, protocol_magic.into());
+    let mut change_addresses = ChangeAddresses::new(&to, &to_lookup);
+    let addresses = change_addresses.next(outputs)?;
+    let plan = consolidation::plan(&LinearFee::default(), inputs, &addresses, 1)
+        .map_err(TransactionError::CannotConsolidate)?;
+
+    display_consolidation_plan(term, &plan, &addresses)?;
+    if plan.transactions.is_empty() {
+        writeln!(term, "nothing to migrate")?;
+        return Ok(());
+    }
+
+    if !confirm()? {
+        return Ok(());
+    }
+    let from_lookup = WalletLookup::new(term, &from, protocol_magic.into());
+    let ids = stage_consolidation_plan(
+        &root_dir,
+        protocol_magic,
+        plan,
+        addresses,
+        &mut change_addresses,
+        Some(&from_lookup),
+    )?;
+    if send {
+        send_staging_transactions(term, &root_dir, &blockchain, ids)
+    } else {
+        for id in ids {
+            writeln!(
+                term,
+                "transaction signed but not sent, it is available in the staging transaction {}",
+                style!(id)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 use cardano::hdwallet::{self, DerivationScheme};
 
 use super::super::utils::password_encrypted::{self, Password};
+use super::super::utils::secret::xprv_from_bytes_verified;
 use super::Error;
 use super::Result;
 use blockchain::{BlockchainName, BlockchainNameError};
@@ -98,14 +99,5 @@ pub fn decrypt_primary_key(password: &Password, encrypted_key: &[u8]) -> Result<
         Some(bytes) => bytes,
     };
 
-    if xprv_vec.len() != hdwallet::XPRV_SIZE {
-        return Err(Error::CannotRetrievePrivateKey(
-            hdwallet::Error::InvalidXPrvSize(xprv_vec.len()),
-        ));
-    }
-
-    let mut xprv_bytes = [0; hdwallet::XPRV_SIZE];
-    xprv_bytes.copy_from_slice(&xprv_vec[..]);
-
-    Ok(hdwallet::XPrv::from_bytes_verified(xprv_bytes)?)
+    Ok(xprv_from_bytes_verified(&xprv_vec)?)
 }
@@ -0,0 +1,199 @@
+//! Daedalus' `secret.key` user secret files
+//!
+//! The user secret is a CBOR array of 4 elements:
+//!
+//! 1. the optional VSS key pair (unused here);
+//! 2. the optional primary secret key (unused here);
+//! 3. the encrypted root keys of the Daedalus wallets;
+//! 4. the optional wallet user secret, starting with an encrypted root key.
+//!
+//! (optional values are encoded as arrays of zero or one element.)
+//!
+//! An encrypted root key is the array of the encrypted extended private key
+//! (the 64 bytes of the secret key encrypted with ChaCha20, followed by the
+//! public key and the chain code) and of the hash of the passphrase.
+
+use cardano::{
+    hash::Blake2b256,
+    hdwallet::{self, XPrv, XPub, XPRV_SIZE, XPUB_SIZE},
+};
+use cbor_event::{self, de::Deserializer, Value};
+use cryptoxide::{
+    chacha20::ChaCha20, ed25519, hmac::Hmac, pbkdf2::pbkdf2, sha2::Sha512,
+    symmetriccipher::SynchronousStreamCipher,
+};
+use std::{error, fmt, fs, io, path::Path};
+
+use utils::secret::{xprv_from_bytes_verified, Secret};
+
+const ENCRYPTED_KEY_SIZE: usize = 128;
+const SECRET_KEY_SIZE: usize = 64;
+const PUBLIC_KEY_SIZE: usize = 32;
+
+/// the salt of `encrypted_sign.c` in cardano-crypto: the C `sizeof` of the
+/// string literal counts its terminating NUL byte, so does the salt.
+const STRETCH_SALT: &'static [u8] = b"encrypted wallet salt\0";
+const STRETCH_ITERATIONS: u32 = 15_000;
+const STRETCH_KEY_SIZE: usize = 32;
+/// the (constant) ChaCha20 nonce of `encrypted_sign.c`: only the key is
+/// derived from the passphrase.
+const CHACHA_NONCE: &'static [u8; 12] = b"serokellfore";
+
+#[derive(Debug)]
+pub enum Error {
+    CannotReadFile(io::Error),
+    InvalidCbor(cbor_event::Error),
+    InvalidFormat(&'static str),
+    InvalidPassphrase,
+    InvalidKey(hdwallet::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::CannotReadFile(_) => write!(f, "Cannot read the Daedalus key file"),
+            Error::InvalidCbor(_) => write!(f, "The Daedalus key file is not valid CBOR"),
+            Error::InvalidFormat(what) => {
+                write!(f, "Invalid Daedalus key file format ({})", what)
+            }
+            Error::InvalidPassphrase => write!(f, "Invalid Daedalus spending password"),
+            Error::InvalidKey(_) => write!(f, "Invalid Daedalus root key"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::CannotReadFile(ref err) => Some(err),
+            Error::InvalidCbor(ref err) => Some(err),
+            Error::InvalidFormat(_) => None,
+            Error::InvalidPassphrase => None,
+            Error::InvalidKey(ref err) => Some(err),
+        }
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// a root key encrypted with the Daedalus spending password
+pub struct EncryptedRootKey([u8; ENCRYPTED_KEY_SIZE]);
+impl EncryptedRootKey {
+    fn from_value(value: &Value) -> Result<Self> {
+        match array(value, "encrypted secret key")?.first() {
+            Some(Value::Bytes(bytes)) if bytes.len() == ENCRYPTED_KEY_SIZE => {
+                let mut key = [0; ENCRYPTED_KEY_SIZE];
+                key.copy_from_slice(bytes);
+                Ok(EncryptedRootKey(key))
+            }
+            _ => Err(Error::InvalidFormat("encrypted secret key")),
+        }
+    }
+
+    /// the (unencrypted) public key of the root key
+    fn public_key(&self) -> &[u8] {
+        &self.0[SECRET_KEY_SIZE..SECRET_KEY_SIZE + PUBLIC_KEY_SIZE]
+    }
+
+    /// the root public key (the public key followed by the chain code are
+    /// stored in clear)
+    pub fn root_public_key(&self) -> XPub {
+        let mut bytes = [0; XPUB_SIZE];
+        bytes.copy_from_slice(&self.0[SECRET_KEY_SIZE..]);
+        XPub::from_bytes(bytes)
+    }
+
+    /// decrypt the root key with the spending password set in Daedalus (if
+    /// any): Daedalus uses the Blake2b256 hash of the password as passphrase,
+    /// and leaves the key unencrypted when there is no password.
+    pub fn decrypt(&self, password: &str) -> Result<XPrv> {
+        let mut secret = Secret::new(vec![0; SECRET_KEY_SIZE]);
+        if password.is_empty() {
+            secret.copy_from_slice(&self.0[..SECRET_KEY_SIZE]);
+        } else {
+            let passphrase = Blake2b256::new(password.as_bytes());
+            let mut key = Secret::new(vec![0; STRETCH_KEY_SIZE]);
+            let mut mac = Hmac::new(Sha512::new(), passphrase.as_ref());
+            pbkdf2(&mut mac, STRETCH_SALT, STRETCH_ITERATIONS, &mut key);
+            let mut cipher = ChaCha20::new(&key, CHACHA_NONCE);
+            cipher.process(&self.0[..SECRET_KEY_SIZE], &mut secret);
+        }
+
+        // the public key is stored in clear: it tells if the password is right
+        if &ed25519::to_public(&*secret)[..] != self.public_key() {
+            return Err(Error::InvalidPassphrase);
+        }
+
+        let mut bytes = Secret::new(Vec::with_capacity(XPRV_SIZE));
+        bytes.extend_from_slice(&secret);
+        bytes.extend_from_slice(&self.0[SECRET_KEY_SIZE + PUBLIC_KEY_SIZE..]);
+        xprv_from_bytes_verified(&bytes).map_err(Error::InvalidKey)
+    }
+}
+
+fn array<'a>(value: &'a Value, what: &'static str) -> Result<&'a [Value]> {
+    match value {
+        Value::Array(values) | Value::IArray(values) => Ok(values),
+        _ => Err(Error::InvalidFormat(what)),
+    }
+}
+
+/// read all the encrypted root keys of the given user secret file
+pub fn read_root_keys<P: AsRef<Path>>(path: P) -> Result<Vec<EncryptedRootKey>> {
+    let bytes = fs::read(path).map_err(Error::CannotReadFile)?;
+    parse_root_keys(bytes)
+}
+
+fn parse_root_keys(bytes: Vec<u8>) -> Result<Vec<EncryptedRootKey>> {
+    let value: Value = Deserializer::from(io::Cursor::new(bytes))
+        .deserialize()
+        .map_err(Error::InvalidCbor)?;
+
+    let user_secret = array(&value, "user secret")?;
+    if user_secret.len() < 4 {
+        return Err(Error::InvalidFormat("user secret"));
+    }
+
+    let mut keys = Vec::new();
+    for key in array(&user_secret[2], "secret keys")? {
+        keys.push(EncryptedRootKey::from_value(key)?);
+    }
+    if let Some(wallet) = array(&user_secret[3], "wallet user secret")?.first() {
+        match array(wallet, "wallet user secret")?.first() {
+            None => return Err(Error::InvalidFormat("wallet user secret")),
+            Some(key) => keys.push(EncryptedRootKey::from_value(key)?),
+        }
+    }
+
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cardano::util::hex;
+
+    /// a user secret with one root key encrypted with the spending password
+    /// `correct horse battery staple', built following `encrypted_sign.c`
+    /// (PBKDF2-SHA512 of the Blake2b256 of the password, ChaCha20 with the
+    /// `serokellfore' nonce) with an implementation independent of this one.
+    const USER_SECRET: &'static str = "84808081825880ed22020ab33b00b07c10dcefa781359a611be52144fb71b3f880eba1943d38088d24ee298e94457463751fc358d297cb1ac8d9c88cd7534411f0260b9595171e280d9cc7232d8a4bde48676af17c31fd2040d6eb64fe195319d73e2cb6105511fd54f6e23ba6ded7d9a10acefdf80ad7162f7683c00e9ab942b085d30132efdd4080";
+    const PASSWORD: &'static str = "correct horse battery staple";
+    const ROOT_KEY: &'static str = "682b085972efd22aac182640d2e2ce4818fb1f0b72cb767e89546e8d8ecccb512934a12af6db81a39e96bc24fbda9cfb0e0e997dced4922694e0b372815b9d49fd54f6e23ba6ded7d9a10acefdf80ad7162f7683c00e9ab942b085d30132efdd";
+
+    #[test]
+    fn decrypt_known_answer() {
+        let keys = parse_root_keys(hex::decode(USER_SECRET).unwrap()).unwrap();
+        assert_eq!(keys.len(), 1);
+        let xprv = keys[0].decrypt(PASSWORD).unwrap();
+        assert_eq!(hex::encode(xprv.as_ref()), ROOT_KEY);
+        assert_eq!(xprv.public(), keys[0].root_public_key());
+    }
+
+    #[test]
+    fn decrypt_wrong_password() {
+        let keys = parse_root_keys(hex::decode(USER_SECRET).unwrap()).unwrap();
+        match keys[0].decrypt("wrong password") {
+            Err(Error::InvalidPassphrase) => {}
+            _ => panic!("the key is decrypted with a wrong password"),
+        }
+    }
+}
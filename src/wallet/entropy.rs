@@ -0,0 +1,191 @@
+//! user supplied entropy for the wallet mnemonics
+//!
+//! For cold storage setups where the entropy needs to be auditable, it can
+//! be collected from dice rolls or given in hexadecimal instead of being
+//! generated by the system's random number generator.
+
+use cardano::{bip::bip39, hash::Blake2b256, util::hex};
+use std::{error, fmt, str::FromStr};
+
+/// where the entropy of the mnemonics comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySource {
+    /// the system's random number generator
+    System,
+    /// dice rolls (digits `1` to `6`), hashed with Blake2b256
+    Dice,
+    /// hexadecimal digits, used as is
+    Hex,
+    /// raw bytes read from the standard input, used as is
+    Stdin,
+}
+impl FromStr for EntropySource {
+    type Err = String;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(EntropySource::System),
+            "dice" => Ok(EntropySource::Dice),
+            "hex" => Ok(EntropySource::Hex),
+            "stdin" => Ok(EntropySource::Stdin),
+            _ => Err(format!("unknown entropy source `{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    InvalidDiceRoll(char),
+    NotEnoughDiceRolls { needed: usize, got: usize },
+    InvalidHex,
+    InvalidHexLength { expected: usize, got: usize },
+    NotEnoughBytes { needed: usize, got: usize },
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidDiceRoll(c) => {
+                write!(f, "Invalid dice roll `{}' (expected `1' to `6')", c)
+            }
+            Error::NotEnoughDiceRolls { needed, got } => write!(
+                f,
+                "Not enough dice rolls, {} needed but only {} given",
+                needed, got
+            ),
+            Error::InvalidHex => write!(f, "Invalid hexadecimal digits"),
+            Error::InvalidHexLength { expected, got } => write!(
+                f,
+                "Invalid entropy length, expected {} hexadecimal digits but received {}",
+                expected, got
+            ),
+            Error::NotEnoughBytes { needed, got } => write!(
+                f,
+                "Not enough entropy, {} bytes needed but only {} given",
+                needed, got
+            ),
+        }
+    }
+}
+impl error::Error for Error {}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// the size (in bytes) of the entropy of mnemonics of the given size
+///
+/// Each word encodes 11 bits, 1 bit out of 33 being the checksum.
+pub fn entropy_size(size: bip39::Type) -> usize {
+    size.mnemonic_count() * 4 / 3
+}
+
+/// the number of dice rolls needed to have (at least) as much entropy as
+/// mnemonics of the given size, each roll bringing `log2(6)` bits.
+pub fn dice_rolls_needed(size: bip39::Type) -> usize {
+    let bits = entropy_size(size) * 8;
+    (bits as f64 / 6f64.log2()).ceil() as usize
+}
+
+/// keep the dice rolls of the given input (whitespaces are ignored)
+pub fn parse_dice_rolls(input: &str) -> Result<String> {
+    input
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '1'..='6' => Ok(c),
+            _ => Err(Error::InvalidDiceRoll(c)),
+        })
+        .collect()
+}
+
+/// compute the entropy from the dice rolls (as returned by
+/// `parse_dice_rolls`)
+pub fn from_dice_rolls(rolls: &str, size: bip39::Type) -> Result<Vec<u8>> {
+    let needed = dice_rolls_needed(size);
+    if rolls.len() < needed {
+        return Err(Error::NotEnoughDiceRolls {
+            needed: needed,
+            got: rolls.len(),
+        });
+    }
+    let hash = Blake2b256::new(rolls.as_bytes());
+    Ok(hash.as_ref()[..entropy_size(size)].to_vec())
+}
+
+/// decode the entropy from hexadecimal digits (whitespaces are ignored)
+pub fn from_hex(input: &str, size: bip39::Type) -> Result<Vec<u8>> {
+    let digits: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let expected = entropy_size(size) * 2;
+    if digits.len() != expected {
+        return Err(Error::InvalidHexLength {
+            expected: expected,
+            got: digits.len(),
+        });
+    }
+    hex::decode(&digits).map_err(|_| Error::InvalidHex)
+}
+
+/// take the entropy from the first bytes of the given data
+pub fn from_bytes(bytes: &[u8], size: bip39::Type) -> Result<Vec<u8>> {
+    let needed = entropy_size(size);
+    if bytes.len() < needed {
+        return Err(Error::NotEnoughBytes {
+            needed: needed,
+            got: bytes.len(),
+        });
+    }
+    Ok(bytes[..needed].to_vec())
+}
+
+/// mix the given entropy with other random bytes (of the same size)
+pub fn mix(entropy: &mut [u8], random: &[u8]) {
+    for (byte, other) in entropy.iter_mut().zip(random) {
+        *byte ^= other;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(entropy_size(bip39::Type::Type12Words), 16);
+        assert_eq!(entropy_size(bip39::Type::Type24Words), 32);
+        assert_eq!(dice_rolls_needed(bip39::Type::Type12Words), 50);
+        assert_eq!(dice_rolls_needed(bip39::Type::Type24Words), 100);
+    }
+
+    #[test]
+    fn dice_rolls() {
+        let rolls = parse_dice_rolls("1234 5612\n3456").unwrap();
+        assert_eq!(rolls, "123456123456");
+        assert_eq!(parse_dice_rolls("1237"), Err(Error::InvalidDiceRoll('7')));
+        assert_eq!(
+            from_dice_rolls(&rolls, bip39::Type::Type12Words),
+            Err(Error::NotEnoughDiceRolls {
+                needed: 50,
+                got: 12
+            })
+        );
+
+        let rolls = "3".repeat(50);
+        let entropy = from_dice_rolls(&rolls, bip39::Type::Type12Words).unwrap();
+        assert_eq!(entropy.len(), 16);
+    }
+
+    #[test]
+    fn hexadecimal() {
+        let entropy = from_hex(
+            "00112233 44556677 8899aabb ccddeeff",
+            bip39::Type::Type12Words,
+        )
+        .unwrap();
+        assert_eq!(entropy[0], 0x00);
+        assert_eq!(entropy[15], 0xff);
+        assert_eq!(
+            from_hex("0011", bip39::Type::Type12Words),
+            Err(Error::InvalidHexLength {
+                expected: 32,
+                got: 4
+            })
+        );
+    }
+}
@@ -1,11 +1,18 @@
 use blockchain;
-use cardano::{bip::bip44, coin, hdwallet, wallet::rindex};
+use cardano::{
+    address::ExtendedAddr,
+    bip::{bip39, bip44},
+    coin, hdwallet,
+    wallet::rindex,
+};
 use serde_yaml;
 use storage_units::utils::lock;
+use transaction;
 
 use std::{error, fmt, io, path::PathBuf};
 
 use super::state::{log, lookup};
+use super::{daedalus_keyfile, entropy, shamir};
 
 /// wallet errors
 #[derive(Debug)]
@@ -30,6 +37,26 @@ pub enum Error {
     NotAttachedToBlockchain,
     AttachAlreadyAttached(String),
     WalletsLoadFailed(io::Error),
+    CannotSendFunds(Box<transaction::error::Error>),
+    CannotRedeemNoFunds(ExtendedAddr),
+    CannotSignMessageUnknownAddress(ExtendedAddr),
+    CannotSignMessageInvalidPath(String),
+    AddressNotOwned(ExtendedAddr),
+    WalletAddressingMismatch,
+    AddressPoolGapLimitReached(u32),
+    ChangeAddressesGapLimitReached(u32),
+    CannotMigrateToOtherNetwork(String, String),
+    CannotScramblePaperWallet(bip39::Error),
+    CannotUnscramblePaperWallet(bip39::Error),
+    ShamirError(shamir::Error),
+    CannotRecoverFromSharesFingerprintMismatch,
+    CannotRecoverFromSharesInvalidEntropy(bip39::Error),
+    CannotSplitMnemonicsMismatch,
+    CannotRepairMnemonics,
+    EntropyError(entropy::Error),
+    DaedalusKeyfileError(daedalus_keyfile::Error),
+    DaedalusKeyfileNoKey,
+    DaedalusKeyfileAmbiguousKey(usize),
 }
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
@@ -46,6 +73,21 @@ impl From<blockchain::BlockchainNameError> for Error {
         Error::BlockchainNameError(e)
     }
 }
+impl From<daedalus_keyfile::Error> for Error {
+    fn from(e: daedalus_keyfile::Error) -> Self {
+        Error::DaedalusKeyfileError(e)
+    }
+}
+impl From<entropy::Error> for Error {
+    fn from(e: entropy::Error) -> Self {
+        Error::EntropyError(e)
+    }
+}
+impl From<shamir::Error> for Error {
+    fn from(e: shamir::Error) -> Self {
+        Error::ShamirError(e)
+    }
+}
 impl From<coin::Error> for Error {
     fn from(e: coin::Error) -> Self {
         Error::CoinError(e)
@@ -77,6 +119,11 @@ impl From<log::Error> for Error {
         }
     }
 }
+impl From<transaction::error::Error> for Error {
+    fn from(e: transaction::error::Error) -> Self {
+        Error::CannotSendFunds(Box::new(e))
+    }
+}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -120,6 +167,76 @@ impl fmt::Display for Error {
                 write!(f, "Wallet already attached to blockchain `{}'", bn)
             }
             Error::WalletsLoadFailed(_) => write!(f, "Cannot load wallets"),
+            Error::CannotSendFunds(_) => write!(f, "Cannot send funds from the wallet"),
+            Error::CannotRedeemNoFunds(address) => write!(
+                f,
+                "No funds to redeem at `{}' (in the genesis AVVM distribution or the local blockchain)",
+                address
+            ),
+            Error::CannotSignMessageUnknownAddress(address) => {
+                write!(f, "Address `{}' does not belong to the wallet", address)
+            }
+            Error::CannotSignMessageInvalidPath(path) => write!(
+                f,
+                "Invalid derivation path `{}' (expected `account/change/index' for BIP44 wallets, `account/index' for random index wallets)",
+                path
+            ),
+            Error::AddressNotOwned(address) => {
+                write!(f, "Address `{}' does not belong to any wallet", address)
+            }
+            Error::WalletAddressingMismatch => write!(
+                f,
+                "The address derivation scheme does not match the wallet's (BIP44 or random index)"
+            ),
+            Error::AddressPoolGapLimitReached(gap_limit) => write!(
+                f,
+                "{} issued addresses are still unused (gap limit), use `--force' to issue more",
+                gap_limit
+            ),
+            Error::ChangeAddressesGapLimitReached(gap_limit) => write!(
+                f,
+                "Cannot issue more change addresses, at most {} of them can be unused (gap limit)",
+                gap_limit
+            ),
+            Error::CannotMigrateToOtherNetwork(from, to) => write!(
+                f,
+                "Cannot migrate the funds from the blockchain `{}' to the blockchain `{}' (different protocol magic)",
+                from, to
+            ),
+            Error::CannotScramblePaperWallet(_) => write!(
+                f,
+                "Cannot scramble the mnemonics, use at most 18 mnemonic words"
+            ),
+            Error::CannotUnscramblePaperWallet(_) => write!(
+                f,
+                "Cannot unscramble the mnemonics, is it a scrambled paper wallet?"
+            ),
+            Error::ShamirError(_) => write!(f, "Cannot split or combine the wallet's shares"),
+            Error::CannotRecoverFromSharesFingerprintMismatch => write!(
+                f,
+                "The recovered wallet does not have the fingerprint of the shares, some shares (or the recovery password) are wrong"
+            ),
+            Error::CannotRecoverFromSharesInvalidEntropy(_) => {
+                write!(f, "The shares do not hold valid mnemonics, some shares are wrong")
+            }
+            Error::CannotSplitMnemonicsMismatch => write!(
+                f,
+                "The mnemonics are not the wallet's (or the recovery password is wrong)"
+            ),
+            Error::CannotRepairMnemonics => write!(
+                f,
+                "No valid mnemonics found with one wrong, swapped or missing word"
+            ),
+            Error::EntropyError(_) => write!(f, "Cannot use the given entropy"),
+            Error::DaedalusKeyfileError(_) => write!(f, "Cannot import the Daedalus key file"),
+            Error::DaedalusKeyfileNoKey => {
+                write!(f, "The Daedalus key file does not contain any wallet key")
+            }
+            Error::DaedalusKeyfileAmbiguousKey(count) => write!(
+                f,
+                "The Daedalus key file contains {} wallet keys, select one with `--key-index'",
+                count
+            ),
         }
     }
 }
@@ -146,6 +263,26 @@ impl error::Error for Error {
             Error::NotAttachedToBlockchain => None,
             Error::AttachAlreadyAttached(_) => None,
             Error::WalletsLoadFailed(ref err) => Some(err),
+            Error::CannotSendFunds(ref err) => Some(err),
+            Error::CannotRedeemNoFunds(_) => None,
+            Error::CannotSignMessageUnknownAddress(_) => None,
+            Error::CannotSignMessageInvalidPath(_) => None,
+            Error::AddressNotOwned(_) => None,
+            Error::WalletAddressingMismatch => None,
+            Error::AddressPoolGapLimitReached(_) => None,
+            Error::ChangeAddressesGapLimitReached(_) => None,
+            Error::CannotMigrateToOtherNetwork(_, _) => None,
+            Error::CannotScramblePaperWallet(ref err) => Some(err),
+            Error::CannotUnscramblePaperWallet(ref err) => Some(err),
+            Error::ShamirError(ref err) => Some(err),
+            Error::CannotRecoverFromSharesFingerprintMismatch => None,
+            Error::CannotRecoverFromSharesInvalidEntropy(ref err) => Some(err),
+            Error::CannotSplitMnemonicsMismatch => None,
+            Error::CannotRepairMnemonics => None,
+            Error::EntropyError(ref err) => Some(err),
+            Error::DaedalusKeyfileError(ref err) => Some(err),
+            Error::DaedalusKeyfileNoKey => None,
+            Error::DaedalusKeyfileAmbiguousKey(_) => None,
         }
     }
 }
@@ -1,11 +1,16 @@
+mod address_pool;
 pub mod commands;
 mod config;
+mod daedalus_keyfile;
+mod entropy;
 mod error;
 mod result;
+mod shamir;
 pub mod state;
 pub mod utils;
 
 pub use self::config::{Config, HDWalletModel};
+pub use self::entropy::EntropySource;
 pub use self::error::Error;
 pub use self::result::Result;
 
@@ -21,7 +26,7 @@ use std::{
 };
 
 use cardano::{
-    hdwallet::{XPub, XPUB_SIZE},
+    hdwallet::{self, XPub, XPUB_SIZE},
     wallet,
 };
 use serde_yaml;
@@ -213,6 +218,19 @@ impl Wallet {
         Ok(lock.delete_wallet_log()?)
     }
 
+    /// convenient function to decrypt the root private key of the wallet
+    ///
+    /// # Error
+    ///
+    /// This function may fail if:
+    ///
+    /// * the password in invalid;
+    /// * the encrypted value did not represent a HDWallet XPrv
+    ///
+    pub fn get_root_key(&self, password: &Password) -> Result<hdwallet::XPrv> {
+        decrypt_primary_key(password, &self.encrypted_key)
+    }
+
     /// convenient function to reconstruct a BIP44 wallet from the encrypted key and password
     ///
     /// # Error
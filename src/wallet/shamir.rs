@@ -0,0 +1,436 @@
+//! Shamir's secret sharing of the wallet's mnemonic entropy
+//!
+//! The secret is the entropy of the wallet's mnemonics (16 to 32 bytes): the
+//! recovered entropy gives the mnemonics back, and the wallet is rebuilt from
+//! them as usual (with the recovery password, if any).
+//!
+//! The secret is split byte per byte: each byte is the constant term of a
+//! random polynomial of degree `threshold - 1` over GF(256), and each share
+//! holds the evaluation of these polynomials at its own (non zero) index.
+//! Any `threshold` shares allow to recover the secret with the Lagrange
+//! interpolation at zero, fewer shares give no information about it.
+//!
+//! A share is encoded as a list of words of a BIP39 dictionary (11 bits per
+//! word). It embeds:
+//!
+//! * a random identifier of the split (to detect shares of different splits),
+//!   nothing about the secret is derived from it;
+//! * the threshold and the index of the share;
+//! * a checksum, so every share can be checked as soon as it is entered.
+//!
+//! A share of 16 bytes of entropy is 18 words long, 30 words for 32 bytes.
+//!
+//! The recovered secret itself is not checked here: the caller is to compare
+//! the public key derived from it with the expected one.
+
+use cardano::{
+    bip::bip39::{dictionary::Language, MnemonicIndex},
+    hash::Blake2b256,
+};
+use rand::Rng;
+use std::{error, fmt};
+
+const ID_SIZE: usize = 2;
+const CHECKSUM_SIZE: usize = 4;
+const HEADER_SIZE: usize = ID_SIZE + 2;
+const BITS_PER_WORD: usize = 11;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the threshold must be at least 1 and at most the number of shares,
+    /// and there are at most 255 shares
+    InvalidParameters {
+        threshold: u8,
+        shares: u8,
+    },
+    InvalidWord(String),
+    InvalidLength(usize),
+    InvalidChecksum,
+    NotEnoughShares {
+        threshold: u8,
+        shares: usize,
+    },
+    DuplicateShare(u8),
+    /// the share does not belong to the same split as the other ones
+    MismatchedShare(u8),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidParameters { threshold, shares } => write!(
+                f,
+                "Cannot split in {} shares with a threshold of {} (expected 1 <= threshold <= shares)",
+                shares, threshold
+            ),
+            Error::InvalidWord(word) => write!(f, "`{}' is not a valid mnemonic word", word),
+            Error::InvalidLength(count) => write!(f, "Invalid share length ({} words)", count),
+            Error::InvalidChecksum => write!(f, "Invalid share (checksum mismatch)"),
+            Error::NotEnoughShares { threshold, shares } => write!(
+                f,
+                "Not enough shares, {} needed but only {} given",
+                threshold, shares
+            ),
+            Error::DuplicateShare(index) => write!(f, "Share #{} given twice", index),
+            Error::MismatchedShare(index) => {
+                write!(f, "Share #{} does not belong to the same split", index)
+            }
+        }
+    }
+}
+impl error::Error for Error {}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// arithmetic in GF(256), with the AES polynomial (x^8 + x^4 + x^3 + x + 1)
+mod gf256 {
+    pub fn add(a: u8, b: u8) -> u8 {
+        a ^ b
+    }
+
+    pub fn mul(mut a: u8, mut b: u8) -> u8 {
+        let mut r = 0;
+        while b != 0 {
+            if b & 1 != 0 {
+                r ^= a;
+            }
+            let carry = a & 0x80 != 0;
+            a <<= 1;
+            if carry {
+                a ^= 0x1b;
+            }
+            b >>= 1;
+        }
+        r
+    }
+
+    /// multiplicative inverse (`a^254`), `a` must not be zero
+    pub fn inv(a: u8) -> u8 {
+        debug_assert!(a != 0);
+        let mut r = 1;
+        for _ in 0..254 {
+            r = mul(r, a);
+        }
+        r
+    }
+
+    pub fn div(a: u8, b: u8) -> u8 {
+        mul(a, inv(b))
+    }
+}
+
+/// one of the shares of a secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    id: [u8; ID_SIZE],
+    threshold: u8,
+    index: u8,
+    value: Vec<u8>,
+}
+impl Share {
+    /// the number of shares needed to recover the secret
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// the index of the share (starting from 1)
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.value.len() + CHECKSUM_SIZE);
+        bytes.extend_from_slice(&self.id);
+        bytes.push(self.threshold);
+        bytes.push(self.index);
+        bytes.extend_from_slice(&self.value);
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (data, sum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        if checksum(data) != sum {
+            return Err(Error::InvalidChecksum);
+        }
+        let mut id = [0; ID_SIZE];
+        id.copy_from_slice(&data[..ID_SIZE]);
+        Ok(Share {
+            id: id,
+            threshold: data[ID_SIZE],
+            index: data[ID_SIZE + 1],
+            value: data[HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    /// encode the share in words of the given dictionary
+    pub fn to_words<D: Language>(&self, dic: &D) -> Vec<String> {
+        let bytes = self.to_bytes();
+        let count = (bytes.len() * 8 + BITS_PER_WORD - 1) / BITS_PER_WORD;
+
+        (0..count)
+            .map(|word| {
+                let mut index = 0u16;
+                for bit in word * BITS_PER_WORD..(word + 1) * BITS_PER_WORD {
+                    index <<= 1;
+                    if let Some(byte) = bytes.get(bit / 8) {
+                        index |= ((byte >> (7 - bit % 8)) & 1) as u16;
+                    }
+                }
+                dic.lookup_word(MnemonicIndex::new(index).unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    /// decode a share from words of the given dictionary, checking its
+    /// checksum
+    pub fn from_words<D: Language, S: AsRef<str>>(dic: &D, words: &[S]) -> Result<Self> {
+        let mut bits = Vec::with_capacity(words.len() * BITS_PER_WORD);
+        for word in words {
+            let index = dic
+                .lookup_mnemonic(word.as_ref())
+                .map_err(|_| Error::InvalidWord(word.as_ref().to_owned()))?
+                .0;
+            for i in (0..BITS_PER_WORD).rev() {
+                bits.push((index >> i) & 1 == 1);
+            }
+        }
+
+        // the padding may be longer than a byte, so two sizes may be
+        // encoded in the same number of words: the checksum tells which one
+        let max_size = bits.len() / 8;
+        let mut result = Err(Error::InvalidLength(words.len()));
+        for size in (max_size.saturating_sub(1)..=max_size).rev() {
+            if size <= HEADER_SIZE + CHECKSUM_SIZE
+                || (size * 8 + BITS_PER_WORD - 1) / BITS_PER_WORD != words.len()
+            {
+                continue;
+            }
+            let bytes: Vec<u8> = bits[..size * 8]
+                .chunks(8)
+                .map(|byte| byte.iter().fold(0, |acc, bit| acc << 1 | *bit as u8))
+                .collect();
+            result = Share::from_bytes(&bytes);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+}
+
+fn checksum(bytes: &[u8]) -> Vec<u8> {
+    Blake2b256::new(bytes).as_ref()[..CHECKSUM_SIZE].to_vec()
+}
+
+/// split the secret in `shares` shares, `threshold` of them being needed to
+/// recover the secret
+pub fn split<R: Rng>(rng: &mut R, secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>> {
+    if threshold == 0 || threshold > shares {
+        return Err(Error::InvalidParameters {
+            threshold: threshold,
+            shares: shares,
+        });
+    }
+
+    let id: [u8; ID_SIZE] = rng.gen();
+    let mut result: Vec<Share> = (1..=shares)
+        .map(|index| Share {
+            id: id,
+            threshold: threshold,
+            index: index,
+            value: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    for byte in secret {
+        let coefficients: Vec<u8> = (1..threshold).map(|_| rng.gen()).collect();
+        for share in result.iter_mut() {
+            // Horner's method
+            let mut y = 0;
+            for coefficient in coefficients.iter().rev() {
+                y = gf256::add(gf256::mul(y, share.index), *coefficient);
+            }
+            y = gf256::add(gf256::mul(y, share.index), *byte);
+            share.value.push(y);
+        }
+    }
+
+    Ok(result)
+}
+
+/// check the share can be combined with the given ones
+pub fn check_compatible(shares: &[Share], share: &Share) -> Result<()> {
+    for other in shares {
+        if other.id != share.id
+            || other.threshold != share.threshold
+            || other.value.len() != share.value.len()
+        {
+            return Err(Error::MismatchedShare(share.index));
+        }
+        if other.index == share.index {
+            return Err(Error::DuplicateShare(share.index));
+        }
+    }
+    Ok(())
+}
+
+/// recover the secret from (at least `threshold`) shares
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>> {
+    let threshold = match shares.first() {
+        None => {
+            return Err(Error::NotEnoughShares {
+                threshold: 1,
+                shares: 0,
+            })
+        }
+        Some(share) => share.threshold,
+    };
+    for (i, share) in shares.iter().enumerate() {
+        check_compatible(&shares[..i], share)?;
+    }
+    if shares.len() < threshold as usize {
+        return Err(Error::NotEnoughShares {
+            threshold: threshold,
+            shares: shares.len(),
+        });
+    }
+    let shares = &shares[..threshold as usize];
+
+    // the Lagrange basis polynomials evaluated at zero
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |acc, other| {
+                    gf256::mul(
+                        acc,
+                        gf256::div(other.index, gf256::add(other.index, share.index)),
+                    )
+                })
+        })
+        .collect();
+
+    let secret: Vec<u8> = (0..shares[0].value.len())
+        .map(|i| {
+            shares.iter().zip(basis.iter()).fold(0, |acc, (share, l)| {
+                gf256::add(acc, gf256::mul(share.value[i], *l))
+            })
+        })
+        .collect();
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cardano::bip::bip39::dictionary::ENGLISH;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn secret() -> Vec<u8> {
+        (0..32).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn gf256_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf256::mul(a, gf256::inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn any_threshold_shares_recover_the_secret() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let secret = secret();
+        let shares = split(&mut rng, &secret, 3, 5).unwrap();
+
+        for a in 0..5 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let subset = vec![shares[c].clone(), shares[a].clone(), shares[b].clone()];
+                    assert_eq!(combine(&subset).unwrap(), secret);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn not_enough_shares() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let shares = split(&mut rng, &secret(), 3, 5).unwrap();
+
+        assert_eq!(
+            combine(&shares[..2]),
+            Err(Error::NotEnoughShares {
+                threshold: 3,
+                shares: 2
+            })
+        );
+    }
+
+    #[test]
+    fn duplicate_and_mismatched_shares() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let shares = split(&mut rng, &secret(), 2, 3).unwrap();
+        let others = split(&mut rng, &[1; 32], 2, 3).unwrap();
+
+        assert_eq!(
+            combine(&[shares[0].clone(), shares[0].clone()]),
+            Err(Error::DuplicateShare(1))
+        );
+        assert_eq!(
+            combine(&[shares[0].clone(), others[1].clone()]),
+            Err(Error::MismatchedShare(2))
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut rng = StdRng::seed_from_u64(42);
+        assert!(split(&mut rng, &secret(), 0, 3).is_err());
+        assert!(split(&mut rng, &secret(), 4, 3).is_err());
+    }
+
+    #[test]
+    fn words_roundtrip() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let shares = split(&mut rng, &secret(), 2, 3).unwrap();
+
+        for share in shares {
+            let words = share.to_words(&ENGLISH);
+            assert_eq!(Share::from_words(&ENGLISH, &words).unwrap(), share);
+        }
+    }
+
+    #[test]
+    fn words_of_every_entropy_size() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for (size, count) in vec![(16, 18), (20, 21), (24, 24), (28, 27), (32, 30)] {
+            let shares = split(&mut rng, &secret()[..size], 2, 2).unwrap();
+            let words = shares[0].to_words(&ENGLISH);
+            assert_eq!(words.len(), count);
+            assert_eq!(Share::from_words(&ENGLISH, &words).unwrap(), shares[0]);
+            assert_eq!(combine(&shares).unwrap(), &secret()[..size]);
+        }
+    }
+
+    #[test]
+    fn words_checksum() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let shares = split(&mut rng, &secret(), 2, 3).unwrap();
+        let mut words = shares[0].to_words(&ENGLISH);
+        words[3] = if words[3] == "abandon" {
+            "ability".to_owned()
+        } else {
+            "abandon".to_owned()
+        };
+
+        assert_eq!(
+            Share::from_words(&ENGLISH, &words),
+            Err(Error::InvalidChecksum)
+        );
+    }
+}
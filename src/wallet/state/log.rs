@@ -229,6 +229,11 @@ impl LogLock {
         Ok(LogLock { lock, log_path })
     }
 
+    /// tell if the wallet has a log file already
+    pub fn log_exists(&self) -> bool {
+        self.log_path.exists()
+    }
+
     /// Deletes the wallet log file, consuming the lock object.
     pub fn delete_wallet_log(self) -> Result<()> {
         Ok(remove_file(self.log_path)?)
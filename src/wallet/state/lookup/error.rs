@@ -1,4 +1,8 @@
-use cardano::wallet::{bip44, rindex};
+use agent;
+use cardano::{
+    hdwallet,
+    wallet::{bip44, rindex},
+};
 
 use std::{
     error::Error,
@@ -9,6 +13,15 @@ use std::{
 pub enum AddressLookupError {
     RandomIndex(rindex::Error),
     SequentialBip44(bip44::bip44::Error),
+    Agent(agent::Error),
+    /// the soft derivation of an address key from its account key
+    Derivation(hdwallet::DerivationError),
+}
+
+impl From<agent::Error> for AddressLookupError {
+    fn from(e: agent::Error) -> Self {
+        AddressLookupError::Agent(e)
+    }
 }
 
 impl Display for AddressLookupError {
@@ -18,6 +31,8 @@ impl Display for AddressLookupError {
         match self {
             RandomIndex(e) => e.fmt(f),
             SequentialBip44(e) => e.fmt(f),
+            Agent(e) => e.fmt(f),
+            Derivation(e) => write!(f, "cannot derive the address key: {:?}", e),
         }
     }
 }
@@ -27,6 +42,8 @@ impl Error for AddressLookupError {
         match self {
             AddressLookupError::RandomIndex(err) => err.cause(),
             AddressLookupError::SequentialBip44(err) => err.cause(),
+            AddressLookupError::Agent(err) => err.cause(),
+            AddressLookupError::Derivation(_) => None,
         }
     }
 }
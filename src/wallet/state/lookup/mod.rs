@@ -7,6 +7,7 @@ mod error;
 pub mod accum;
 pub mod randomindex;
 pub mod sequentialindex;
+pub mod singleaddress;
 
 pub use self::address::Address;
 pub use self::error::AddressLookupError;
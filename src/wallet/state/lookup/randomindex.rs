@@ -1,38 +1,139 @@
-use cardano::config::NetworkMagic;
-use cardano::hdwallet;
+use cardano::config::{NetworkMagic, ProtocolMagic};
 use cardano::wallet::rindex;
-use cardano::{address::ExtendedAddr, hdwallet::XPrv};
+use cardano::{
+    address::ExtendedAddr,
+    hdpayload,
+    hdwallet::{self, Signature, XPub},
+    tx::{TxId, TxInWitness},
+};
 
 use super::super::utxo::UTxO;
 use super::{Address, AddressLookup, AddressLookupError};
+use agent::RemoteWallet;
+use utils::secret::Wiped;
+
+/// where the keys of the wallet are
+enum Keys {
+    /// the decrypted wallet
+    Wallet(Wiped<rindex::AddressGenerator<hdwallet::XPrv>>),
+    /// the key agent, doing the derivations and the signatures; the key to
+    /// decrypt the derivation paths of the addresses is derived from the
+    /// root public key, it does not need the agent.
+    Agent {
+        wallet: RemoteWallet,
+        hdkey: hdpayload::HDKey,
+    },
+}
 
 pub struct RandomIndexLookup {
-    generator: rindex::AddressGenerator<hdwallet::XPrv>,
+    // the root private key is wiped from memory when the lookup structure is
+    // dropped, and the derived private keys once used.
+    keys: Keys,
     network_magic: NetworkMagic,
 }
 impl RandomIndexLookup {
     pub fn from_wallet(wallet: rindex::Wallet, network_magic: NetworkMagic) -> Self {
-        RandomIndexLookup {
-            generator: wallet.address_generator(),
-            network_magic: network_magic,
-        }
+        Self::new(wallet.address_generator(), network_magic)
     }
     pub fn new(
         generator: rindex::AddressGenerator<hdwallet::XPrv>,
         network_magic: NetworkMagic,
     ) -> Self {
         RandomIndexLookup {
-            generator,
+            keys: Keys::Wallet(Wiped::new(generator)),
+            network_magic,
+        }
+    }
+
+    /// the lookup structure of a wallet whose key is held by the key agent
+    pub fn with_agent(
+        wallet: RemoteWallet,
+        network_magic: NetworkMagic,
+    ) -> Result<Self, AddressLookupError> {
+        let hdkey = hdpayload::HDKey::new(&wallet.root_public_key()?);
+        Ok(RandomIndexLookup {
+            keys: Keys::Agent { wallet, hdkey },
             network_magic,
+        })
+    }
+
+    pub fn get_public_key(&self, addr: &rindex::Addressing) -> Result<XPub, AddressLookupError> {
+        match &self.keys {
+            Keys::Wallet(generator) => Ok(Wiped::new(generator.key(addr)).public()),
+            Keys::Agent { wallet, .. } => Ok(wallet.public_key(&addr.into())?),
+        }
+    }
+
+    pub fn get_address(
+        &self,
+        addr: &rindex::Addressing,
+    ) -> Result<ExtendedAddr, AddressLookupError> {
+        match &self.keys {
+            Keys::Wallet(generator) => Ok(generator.address(addr, self.network_magic)),
+            Keys::Agent { wallet, .. } => Ok(wallet.address(&addr.into(), self.network_magic)?),
+        }
+    }
+
+    /// sign the data with the private key of the given address
+    pub fn sign<T>(
+        &self,
+        addr: &rindex::Addressing,
+        data: &[u8],
+    ) -> Result<Signature<T>, AddressLookupError> {
+        match &self.keys {
+            Keys::Wallet(generator) => Ok(Wiped::new(generator.key(addr)).sign(data)),
+            Keys::Agent { wallet, .. } => Ok(wallet.sign(&addr.into(), data)?),
         }
     }
 
-    pub fn get_private_key(&self, addr: &rindex::Addressing) -> XPrv {
-        self.generator.key(addr)
+    /// the witness of a transaction's input spending from the given address
+    pub fn sign_tx(
+        &self,
+        protocol_magic: ProtocolMagic,
+        addr: &rindex::Addressing,
+        txid: &TxId,
+    ) -> Result<TxInWitness, AddressLookupError> {
+        match &self.keys {
+            Keys::Wallet(generator) => {
+                let xprv = Wiped::new(generator.key(addr));
+                Ok(TxInWitness::new(protocol_magic, &xprv, txid))
+            }
+            Keys::Agent { wallet, .. } => Ok(wallet.sign_tx(&addr.into(), protocol_magic, txid)?),
+        }
     }
 
-    pub fn get_address(&self, addr: &rindex::Addressing) -> ExtendedAddr {
-        self.generator.address(addr, self.network_magic)
+    fn try_get_addressing(
+        &self,
+        address: &ExtendedAddr,
+    ) -> Result<Option<rindex::Addressing>, AddressLookupError> {
+        match &self.keys {
+            Keys::Wallet(generator) => match generator.try_get_addressing(address) {
+                Ok(addressing) => Ok(addressing),
+                Err(rindex::Error::PayloadError(hdpayload::Error::PayloadIsTooLarge(_))) => {
+                    Ok(None)
+                }
+                Err(rindex::Error::PayloadError(hdpayload::Error::NotEnoughEncryptedData)) => {
+                    Ok(None)
+                }
+                Err(err) => Err(err.into()),
+            },
+            Keys::Agent { hdkey, .. } => {
+                let payload = match address.attributes.derivation_path {
+                    None => return Ok(None),
+                    Some(ref payload) => payload,
+                };
+                // the payloads not encrypted with our key are not ours
+                let path = match hdkey.decrypt_path(payload) {
+                    Ok(path) => path,
+                    Err(_) => return Ok(None),
+                };
+                let path = path.as_ref();
+                if path.len() != 2 {
+                    return Ok(None);
+                }
+                Ok(Some(rindex::Addressing::new(path[0], path[1])))
+            }
+        }
     }
 }
 
@@ -52,19 +153,12 @@ impl AddressLookup for RandomIndexLookup {
         &mut self,
         utxo: UTxO<ExtendedAddr>,
     ) -> Result<Option<UTxO<Address>>, AddressLookupError> {
-        use cardano::hdpayload;
-        use cardano::wallet::rindex;
-        let opt_addressing = match self.generator.try_get_addressing(&utxo.credited_address) {
-            Ok(addressing) => addressing,
-            Err(rindex::Error::PayloadError(hdpayload::Error::PayloadIsTooLarge(_))) => None,
-            Err(rindex::Error::PayloadError(hdpayload::Error::NotEnoughEncryptedData)) => None,
-            Err(err) => return Err(err.into()),
-        };
+        let opt_addressing = self.try_get_addressing(&utxo.credited_address)?;
 
         match opt_addressing {
             None => Ok(None),
             Some(addressing) => {
-                let address = self.get_address(&addressing);
+                let address = self.get_address(&addressing)?;
 
                 if address != utxo.credited_address {
                     debug!("credited address:    {}", utxo.credited_address);
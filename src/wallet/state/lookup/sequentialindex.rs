@@ -1,13 +1,16 @@
-use cardano::config::NetworkMagic;
+use cardano::config::{NetworkMagic, ProtocolMagic};
 use cardano::wallet::bip44;
 use cardano::{
     address::{Addr, ExtendedAddr},
-    hdwallet::XPrv,
+    hdwallet::{Signature, XPrv, XPub},
+    tx::{TxId, TxInWitness},
 };
-use std::collections::BTreeMap;
+use std::{cell::RefCell, collections::BTreeMap};
 
 use super::super::utxo::UTxO;
 use super::{Address, AddressLookup, AddressLookupError};
+use agent::RemoteWallet;
+use utils::secret::Wiped;
 
 lazy_static! {
     pub static ref DEFAULT_GAP_LIMIT: u32 = {
@@ -22,15 +25,31 @@ lazy_static! {
 
 type Result<T> = std::result::Result<T, AddressLookupError>;
 
+/// where the keys of the wallet are
+enum Keys {
+    /// the decrypted wallet
+    Wallet(Wiped<bip44::Wallet>),
+    /// the key agent, doing the signatures: the keys of the addresses are
+    /// derived here from the public keys of the accounts (asked once to the
+    /// agent and cached)
+    Agent {
+        wallet: RemoteWallet,
+        accounts: RefCell<BTreeMap<u32, XPub>>,
+    },
+}
+
 pub struct SequentialBip44Lookup {
     // cryptographic wallet
     //
     // downside of needed the bip44's wallet is that we need to decrypt the
-    // wallet private key with the password. This is needed because we might need
-    // to create new addresses and they need hard derivation (which cannot be
-    // done through the public key).
+    // wallet private key with the password (unless the key agent holds it).
+    // This is needed because we might need to create new addresses and they
+    // need hard derivation (which cannot be done through the public key).
+    //
+    // the root private key is wiped from memory when the lookup structure
+    // is dropped, and the derived private keys once used.
     //
-    wallet: bip44::Wallet,
+    keys: Keys,
     // all the known expected addresses, that includes
     // all different accounts, and also the next not yet live
     // account's addresses
@@ -47,8 +66,23 @@ pub struct SequentialBip44Lookup {
 
 impl SequentialBip44Lookup {
     pub fn new(wallet: bip44::Wallet, network_magic: NetworkMagic) -> Self {
+        Self::with_keys(Keys::Wallet(Wiped::new(wallet)), network_magic)
+    }
+
+    /// the lookup structure of a wallet whose key is held by the key agent
+    pub fn with_agent(wallet: RemoteWallet, network_magic: NetworkMagic) -> Self {
+        Self::with_keys(
+            Keys::Agent {
+                wallet: wallet,
+                accounts: RefCell::new(BTreeMap::new()),
+            },
+            network_magic,
+        )
+    }
+
+    fn with_keys(keys: Keys, network_magic: NetworkMagic) -> Self {
         SequentialBip44Lookup {
-            wallet: wallet,
+            keys: keys,
             expected: BTreeMap::new(),
             accounts: Vec::new(),
             gap_limit: *DEFAULT_GAP_LIMIT,
@@ -56,23 +90,66 @@ impl SequentialBip44Lookup {
         }
     }
 
-    pub fn get_private_key(&self, addr: &bip44::Addressing) -> bip44::IndexLevel<XPrv> {
-        self.wallet
-            .account(
-                self.wallet.derivation_scheme(),
-                addr.account.get_scheme_value(),
-            )
-            .change(self.wallet.derivation_scheme(), addr.address_type())
-            .index(
-                self.wallet.derivation_scheme(),
-                addr.index.get_scheme_value(),
-            )
+    fn get_private_key(
+        wallet: &bip44::Wallet,
+        addr: &bip44::Addressing,
+    ) -> Wiped<bip44::IndexLevel<XPrv>> {
+        let account =
+            Wiped::new(wallet.account(wallet.derivation_scheme(), addr.account.get_scheme_value()));
+        let change = Wiped::new(account.change(wallet.derivation_scheme(), addr.address_type()));
+        Wiped::new(change.index(wallet.derivation_scheme(), addr.index.get_scheme_value()))
+    }
+
+    pub fn get_public_key(&self, addr: &bip44::Addressing) -> Result<XPub> {
+        match &self.keys {
+            Keys::Wallet(wallet) => Ok(*Self::get_private_key(wallet, addr).public()),
+            Keys::Agent { wallet, accounts } => {
+                let number = addr.account.get_account_number();
+                let cached = accounts.borrow().get(&number).cloned();
+                let account = match cached {
+                    Some(account) => account,
+                    None => {
+                        let account = wallet.account_public_key(number)?;
+                        accounts.borrow_mut().insert(number, account);
+                        account
+                    }
+                };
+                let scheme = wallet.derivation_scheme();
+                account
+                    .derive(scheme, addr.change)
+                    .and_then(|change| change.derive(scheme, addr.index.get_scheme_value()))
+                    .map_err(AddressLookupError::Derivation)
+            }
+        }
+    }
+
+    pub fn get_address(&self, addr: &bip44::Addressing) -> Result<ExtendedAddr> {
+        let xpub = self.get_public_key(addr)?;
+        Ok(ExtendedAddr::new_simple(xpub, self.network_magic))
     }
 
-    pub fn get_address(&self, addr: &bip44::Addressing) -> ExtendedAddr {
-        let xprv = self.get_private_key(addr);
-        let xpub = xprv.public();
-        ExtendedAddr::new_simple(*xpub, self.network_magic)
+    /// sign the data with the private key of the given address
+    pub fn sign<T>(&self, addr: &bip44::Addressing, data: &[u8]) -> Result<Signature<T>> {
+        match &self.keys {
+            Keys::Wallet(wallet) => Ok(Self::get_private_key(wallet, addr).sign(data)),
+            Keys::Agent { wallet, .. } => Ok(wallet.sign(&addr.into(), data)?),
+        }
+    }
+
+    /// the witness of a transaction's input spending from the given address
+    pub fn sign_tx(
+        &self,
+        protocol_magic: ProtocolMagic,
+        addr: &bip44::Addressing,
+        txid: &TxId,
+    ) -> Result<TxInWitness> {
+        match &self.keys {
+            Keys::Wallet(wallet) => {
+                let xprv = Self::get_private_key(wallet, addr);
+                Ok(TxInWitness::new(protocol_magic, &**xprv, txid))
+            }
+            Keys::Agent { wallet, .. } => Ok(wallet.sign_tx(&addr.into(), protocol_magic, txid)?),
+        }
     }
 
     fn mut_generate_from(
@@ -91,7 +168,7 @@ impl SequentialBip44Lookup {
                 change: change,
                 index: r,
             };
-            let addr = self.get_address(&addressing);
+            let addr = self.get_address(&addressing)?;
             self.expected.insert(addr.into(), addressing);
             r = r.incr(1)?;
         }
@@ -0,0 +1,34 @@
+use super::super::utxo::UTxO;
+use super::{Address, AddressLookup, AddressLookupError};
+use cardano::address::ExtendedAddr;
+
+/// lookup structure recognizing the UTxOs of a single given address
+///
+/// Useful to retrieve the funds of a standalone key, not managed by any
+/// wallet (e.g. the bootstrap address of a raw `XPrv`).
+pub struct SingleAddressLookup(ExtendedAddr);
+impl SingleAddressLookup {
+    pub fn new(address: ExtendedAddr) -> Self {
+        SingleAddressLookup(address)
+    }
+}
+
+impl AddressLookup for SingleAddressLookup {
+    fn lookup(
+        &mut self,
+        utxo: UTxO<ExtendedAddr>,
+    ) -> Result<Option<UTxO<Address>>, AddressLookupError> {
+        if utxo.credited_address == self.0 {
+            Ok(Some(utxo.map(|a| a.into())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn acknowledge<A>(&mut self, _: A) -> Result<(), AddressLookupError>
+    where
+        A: Into<Address>,
+    {
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@
 //! manipulate wallets, load filter, or even create.
 //!
 
+use super::address_pool::{self, AddressPool};
 use super::config::{Config, HDWalletModel};
 use super::error::{Error, Result};
 use super::state::{iter::TransactionIterator, log, lookup, ptr, ptr::StatePtr, state, utxo::UTxO};
@@ -11,16 +12,23 @@ use super::Wallet;
 
 use cardano::{
     address::ExtendedAddr,
+    bip::bip44,
     block::BlockDate,
+    coin::Coin,
     config::{NetworkMagic, ProtocolMagic},
+    hash::Blake2b256,
+    hdwallet::XPub,
     tx::{TxId, TxInWitness},
+    wallet::rindex,
 };
+use rand::random;
 
 use utils::term::{style::Style, Term};
 
+use agent;
 use blockchain::Blockchain;
 
-use std::{io::Write, path::Path};
+use std::{collections::HashMap, io::Write, path::Path};
 
 pub fn update_wallet_state_with_utxos<LS>(
     term: &mut Term,
@@ -112,6 +120,180 @@ pub fn update_wallet_state_with_utxos<LS>(
     }
 }
 
+/// update the given state with the transactions of the blockchain, without
+/// writing anything in a wallet log (e.g. for addresses not managed by
+/// a wallet).
+pub fn scan_blockchain_utxos<LS>(
+    term: &mut Term,
+    blockchain: &Blockchain,
+    state: &mut state::State<LS>,
+) where
+    LS: lookup::AddressLookup,
+{
+    let blockchain_tip = blockchain.load_tip().0;
+
+    let from_ptr = state.ptr().clone();
+    let from = from_ptr.latest_known_hash;
+    let from_date = from_ptr.latest_addr.unwrap_or(BlockDate::Boundary(0));
+    let num_blocks = blockchain_tip.date - from_date;
+
+    term.info(&format!(
+        "scanning blockchain from {} to {}\n",
+        from_date, blockchain_tip.date
+    ))
+    .unwrap();
+
+    let progress = term.progress_bar(num_blocks as u64);
+    progress.set_message("loading transactions... ");
+
+    for res in TransactionIterator::new(
+        progress,
+        blockchain
+            .iter_to_tip(from)
+            .unwrap_or_else(|e| term.fail_with(e)),
+    ) {
+        let (ptr, txaux) = res.unwrap_or_else(|e| term.fail_with(e));
+
+        state
+            .forward_with_txins(txaux.tx.inputs.iter().map(|txin| (ptr.clone(), txin)))
+            .unwrap_or_else(|e| term.fail_with(e));
+
+        let txid = txaux.tx.id();
+        state
+            .forward_with_utxos(
+                txaux
+                    .tx
+                    .outputs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, txout)| {
+                        (
+                            ptr.clone(),
+                            UTxO {
+                                transaction_id: txid.clone(),
+                                index_in_transaction: idx as u32,
+                                credited_address: txout.address.clone(),
+                                credited_addressing: txout.address,
+                                credited_value: txout.value,
+                            },
+                        )
+                    }),
+            )
+            .unwrap_or_else(|e| term.fail_with(e));
+    }
+}
+
+/// count, for each of the given lookup structures, the number of outputs of
+/// the whole blockchain it recognizes (in a single pass over the blockchain)
+pub fn count_recognized_outputs<LS>(
+    term: &mut Term,
+    blockchain: &Blockchain,
+    lookups: &mut [LS],
+) -> Vec<usize>
+where
+    LS: lookup::AddressLookup,
+{
+    let blockchain_tip = blockchain.load_tip().0;
+    let mut counts = vec![0; lookups.len()];
+
+    let num_blocks = blockchain_tip.date - BlockDate::Boundary(0);
+    let progress = term.progress_bar(num_blocks as u64);
+    progress.set_message("looking for known outputs... ");
+
+    for res in TransactionIterator::new(
+        progress,
+        blockchain
+            .iter_to_tip(blockchain.config.genesis.clone())
+            .unwrap_or_else(|e| term.fail_with(e)),
+    ) {
+        let (_, txaux) = res.unwrap_or_else(|e| term.fail_with(e));
+        let txid = txaux.tx.id();
+
+        for (idx, txout) in txaux.tx.outputs.iter().enumerate() {
+            for (lookup, count) in lookups.iter_mut().zip(counts.iter_mut()) {
+                let utxo = UTxO {
+                    transaction_id: txid.clone(),
+                    index_in_transaction: idx as u32,
+                    credited_address: txout.address.clone(),
+                    credited_addressing: txout.address.clone(),
+                    credited_value: txout.value,
+                };
+                if lookup
+                    .lookup(utxo)
+                    .unwrap_or_else(|e| term.fail_with(e))
+                    .is_some()
+                {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    counts
+}
+
+/// count, for each of the given addresses, the number of outputs of the whole
+/// blockchain it received (in a single pass over the blockchain)
+///
+/// Unlike `count_recognized_outputs`, each output is only looked up once,
+/// whatever the number of addresses.
+pub fn count_outputs_to_addresses(
+    term: &mut Term,
+    blockchain: &Blockchain,
+    addresses: &[ExtendedAddr],
+) -> Vec<usize> {
+    let blockchain_tip = blockchain.load_tip().0;
+    let mut indices: HashMap<&ExtendedAddr, Vec<usize>> = HashMap::new();
+    for (index, address) in addresses.iter().enumerate() {
+        indices.entry(address).or_insert_with(Vec::new).push(index);
+    }
+    let mut counts = vec![0; addresses.len()];
+
+    let num_blocks = blockchain_tip.date - BlockDate::Boundary(0);
+    let progress = term.progress_bar(num_blocks as u64);
+    progress.set_message("looking for known outputs... ");
+
+    for res in TransactionIterator::new(
+        progress,
+        blockchain
+            .iter_to_tip(blockchain.config.genesis.clone())
+            .unwrap_or_else(|e| term.fail_with(e)),
+    ) {
+        let (_, txaux) = res.unwrap_or_else(|e| term.fail_with(e));
+        for txout in txaux.tx.outputs.iter() {
+            if let Some(indices) = indices.get(&txout.address) {
+                for index in indices {
+                    counts[*index] += 1;
+                }
+            }
+        }
+    }
+
+    counts
+}
+
+/// tell whether the lookup structure recognizes the given address as one of
+/// the wallet's
+pub fn recognizes_address<LS>(lookup: &mut LS, address: &ExtendedAddr) -> bool
+where
+    LS: lookup::AddressLookup,
+{
+    let utxo = UTxO {
+        transaction_id: TxId::new(&[]),
+        index_in_transaction: 0,
+        credited_address: address.clone(),
+        credited_addressing: address.clone(),
+        credited_value: Coin::zero(),
+    };
+    match lookup.lookup(utxo) {
+        Ok(found) => found.is_some(),
+        Err(err) => {
+            debug!("address {} not recognized: {}", address, err);
+            false
+        }
+    }
+}
+
 pub fn display_wallet_state_utxos<LS>(term: &mut Term, state: state::State<LS>)
 where
     LS: lookup::AddressLookup,
@@ -278,6 +460,11 @@ pub fn load_bip44_lookup_structure(
     // TODO: to prevent from the need of the password, we can ask the user to create accounts ahead.
     //       if we store the wallet's account public keys in the config file we may not need for the
     //       password (and for the private key).
+    if let Some(remote) = agent::unlocked_wallet(wallet) {
+        debug!("using the key agent for the wallet `{}'", wallet.name);
+        return lookup::sequentialindex::SequentialBip44Lookup::with_agent(remote, network_magic);
+    }
+
     term.info("Enter the wallet password.\n").unwrap();
     let password = term.password("wallet password: ").unwrap();
 
@@ -309,6 +496,14 @@ pub fn load_randomindex_lookup_structure(
     wallet: &Wallet,
 ) -> lookup::randomindex::RandomIndexLookup {
     // in the case of the random index, we may not need the password if we have the public key
+    if let Some(remote) = agent::unlocked_wallet(wallet) {
+        debug!("using the key agent for the wallet `{}'", wallet.name);
+        match lookup::randomindex::RandomIndexLookup::with_agent(remote, network_magic) {
+            Ok(lookup_struct) => return lookup_struct,
+            Err(err) => warn!("cannot use the key agent: {}", err),
+        }
+    }
+
     term.info("Enter the wallet password.\n").unwrap();
     let password = term.password("wallet password: ").unwrap();
 
@@ -335,6 +530,291 @@ pub fn load_randomindex_lookup_structure(
     lookup::randomindex::RandomIndexLookup::from_wallet(wallet, network_magic)
 }
 
+/// get a fresh change address for the given wallet
+///
+/// * for BIP44 wallets: the internal address of the first account following
+///   the latest internal address seen in the wallet's log or issued already
+///   (the address is recorded in the wallet's address pool);
+/// * for random index wallets: an address with a new random addressing.
+///
+/// The wallet password will be asked in order to derive the address.
+pub fn wallet_next_change_address(
+    term: &mut Term,
+    wallet: &Wallet,
+    network_magic: NetworkMagic,
+) -> Result<ExtendedAddr> {
+    let mut addresses = wallet_next_change_addresses(term, wallet, network_magic, 1)?;
+    Ok(addresses.remove(0))
+}
+
+/// same as `wallet_next_change_address` but get `count` distinct fresh
+/// change addresses (the wallet password is asked only once).
+pub fn wallet_next_change_addresses(
+    term: &mut Term,
+    wallet: &Wallet,
+    network_magic: NetworkMagic,
+    count: u32,
+) -> Result<Vec<ExtendedAddr>> {
+    let lookup = WalletLookup::new(term, wallet, network_magic);
+    ChangeAddresses::new(wallet, &lookup).next(count)
+}
+
+/// the lookup structure of an unlocked wallet, to derive its addresses and
+/// sign with its keys without asking for the password again
+pub enum WalletLookup {
+    Bip44(lookup::sequentialindex::SequentialBip44Lookup),
+    RandomIndex(lookup::randomindex::RandomIndexLookup),
+}
+impl WalletLookup {
+    /// unlock the wallet, the wallet password is asked (unless the key
+    /// agent holds the wallet's key)
+    pub fn new(term: &mut Term, wallet: &Wallet, network_magic: NetworkMagic) -> Self {
+        match wallet.config.hdwallet_model {
+            HDWalletModel::BIP44 => {
+                WalletLookup::Bip44(load_bip44_lookup_structure(term, network_magic, wallet))
+            }
+            HDWalletModel::RandomIndex2Levels => WalletLookup::RandomIndex(
+                load_randomindex_lookup_structure(term, network_magic, wallet),
+            ),
+        }
+    }
+
+    /// sign the given transaction for all the given addresses
+    pub fn sign_tx_inputs(
+        &self,
+        protocol_magic: ProtocolMagic,
+        txid: &TxId,
+        addresses: &[lookup::Address],
+    ) -> Result<Vec<TxInWitness>> {
+        match self {
+            WalletLookup::Bip44(wallet) => addresses
+                .iter()
+                .map(|address| {
+                    if let lookup::Address::Bip44(addressing) = address {
+                        Ok(wallet.sign_tx(protocol_magic, addressing, txid)?)
+                    } else {
+                        Err(Error::WalletAddressingMismatch)
+                    }
+                })
+                .collect(),
+            WalletLookup::RandomIndex(wallet) => addresses
+                .iter()
+                .map(|address| {
+                    if let lookup::Address::RIndex(addressing) = address {
+                        Ok(wallet.sign_tx(protocol_magic, addressing, txid)?)
+                    } else {
+                        Err(Error::WalletAddressingMismatch)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// source of fresh change addresses for the given wallet, see
+/// `wallet_next_change_address`.
+pub struct ChangeAddresses<'a> {
+    wallet: &'a Wallet,
+    lookup: &'a WalletLookup,
+    /// the internal addresses from this index on are not expected to
+    /// receive funds (before the next batch of addresses)
+    unused_from: Option<u32>,
+    /// the BIP44 addresses derived but not issued yet, see `derive`
+    derived: Vec<(ExtendedAddr, bip44::Addressing)>,
+}
+impl<'a> ChangeAddresses<'a> {
+    /// `lookup` is the lookup structure of `wallet`
+    pub fn new(wallet: &'a Wallet, lookup: &'a WalletLookup) -> Self {
+        ChangeAddresses {
+            wallet: wallet,
+            lookup: lookup,
+            unused_from: None,
+            derived: Vec::new(),
+        }
+    }
+
+    /// get `count` distinct fresh change addresses, for one transaction
+    ///
+    /// For BIP44 wallets, the addresses of a batch are expected to receive
+    /// funds before the addresses of the next batch: no more addresses than
+    /// the gap limit can be unused, otherwise the wallet would not find the
+    /// funds sent to them.
+    pub fn next(&mut self, count: u32) -> Result<Vec<ExtendedAddr>> {
+        let addresses = self.derive(count)?;
+        self.issue()?;
+        Ok(addresses)
+    }
+
+    /// same as `next`, but the addresses are only issued by `issue`: until
+    /// then the next addresses derived are the same.
+    pub fn derive(&mut self, count: u32) -> Result<Vec<ExtendedAddr>> {
+        use super::state::lookup::sequentialindex::DEFAULT_GAP_LIMIT;
+
+        match *self.lookup {
+            WalletLookup::Bip44(ref lookup_struct) => {
+                let log_lock = lock_wallet_log(self.wallet);
+                let (entries, _log_lock) = read_wallet_log_locked(log_lock)?;
+                let pool = AddressPool::load(self.wallet)?;
+                let next_index = pool.next_bip44_index(&entries, 1);
+
+                let unused_from = self
+                    .unused_from
+                    .unwrap_or_else(|| address_pool::next_logged_bip44_index(&entries, 1));
+                if next_index + count > unused_from + *DEFAULT_GAP_LIMIT {
+                    return Err(Error::ChangeAddressesGapLimitReached(*DEFAULT_GAP_LIMIT));
+                }
+
+                let account = bip44::Account::new(0)?;
+                self.derived = (next_index..next_index + count)
+                    .map(|index| {
+                        let addressing = account.internal()?.index(index)?;
+                        let address = lookup_struct.get_address(&addressing)?;
+                        Ok((address, addressing))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                self.unused_from = Some(next_index + count);
+                Ok(self
+                    .derived
+                    .iter()
+                    .map(|(address, _)| address.clone())
+                    .collect())
+            }
+            WalletLookup::RandomIndex(ref lookup_struct) => {
+                // Daedalus uses hardened indices for both levels
+                (0..count)
+                    .map(|_| {
+                        let addressing = rindex::Addressing::new(
+                            0x8000_0000 | random::<u32>(),
+                            0x8000_0000 | random::<u32>(),
+                        );
+                        Ok(lookup_struct.get_address(&addressing)?)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// issue the addresses of the last `derive`: they are recorded in the
+    /// address pool, so the next transactions do not reuse them before they
+    /// appear in the log (the random index addresses need no record).
+    pub fn issue(&mut self) -> Result<()> {
+        if self.derived.is_empty() {
+            return Ok(());
+        }
+        let _log_lock = lock_wallet_log(self.wallet);
+        let mut pool = AddressPool::load(self.wallet)?;
+        for (address, addressing) in self.derived.drain(..) {
+            pool.issue(address, addressing.into());
+        }
+        pool.save(self.wallet)
+    }
+}
+
+/// find the derivation path of the given address with the lookup structure
+pub fn lookup_address<LS>(
+    lookup_struct: &mut LS,
+    address: ExtendedAddr,
+) -> Result<Option<lookup::Address>>
+where
+    LS: lookup::AddressLookup,
+{
+    // the lookup structures only work on UTxOs
+    let utxo = UTxO {
+        transaction_id: TxId::new(&[]),
+        index_in_transaction: 0,
+        credited_address: address.clone(),
+        credited_addressing: address,
+        credited_value: Coin::zero(),
+    };
+    Ok(lookup_struct
+        .lookup(utxo)?
+        .map(|utxo| utxo.credited_addressing))
+}
+
+/// parse a derivation path of the form `0'/1/42` (the `'` (or `h`) marks
+/// the hardened indices), an optional `m/` prefix is ignored.
+pub fn parse_derivation_path(path: &str) -> Option<Vec<u32>> {
+    let path = path.trim_start_matches("m/");
+    path.split('/')
+        .map(|component| {
+            let (component, hardened) = if component.ends_with('\'') || component.ends_with('h') {
+                (&component[..component.len() - 1], true)
+            } else {
+                (component, false)
+            };
+            let index = component.parse::<u32>().ok()?;
+            if hardened {
+                if index >= 0x8000_0000 {
+                    return None;
+                }
+                Some(0x8000_0000 | index)
+            } else {
+                Some(index)
+            }
+        })
+        .collect()
+}
+
+/// the data signed by `wallet sign-message`, the prefix prevents from
+/// signing anything that could be taken for a transaction
+pub fn message_signing_data(message: &str) -> Vec<u8> {
+    let mut data = b"Cardano Signed Message:\n".to_vec();
+    data.extend_from_slice(message.as_bytes());
+    data
+}
+
+/// the checksum printed on paper wallets: the first 4 bytes of the
+/// Blake2b256 hash of the mnemonic phrase, allowing to check the words have
+/// been typed in correctly before recovering the wallet
+pub fn paper_wallet_checksum(phrase: &str) -> String {
+    short_digest(phrase.as_bytes())
+}
+
+/// the fingerprint of a wallet: the first 4 bytes of the Blake2b256 hash of
+/// its root public key, allowing to tell wallets apart without revealing
+/// their public key
+pub fn wallet_fingerprint(root_public_key: &XPub) -> String {
+    short_digest(root_public_key.as_ref())
+}
+
+fn short_digest(data: &[u8]) -> String {
+    Blake2b256::new(data).as_ref()[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// read all the entries of the wallet's log (none if the wallet has never
+/// been synced)
+pub fn read_wallet_log(wallet: &Wallet) -> Result<Vec<log::Log<lookup::Address>>> {
+    let log_lock = lock_wallet_log(wallet);
+    match log::LogReader::open(log_lock) {
+        Err(log::Error::LogNotFound) => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+        Ok(reader) => {
+            let reader: log::LogIterator<lookup::Address> = reader.into_iter();
+            reader.map(|entry| entry.map_err(Error::from)).collect()
+        }
+    }
+}
+
+/// same as `read_wallet_log` but with the lock of the wallet's log already
+/// acquired: the lock is given back so it can be kept while updating files
+/// which must be consistent with the log (e.g. the address pool).
+pub fn read_wallet_log_locked(
+    log_lock: log::LogLock,
+) -> Result<(Vec<log::Log<lookup::Address>>, log::LogLock)> {
+    if !log_lock.log_exists() {
+        return Ok((Vec::new(), log_lock));
+    }
+    let mut reader = log::LogReader::open(log_lock)?;
+    let mut entries = Vec::new();
+    while let Some(entry) = reader.next()? {
+        entries.push(entry);
+    }
+    Ok((entries, reader.release_lock()))
+}
+
 pub fn lock_wallet_log(wallet: &Wallet) -> log::LogLock {
     match wallet.log() {
         Err(Error::WalletLogAlreadyLocked(pid)) => {
@@ -378,27 +858,25 @@ pub fn wallet_sign_tx(
     protocol_magic: ProtocolMagic,
     txid: &TxId,
     address: &lookup::Address,
-) -> TxInWitness {
-    match wallet.config.hdwallet_model {
-        HDWalletModel::BIP44 => {
-            let wallet = load_bip44_lookup_structure(term, protocol_magic.into(), wallet);
-            if let lookup::Address::Bip44(addressing) = address {
-                let xprv = wallet.get_private_key(addressing);
-                TxInWitness::new(protocol_magic, &*xprv, txid)
-            } else {
-                panic!()
-            }
-        }
-        HDWalletModel::RandomIndex2Levels => {
-            let wallet = load_randomindex_lookup_structure(term, protocol_magic.into(), wallet);
-            if let lookup::Address::RIndex(addressing) = address {
-                let xprv = wallet.get_private_key(addressing);
-                TxInWitness::new(protocol_magic, &xprv, txid)
-            } else {
-                panic!()
-            }
-        }
-    }
+) -> Result<TxInWitness> {
+    Ok(wallet_sign_tx_inputs(term, wallet, protocol_magic, txid, &[address.clone()])?.remove(0))
+}
+
+/// sign the given transaction for all the given addresses at once, the
+/// wallet password is asked only once (not at all if the key agent holds
+/// the wallet's key).
+pub fn wallet_sign_tx_inputs(
+    term: &mut Term,
+    wallet: &Wallet,
+    protocol_magic: ProtocolMagic,
+    txid: &TxId,
+    addresses: &[lookup::Address],
+) -> Result<Vec<TxInWitness>> {
+    WalletLookup::new(term, wallet, protocol_magic.into()).sign_tx_inputs(
+        protocol_magic,
+        txid,
+        addresses,
+    )
 }
 
 const IMPOSSIBLE_HAPPENED: &'static str = "The impossible happened